@@ -133,7 +133,7 @@ async fn main(spawner: Spawner) {
         let conn = unwrap!(ble.connect(&config).await);
         info!("Connected, creating gatt client");
 
-        let mut client = ble.gatt_client::<10, 128>(&conn).await.unwrap();
+        let mut client = ble.gatt_client::<10>(&conn).await.unwrap();
 
         info!("Looking for battery service");
         let services = unwrap!(client.services_by_uuid(&Uuid::new_short(0x180f)).await);
@@ -0,0 +1,81 @@
+//! Procedural macros for declaring GATT services with `trouble-host`.
+//!
+//! These macros are re-exported by `trouble-host` when the `derive` feature is enabled.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, Expr, ItemStruct};
+
+mod service;
+mod uuid;
+
+/// Declare a GATT service as a struct of characteristics.
+///
+/// Each field is a characteristic annotated with `#[characteristic(uuid = ..., <props>)]`, where
+/// the properties are any of `broadcast`, `read`, `write_without_response`, `write`, `notify`,
+/// `indicate` and `authenticated_write`. The field type must implement `GattValue`, and its maximum
+/// size determines the size of the value storage.
+///
+/// UUIDs can be given as 16-bit or 128-bit strings (`"180f"`, `"0000180f-0000-1000-8000-00805f9b34fb"`),
+/// or as any expression convertible into a `Uuid`.
+///
+/// ```no_run
+/// # use bt_hci::controller::Controller;
+/// # use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+/// # use static_cell::StaticCell;
+/// # use trouble_host::attribute::AttributeTable;
+/// # use trouble_host::gatt::{GattClient, GattEvent};
+/// # use trouble_host::{gatt_service, BleHostError};
+/// #[gatt_service(uuid = "180f")]
+/// struct BatteryService {
+///     #[characteristic(uuid = "2a19", read, notify)]
+///     level: u8,
+/// }
+///
+/// # fn server(mut table: AttributeTable<'static, NoopRawMutex, 10>, event: GattEvent<'_>) {
+/// static STORAGE: StaticCell<BatteryServiceStorage> = StaticCell::new();
+/// let battery = BatteryService::new(&mut table, STORAGE.init(BatteryServiceStorage::new()));
+///
+/// if let Some(BatteryServiceEvent::LevelRead { connection }) = battery.event(&event) {
+///     // ...
+/// }
+/// # }
+///
+/// // On the client side
+/// # async fn client<T: Controller>(mut client: GattClient<'_, '_, T, 10>) -> Result<(), BleHostError<T::Error>> {
+/// let battery = BatteryServiceClient::discover(&mut client).await?;
+/// let level: u8 = battery.read_level(&mut client).await?;
+/// let mut levels = battery.subscribe_level(&client).await?;
+/// let level: u8 = levels.next_value().await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// This generates:
+///
+/// * The struct itself, with an accessor returning the `Characteristic` for each field.
+/// * A `<Name>Storage` struct holding the values, to be passed to `<Name>::new`.
+/// * `<Name>::new`, which registers the service in an `AttributeTable`.
+/// * A `<Name>Event` enum and `<Name>::event`, which maps a `GattEvent` to the characteristic it concerns.
+/// * A `<Name>Client` proxy for the service on a peer. `<Name>Client::discover` finds it through a `GattClient`,
+///   and `read_<field>`, `write_<field>` and `subscribe_<field>` methods are provided according to the properties
+///   of each characteristic.
+#[proc_macro_attribute]
+pub fn gatt_service(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut uuid: Option<Expr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("uuid") {
+            uuid = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported gatt_service argument"))
+        }
+    });
+    parse_macro_input!(args with parser);
+    let item = parse_macro_input!(item as ItemStruct);
+
+    match service::expand(uuid, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
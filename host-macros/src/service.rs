@@ -0,0 +1,348 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{Expr, Fields, Ident, ItemStruct, Type, Visibility};
+
+use crate::uuid;
+
+/// Characteristic properties accepted in `#[characteristic(...)]`, and the matching `CharacteristicProp`.
+const PROPS: &[(&str, &str)] = &[
+    ("broadcast", "Broadcast"),
+    ("read", "Read"),
+    ("write_without_response", "WriteWithoutResponse"),
+    ("write", "Write"),
+    ("notify", "Notify"),
+    ("indicate", "Indicate"),
+    ("authenticated_write", "AuthenticatedWrite"),
+];
+
+struct Characteristic {
+    name: Ident,
+    vis: Visibility,
+    ty: Type,
+    uuid: TokenStream,
+    props: Vec<Ident>,
+}
+
+impl Characteristic {
+    fn has(&self, props: &[&str]) -> bool {
+        self.props.iter().any(|p| props.iter().any(|name| p == name))
+    }
+
+    fn readable(&self) -> bool {
+        self.has(&["Read"])
+    }
+
+    fn writable(&self) -> bool {
+        self.has(&["Write", "WriteWithoutResponse", "AuthenticatedWrite"])
+    }
+
+    fn subscribable(&self) -> bool {
+        self.has(&["Notify", "Indicate"])
+    }
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<Characteristic> {
+    let name = field.ident.clone().expect("named field");
+    let attr = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("characteristic"))
+        .ok_or_else(|| syn::Error::new(field.span(), "missing #[characteristic(...)] attribute"))?;
+
+    let mut uuid: Option<Expr> = None;
+    let mut props = Vec::new();
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("uuid") {
+            uuid = Some(meta.value()?.parse()?);
+            return Ok(());
+        }
+        for (name, prop) in PROPS {
+            if meta.path.is_ident(name) {
+                props.push(Ident::new(prop, Span::call_site()));
+                return Ok(());
+            }
+        }
+        Err(meta.error("unknown characteristic property"))
+    })?;
+
+    let uuid = uuid.ok_or_else(|| syn::Error::new(attr.span(), "missing characteristic uuid"))?;
+    Ok(Characteristic {
+        name,
+        vis: field.vis.clone(),
+        ty: field.ty.clone(),
+        uuid: uuid::expand(&uuid)?,
+        props,
+    })
+}
+
+fn camel_case(ident: &Ident) -> String {
+    ident
+        .to_string()
+        .split('_')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let mut chars = s.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            core::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+pub(crate) fn expand(uuid: Option<Expr>, item: ItemStruct) -> syn::Result<TokenStream> {
+    let uuid = uuid.ok_or_else(|| syn::Error::new(item.span(), "missing service uuid"))?;
+    let service_uuid = uuid::expand(&uuid)?;
+
+    let Fields::Named(fields) = &item.fields else {
+        return Err(syn::Error::new(
+            item.span(),
+            "gatt_service requires a struct with named fields",
+        ));
+    };
+    let characteristics = fields.named.iter().map(parse_field).collect::<syn::Result<Vec<_>>>()?;
+
+    let attrs = &item.attrs;
+    let vis = &item.vis;
+    let name = &item.ident;
+    let storage_name = format_ident!("{}Storage", name);
+    let event_name = format_ident!("{}Event", name);
+    let client_name = format_ident!("{}Client", name);
+
+    let names: Vec<_> = characteristics.iter().map(|c| &c.name).collect();
+    let types: Vec<_> = characteristics.iter().map(|c| &c.ty).collect();
+
+    let register = characteristics.iter().map(|c| {
+        let name = &c.name;
+        let uuid = &c.uuid;
+        let props = &c.props;
+        let ty = &c.ty;
+        quote! {
+            let props = &[#(::trouble_host::attribute::CharacteristicProp::#props),*];
+            let #name = if <#ty as ::trouble_host::types::gatt_traits::GattValue>::MIN_SIZE
+                == <#ty as ::trouble_host::types::gatt_traits::GattValue>::MAX_SIZE
+            {
+                service.add_characteristic(#uuid, props, &mut #name[..]).build()
+            } else {
+                service.add_characteristic_variable(#uuid, props, &mut #name[..], 0).build()
+            };
+        }
+    });
+
+    let accessors = characteristics.iter().map(|c| {
+        let name = &c.name;
+        let vis = &c.vis;
+        quote! {
+            #vis fn #name(&self) -> ::trouble_host::attribute::Characteristic {
+                self.#name
+            }
+        }
+    });
+
+    let mut variants = Vec::new();
+    let mut read_arms = Vec::new();
+    let mut write_arms = Vec::new();
+    for c in characteristics.iter() {
+        let field = &c.name;
+        if c.readable() {
+            let variant = format_ident!("{}Read", camel_case(field));
+            variants.push(variant.clone());
+            read_arms.push(quote! {
+                if handle.handle() == self.#field.handle() {
+                    return Some(#event_name::#variant { connection: connection.clone() });
+                }
+            });
+        }
+        if c.writable() {
+            let variant = format_ident!("{}Write", camel_case(field));
+            variants.push(variant.clone());
+            write_arms.push(quote! {
+                if handle.handle() == self.#field.handle() {
+                    return Some(#event_name::#variant { connection: connection.clone() });
+                }
+            });
+        }
+    }
+    let lifetime = if variants.is_empty() { quote!() } else { quote!(<'a>) };
+
+    let discover = characteristics.iter().map(|c| {
+        let name = &c.name;
+        let uuid = &c.uuid;
+        quote! {
+            let #name = client.characteristic_by_uuid(&service, &#uuid).await?;
+        }
+    });
+
+    let proxies = characteristics.iter().map(|c| {
+        let name = &c.name;
+        let vis = &c.vis;
+        let ty = &c.ty;
+        let mut methods = quote! {
+            #vis fn #name(&self) -> ::trouble_host::attribute::Characteristic {
+                self.#name
+            }
+        };
+        if c.readable() {
+            let read = format_ident!("read_{}", name);
+            methods.extend(quote! {
+                /// Read the value of the characteristic.
+                #vis async fn #read<T: ::trouble_host::__private::Controller, const MAX: usize>(
+                    &self,
+                    client: &mut ::trouble_host::gatt::GattClient<'_, '_, T, MAX>,
+                ) -> Result<#ty, ::trouble_host::BleHostError<T::Error>> {
+                    client.read_characteristic_value(&self.#name).await
+                }
+            });
+        }
+        if c.has(&["Write", "AuthenticatedWrite"]) {
+            let write = format_ident!("write_{}", name);
+            methods.extend(quote! {
+                /// Write the value of the characteristic, waiting for the server to acknowledge it.
+                #vis async fn #write<T: ::trouble_host::__private::Controller, const MAX: usize>(
+                    &self,
+                    client: &mut ::trouble_host::gatt::GattClient<'_, '_, T, MAX>,
+                    value: &#ty,
+                ) -> Result<(), ::trouble_host::BleHostError<T::Error>> {
+                    client.write_characteristic_value(&self.#name, value).await
+                }
+            });
+        } else if c.has(&["WriteWithoutResponse"]) {
+            let write = format_ident!("write_{}", name);
+            methods.extend(quote! {
+                /// Write the value of the characteristic, without a response from the server.
+                #vis async fn #write<T: ::trouble_host::__private::Controller, const MAX: usize>(
+                    &self,
+                    client: &mut ::trouble_host::gatt::GattClient<'_, '_, T, MAX>,
+                    value: &#ty,
+                ) -> Result<(), ::trouble_host::BleHostError<T::Error>> {
+                    let mut buf = [0; <#ty as ::trouble_host::types::gatt_traits::GattValue>::MAX_SIZE];
+                    let len = ::trouble_host::types::gatt_traits::GattValue::to_gatt(value, &mut buf)?;
+                    client.write_without_response(&self.#name, &buf[..len]).await
+                }
+            });
+        }
+        if c.subscribable() {
+            let subscribe = format_ident!("subscribe_{}", name);
+            // Notifications are preferred, as they need no confirmation
+            let indications = !c.has(&["Notify"]);
+            methods.extend(quote! {
+                /// Subscribe to the value of the characteristic, received with `Subscription::next_value`.
+                #vis async fn #subscribe<
+                    'c,
+                    'reference,
+                    'resources,
+                    T: ::trouble_host::__private::Controller,
+                    const MAX: usize,
+                >(
+                    &self,
+                    client: &'c ::trouble_host::gatt::GattClient<'reference, 'resources, T, MAX>,
+                ) -> Result<
+                    ::trouble_host::gatt::Subscription<'c, 'reference, 'resources, T, MAX>,
+                    ::trouble_host::BleHostError<T::Error>,
+                > {
+                    client.subscribe(&self.#name, #indications).await
+                }
+            });
+        }
+        methods
+    });
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis struct #name {
+            handle: ::trouble_host::attribute::AttributeHandle,
+            #(#names: ::trouble_host::attribute::Characteristic,)*
+        }
+
+        /// Storage for the characteristic values of the service.
+        #vis struct #storage_name {
+            #(#names: [u8; <#types as ::trouble_host::types::gatt_traits::GattValue>::MAX_SIZE],)*
+        }
+
+        impl #storage_name {
+            pub const fn new() -> Self {
+                Self {
+                    #(#names: [0; <#types as ::trouble_host::types::gatt_traits::GattValue>::MAX_SIZE],)*
+                }
+            }
+        }
+
+        impl ::core::default::Default for #storage_name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        /// Events for the characteristics of the service.
+        #vis enum #event_name #lifetime {
+            #(#variants { connection: ::trouble_host::connection::Connection<'a> },)*
+        }
+
+        impl #name {
+            /// Register the service and its characteristics in the attribute table.
+            #vis fn new<'d, M: ::trouble_host::__private::RawMutex, const MAX: usize>(
+                table: &mut ::trouble_host::attribute::AttributeTable<'d, M, MAX>,
+                storage: &'d mut #storage_name,
+            ) -> Self {
+                let #storage_name { #(#names),* } = storage;
+                let mut service = table.add_service(::trouble_host::attribute::Service::new(#service_uuid));
+                #(#register)*
+                let handle = service.build();
+                Self { handle, #(#names),* }
+            }
+
+            /// Handle of the service declaration.
+            #vis fn handle(&self) -> ::trouble_host::attribute::AttributeHandle {
+                self.handle
+            }
+
+            #(#accessors)*
+
+            /// Map a GATT event to the characteristic of this service it concerns, if any.
+            #[allow(unused_variables)]
+            #vis fn event<'a>(&self, event: &::trouble_host::gatt::GattEvent<'a>) -> Option<#event_name #lifetime> {
+                match event {
+                    ::trouble_host::gatt::GattEvent::Read { connection, handle } => {
+                        #(#read_arms)*
+                    }
+                    ::trouble_host::gatt::GattEvent::Write { connection, handle } => {
+                        #(#write_arms)*
+                    }
+                    #[allow(unreachable_patterns)]
+                    _ => {}
+                }
+                None
+            }
+        }
+
+        /// Client proxy for the service on a peer, with typed access to its characteristics.
+        #vis struct #client_name {
+            service: ::trouble_host::gatt::ServiceHandle,
+            #(#names: ::trouble_host::attribute::Characteristic,)*
+        }
+
+        impl #client_name {
+            /// Discover the service and its characteristics on the server.
+            #vis async fn discover<T: ::trouble_host::__private::Controller, const MAX: usize>(
+                client: &mut ::trouble_host::gatt::GattClient<'_, '_, T, MAX>,
+            ) -> Result<Self, ::trouble_host::BleHostError<T::Error>> {
+                let uuid = #service_uuid;
+                let service = client
+                    .services_by_uuid(&uuid)
+                    .await?
+                    .iter()
+                    .find(|s| s.uuid() == &uuid)
+                    .cloned()
+                    .ok_or(::trouble_host::Error::NotFound)?;
+                #(#discover)*
+                Ok(Self { service, #(#names),* })
+            }
+
+            /// The service on the server.
+            #vis fn service(&self) -> &::trouble_host::gatt::ServiceHandle {
+                &self.service
+            }
+
+            #(#proxies)*
+        }
+    })
+}
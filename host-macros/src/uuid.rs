@@ -0,0 +1,28 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Expr, ExprLit, Lit};
+
+/// Produce an expression evaluating to a `Uuid`.
+///
+/// String literals are parsed at compile time, in the usual big endian notation. Any other
+/// expression is converted using `Uuid::from`.
+pub(crate) fn expand(expr: &Expr) -> syn::Result<TokenStream> {
+    let Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) = expr else {
+        return Ok(quote!(::trouble_host::attribute::Uuid::from(#expr)));
+    };
+
+    let hex: String = s.value().chars().filter(|c| *c != '-').collect();
+    let invalid = || syn::Error::new(s.span(), "invalid UUID, expected 16-bit or 128-bit hex string");
+    match hex.len() {
+        4 => {
+            let val = u16::from_str_radix(&hex, 16).map_err(|_| invalid())?;
+            Ok(quote!(::trouble_host::attribute::Uuid::new_short(#val)))
+        }
+        32 => {
+            let val = u128::from_str_radix(&hex, 16).map_err(|_| invalid())?;
+            let bytes = val.to_le_bytes();
+            Ok(quote!(::trouble_host::attribute::Uuid::new_long([#(#bytes),*])))
+        }
+        _ => Err(invalid()),
+    }
+}
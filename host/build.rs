@@ -8,6 +8,7 @@ static CONFIGS: &[(&str, usize)] = &[
     // Generated by gen_config.py. DO NOT EDIT.
     ("L2CAP_RX_QUEUE_SIZE", 1),
     ("L2CAP_RX_PACKET_POOL_SIZE", 2),
+    ("GATT_CLIENT_NOTIFICATION_QUEUE_SIZE", 1),
     // END AUTOGENERATED CONFIG FEATURES
 ];
 
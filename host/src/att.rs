@@ -22,11 +22,22 @@ pub(crate) const ATT_PREPARE_WRITE_REQ: u8 = 0x16;
 pub(crate) const ATT_PREPARE_WRITE_RSP: u8 = 0x17;
 pub(crate) const ATT_EXECUTE_WRITE_REQ: u8 = 0x18;
 pub(crate) const ATT_EXECUTE_WRITE_RSP: u8 = 0x19;
-pub(crate) const ATT_READ_MULTIPLE_REQ: u8 = 0x20;
-pub(crate) const ATT_READ_MULTIPLE_RSP: u8 = 0x21;
+pub(crate) const ATT_READ_MULTIPLE_REQ: u8 = 0x0e;
+pub(crate) const ATT_READ_MULTIPLE_RSP: u8 = 0x0f;
+pub(crate) const ATT_READ_MULTIPLE_VARIABLE_REQ: u8 = 0x20;
+pub(crate) const ATT_READ_MULTIPLE_VARIABLE_RSP: u8 = 0x21;
 pub(crate) const ATT_READ_BLOB_REQ: u8 = 0x0c;
 pub(crate) const ATT_READ_BLOB_RSP: u8 = 0x0d;
 pub(crate) const ATT_HANDLE_VALUE_NTF: u8 = 0x1b;
+pub(crate) const ATT_MULTIPLE_HANDLE_VALUE_NTF: u8 = 0x23;
+pub(crate) const ATT_HANDLE_VALUE_IND: u8 = 0x1d;
+pub(crate) const ATT_HANDLE_VALUE_CFM: u8 = 0x1e;
+
+/// Bit set in the opcode of commands, which never get a response.
+pub(crate) const ATT_COMMAND_FLAG: u8 = 0x40;
+
+/// Largest ATT MTU allowed by the specification.
+pub(crate) const ATT_MTU_MAX: usize = 517;
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -66,6 +77,8 @@ pub enum AttErrorCode {
     UnsupportedGroupType = 0x10,
     /// Server didn't have enough resources to complete a request.
     InsufficientResources = 0x11,
+    /// Attribute value is outside the range given by its Valid Range descriptor.
+    OutOfRange = 0xFF,
 }
 
 impl TryFrom<u8> for AttErrorCode {
@@ -89,6 +102,7 @@ impl TryFrom<u8> for AttErrorCode {
             0x0F => Ok(Self::InsufficientEncryption),
             0x10 => Ok(Self::UnsupportedGroupType),
             0x11 => Ok(Self::InsufficientResources),
+            0xFF => Ok(Self::OutOfRange),
             _ => Err(()),
         }
     }
@@ -142,6 +156,9 @@ pub enum AttReq<'d> {
     ReadMultiple {
         handles: &'d [u8],
     },
+    ReadMultipleVariable {
+        handles: &'d [u8],
+    },
     ReadBlob {
         handle: u16,
         offset: u16,
@@ -163,10 +180,31 @@ pub enum AttRsp<'d> {
     ReadByType {
         it: ReadByTypeIter<'d>,
     },
+    ReadByGroupType {
+        it: ReadByGroupTypeIter<'d>,
+    },
+    FindInformation {
+        it: FindInformationIter<'d>,
+    },
     Read {
         data: &'d [u8],
     },
+    ReadBlob {
+        data: &'d [u8],
+    },
+    ReadMultiple {
+        data: &'d [u8],
+    },
+    ReadMultipleVariable {
+        it: ReadMultipleVariableIter<'d>,
+    },
     Write,
+    PrepareWrite {
+        handle: u16,
+        offset: u16,
+        value: &'d [u8],
+    },
+    ExecuteWrite,
 }
 
 impl<'d> codec::Type for AttRsp<'d> {
@@ -228,6 +266,74 @@ impl<'d> ReadByTypeIter<'d> {
     }
 }
 
+#[derive(Clone)]
+pub struct ReadByGroupTypeIter<'d> {
+    item_len: usize,
+    cursor: ReadCursor<'d>,
+}
+
+impl<'d> ReadByGroupTypeIter<'d> {
+    /// Next attribute group, as its start handle, end handle and value.
+    pub fn next(&mut self) -> Option<Result<(u16, u16, &'d [u8]), crate::Error>> {
+        if self.cursor.available() >= self.item_len {
+            let res = (|| {
+                let handle: u16 = self.cursor.read()?;
+                let end: u16 = self.cursor.read()?;
+                let item = self.cursor.slice(self.item_len - 4)?;
+                Ok((handle, end, item))
+            })();
+            Some(res)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReadMultipleVariableIter<'d> {
+    cursor: ReadCursor<'d>,
+}
+
+impl<'d> ReadMultipleVariableIter<'d> {
+    /// Next attribute value, which may be truncated if it is the last.
+    pub fn next(&mut self) -> Option<Result<&'d [u8], crate::Error>> {
+        if self.cursor.available() >= 2 {
+            let res = (|| {
+                let len: u16 = self.cursor.read()?;
+                // The last value is truncated when the response doesn't fit the MTU
+                let len = (len as usize).min(self.cursor.available());
+                Ok(self.cursor.slice(len)?)
+            })();
+            Some(res)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FindInformationIter<'d> {
+    // Length of the UUIDs, 2 or 16
+    uuid_len: usize,
+    cursor: ReadCursor<'d>,
+}
+
+impl<'d> FindInformationIter<'d> {
+    /// Next attribute, as its handle and type.
+    pub fn next(&mut self) -> Option<Result<(u16, Uuid), crate::Error>> {
+        if self.cursor.available() >= 2 + self.uuid_len {
+            let res = (|| {
+                let handle: u16 = self.cursor.read()?;
+                let uuid = Uuid::from_slice(self.cursor.slice(self.uuid_len)?);
+                Ok((handle, uuid))
+            })();
+            Some(res)
+        } else {
+            None
+        }
+    }
+}
+
 impl<'d> AttRsp<'d> {
     pub fn size(&self) -> usize {
         1 + match self {
@@ -235,8 +341,15 @@ impl<'d> AttRsp<'d> {
             Self::FindByTypeValue { it } => it.cursor.len(),
             Self::Error { .. } => 4,
             Self::Read { data } => data.len(),
+            Self::ReadBlob { data } => data.len(),
+            Self::ReadMultiple { data } => data.len(),
+            Self::ReadMultipleVariable { it } => it.cursor.len(),
             Self::ReadByType { it } => it.cursor.len(),
+            Self::ReadByGroupType { it } => it.cursor.len(),
+            Self::FindInformation { it } => 1 + it.cursor.len(),
             Self::Write => 0,
+            Self::PrepareWrite { value, .. } => 4 + value.len(),
+            Self::ExecuteWrite => 0,
         }
     }
 
@@ -270,13 +383,57 @@ impl<'d> AttRsp<'d> {
                     w.append(item)?;
                 }
             }
+            Self::ReadByGroupType { it } => {
+                w.write(ATT_READ_BY_GROUP_TYPE_RSP)?;
+                w.write(it.item_len as u8)?;
+                let mut it = it.clone();
+                while let Some(Ok((handle, end, item))) = it.next() {
+                    w.write(handle)?;
+                    w.write(end)?;
+                    w.append(item)?;
+                }
+            }
+            Self::FindInformation { it } => {
+                w.write(ATT_FIND_INFORMATION_RSP)?;
+                w.write(if it.uuid_len == 2 { 0x01_u8 } else { 0x02 })?;
+                let mut it = it.clone();
+                while let Some(Ok((handle, uuid))) = it.next() {
+                    w.write(handle)?;
+                    w.append(uuid.as_raw())?;
+                }
+            }
             Self::Read { data } => {
                 w.write(ATT_READ_RSP)?;
                 w.append(data)?;
             }
+            Self::ReadBlob { data } => {
+                w.write(ATT_READ_BLOB_RSP)?;
+                w.append(data)?;
+            }
+            Self::ReadMultiple { data } => {
+                w.write(ATT_READ_MULTIPLE_RSP)?;
+                w.append(data)?;
+            }
+            Self::ReadMultipleVariable { it } => {
+                w.write(ATT_READ_MULTIPLE_VARIABLE_RSP)?;
+                let mut it = it.clone();
+                while let Some(Ok(value)) = it.next() {
+                    w.write(value.len() as u16)?;
+                    w.append(value)?;
+                }
+            }
             Self::Write => {
                 w.write(ATT_WRITE_RSP)?;
             }
+            Self::PrepareWrite { handle, offset, value } => {
+                w.write(ATT_PREPARE_WRITE_RSP)?;
+                w.write(*handle)?;
+                w.write(*offset)?;
+                w.append(value)?;
+            }
+            Self::ExecuteWrite => {
+                w.write(ATT_EXECUTE_WRITE_RSP)?;
+            }
         }
         Ok(())
     }
@@ -300,8 +457,16 @@ impl<'d> AttRsp<'d> {
                 Ok(Self::Error { request, handle, code })
             }
             ATT_READ_RSP => Ok(Self::Read { data: r.remaining() }),
+            ATT_READ_BLOB_RSP => Ok(Self::ReadBlob { data: r.remaining() }),
+            ATT_READ_MULTIPLE_RSP => Ok(Self::ReadMultiple { data: r.remaining() }),
+            ATT_READ_MULTIPLE_VARIABLE_RSP => Ok(Self::ReadMultipleVariable {
+                it: ReadMultipleVariableIter { cursor: r },
+            }),
             ATT_READ_BY_TYPE_RSP => {
                 let item_len: u8 = r.read()?;
+                if item_len < 2 {
+                    return Err(codec::Error::InvalidValue);
+                }
                 Ok(Self::ReadByType {
                     it: ReadByTypeIter {
                         item_len: item_len as usize,
@@ -309,7 +474,40 @@ impl<'d> AttRsp<'d> {
                     },
                 })
             }
+            ATT_READ_BY_GROUP_TYPE_RSP => {
+                let item_len: u8 = r.read()?;
+                if item_len < 4 {
+                    return Err(codec::Error::InvalidValue);
+                }
+                Ok(Self::ReadByGroupType {
+                    it: ReadByGroupTypeIter {
+                        item_len: item_len as usize,
+                        cursor: r,
+                    },
+                })
+            }
+            ATT_FIND_INFORMATION_RSP => {
+                let format: u8 = r.read()?;
+                let uuid_len = match format {
+                    0x01 => 2,
+                    0x02 => 16,
+                    _ => return Err(codec::Error::InvalidValue),
+                };
+                Ok(Self::FindInformation {
+                    it: FindInformationIter { uuid_len, cursor: r },
+                })
+            }
             ATT_WRITE_RSP => Ok(Self::Write),
+            ATT_PREPARE_WRITE_RSP => {
+                let handle: u16 = r.read()?;
+                let offset: u16 = r.read()?;
+                Ok(Self::PrepareWrite {
+                    handle,
+                    offset,
+                    value: r.remaining(),
+                })
+            }
+            ATT_EXECUTE_WRITE_RSP => Ok(Self::ExecuteWrite),
             _ => Err(codec::Error::InvalidValue),
         }
     }
@@ -354,8 +552,16 @@ impl<'d> AttReq<'d> {
                 end,
                 attribute_type,
             } => 4 + attribute_type.as_raw().len(),
+            Self::ReadByGroupType { group_type, .. } => 4 + group_type.as_raw().len(),
+            Self::FindInformation { .. } => 4,
             Self::Read { .. } => 2,
+            Self::ReadBlob { .. } => 4,
+            Self::ReadMultiple { handles } => handles.len(),
+            Self::ReadMultipleVariable { handles } => handles.len(),
             Self::Write { handle, data } => 2 + data.len(),
+            Self::WriteCmd { handle, data } => 2 + data.len(),
+            Self::PrepareWrite { value, .. } => 4 + value.len(),
+            Self::ExecuteWrite { .. } => 1,
             _ => unimplemented!(),
         }
     }
@@ -388,15 +594,57 @@ impl<'d> AttReq<'d> {
                 w.write(*end)?;
                 w.write_ref(attribute_type)?;
             }
+            Self::ReadByGroupType { start, end, group_type } => {
+                w.write(ATT_READ_BY_GROUP_TYPE_REQ)?;
+                w.write(*start)?;
+                w.write(*end)?;
+                w.write_ref(group_type)?;
+            }
+            Self::FindInformation {
+                start_handle,
+                end_handle,
+            } => {
+                w.write(ATT_FIND_INFORMATION_REQ)?;
+                w.write(*start_handle)?;
+                w.write(*end_handle)?;
+            }
             Self::Read { handle } => {
                 w.write(ATT_READ_REQ)?;
                 w.write(*handle)?;
             }
+            Self::ReadBlob { handle, offset } => {
+                w.write(ATT_READ_BLOB_REQ)?;
+                w.write(*handle)?;
+                w.write(*offset)?;
+            }
+            Self::ReadMultiple { handles } => {
+                w.write(ATT_READ_MULTIPLE_REQ)?;
+                w.append(handles)?;
+            }
+            Self::ReadMultipleVariable { handles } => {
+                w.write(ATT_READ_MULTIPLE_VARIABLE_REQ)?;
+                w.append(handles)?;
+            }
             Self::Write { handle, data } => {
                 w.write(ATT_WRITE_REQ)?;
                 w.write(*handle)?;
                 w.append(data)?;
             }
+            Self::WriteCmd { handle, data } => {
+                w.write(ATT_WRITE_CMD)?;
+                w.write(*handle)?;
+                w.append(data)?;
+            }
+            Self::PrepareWrite { handle, offset, value } => {
+                w.write(ATT_PREPARE_WRITE_REQ)?;
+                w.write(*handle)?;
+                w.write(*offset)?;
+                w.append(value)?;
+            }
+            Self::ExecuteWrite { flags } => {
+                w.write(ATT_EXECUTE_WRITE_REQ)?;
+                w.write(*flags)?;
+            }
             _ => unimplemented!(),
         }
         Ok(())
@@ -404,110 +652,149 @@ impl<'d> AttReq<'d> {
     pub fn decode(packet: &'d [u8]) -> Result<AttReq<'d>, codec::Error> {
         let mut r = ReadCursor::new(packet);
         let opcode: u8 = r.read()?;
-        let payload = r.remaining();
 
         match opcode {
             ATT_READ_BY_GROUP_TYPE_REQ => {
-                let start_handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-                let end_handle = (payload[2] as u16) + ((payload[3] as u16) << 8);
-
-                let group_type = if payload.len() == 6 {
-                    Uuid::Uuid16([payload[4], payload[5]])
-                } else if payload.len() == 20 {
-                    let uuid = payload[4..21].try_into().map_err(|_| codec::Error::InvalidValue)?;
-                    Uuid::Uuid128(uuid)
-                } else {
-                    return Err(codec::Error::InvalidValue);
-                };
-
-                Ok(Self::ReadByGroupType {
-                    start: start_handle,
-                    end: end_handle,
-                    group_type,
-                })
+                let start: u16 = r.read()?;
+                let end: u16 = r.read()?;
+                let group_type = decode_uuid(r.remaining())?;
+                Ok(Self::ReadByGroupType { start, end, group_type })
             }
             ATT_READ_BY_TYPE_REQ => {
-                let start_handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-                let end_handle = (payload[2] as u16) + ((payload[3] as u16) << 8);
-
-                let attribute_type = if payload.len() == 6 {
-                    Uuid::Uuid16([payload[4], payload[5]])
-                } else if payload.len() == 20 {
-                    let uuid = payload[4..20].try_into().map_err(|_| codec::Error::InvalidValue)?;
-                    Uuid::Uuid128(uuid)
-                } else {
-                    return Err(codec::Error::InvalidValue);
-                };
-
+                let start: u16 = r.read()?;
+                let end: u16 = r.read()?;
+                let attribute_type = decode_uuid(r.remaining())?;
                 Ok(Self::ReadByType {
-                    start: start_handle,
-                    end: end_handle,
+                    start,
+                    end,
                     attribute_type,
                 })
             }
             ATT_READ_REQ => {
-                let handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-
+                let handle: u16 = r.read()?;
                 Ok(Self::Read { handle })
             }
             ATT_WRITE_REQ => {
-                let handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-                let data = &payload[2..];
-
-                Ok(Self::Write { handle, data })
+                let handle: u16 = r.read()?;
+                Ok(Self::Write {
+                    handle,
+                    data: r.remaining(),
+                })
             }
             ATT_WRITE_CMD => {
-                let handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-                let data = &payload[2..];
-
-                Ok(Self::WriteCmd { handle, data })
+                let handle: u16 = r.read()?;
+                Ok(Self::WriteCmd {
+                    handle,
+                    data: r.remaining(),
+                })
             }
             ATT_EXCHANGE_MTU_REQ => {
-                let mtu = (payload[0] as u16) + ((payload[1] as u16) << 8);
+                let mtu: u16 = r.read()?;
                 Ok(Self::ExchangeMtu { mtu })
             }
             ATT_FIND_BY_TYPE_VALUE_REQ => {
-                let start_handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-                let end_handle = (payload[2] as u16) + ((payload[3] as u16) << 8);
-                let att_type = (payload[4] as u16) + ((payload[5] as u16) << 8);
-                let att_value = &payload[6..];
-
+                let start_handle: u16 = r.read()?;
+                let end_handle: u16 = r.read()?;
+                let att_type: u16 = r.read()?;
                 Ok(Self::FindByTypeValue {
                     start_handle,
                     end_handle,
                     att_type,
-                    att_value,
+                    att_value: r.remaining(),
                 })
             }
             ATT_FIND_INFORMATION_REQ => {
-                let start_handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-                let end_handle = (payload[2] as u16) + ((payload[3] as u16) << 8);
-
+                let start_handle: u16 = r.read()?;
+                let end_handle: u16 = r.read()?;
                 Ok(Self::FindInformation {
                     start_handle,
                     end_handle,
                 })
             }
             ATT_PREPARE_WRITE_REQ => {
-                let handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-                let offset = (payload[2] as u16) + ((payload[3] as u16) << 8);
+                let handle: u16 = r.read()?;
+                let offset: u16 = r.read()?;
                 Ok(Self::PrepareWrite {
                     handle,
                     offset,
-                    value: &payload[4..],
+                    value: r.remaining(),
                 })
             }
             ATT_EXECUTE_WRITE_REQ => {
-                let flags = payload[0];
+                let flags: u8 = r.read()?;
                 Ok(Self::ExecuteWrite { flags })
             }
-            ATT_READ_MULTIPLE_REQ => Ok(Self::ReadMultiple { handles: payload }),
+            ATT_READ_MULTIPLE_REQ | ATT_READ_MULTIPLE_VARIABLE_REQ => {
+                let handles = r.remaining();
+                // At least two handles are required
+                if handles.len() < 4 || handles.len() % 2 != 0 {
+                    return Err(codec::Error::InvalidValue);
+                }
+                if opcode == ATT_READ_MULTIPLE_REQ {
+                    Ok(Self::ReadMultiple { handles })
+                } else {
+                    Ok(Self::ReadMultipleVariable { handles })
+                }
+            }
             ATT_READ_BLOB_REQ => {
-                let handle = (payload[0] as u16) + ((payload[1] as u16) << 8);
-                let offset = (payload[2] as u16) + ((payload[3] as u16) << 8);
+                let handle: u16 = r.read()?;
+                let offset: u16 = r.read()?;
                 Ok(Self::ReadBlob { handle, offset })
             }
             _ => Err(codec::Error::InvalidValue),
         }
     }
+
+    /// Check if `opcode` is a request that can be decoded as an `AttReq`.
+    pub(crate) fn is_known(opcode: u8) -> bool {
+        matches!(
+            opcode,
+            ATT_READ_BY_GROUP_TYPE_REQ
+                | ATT_READ_BY_TYPE_REQ
+                | ATT_READ_REQ
+                | ATT_WRITE_REQ
+                | ATT_WRITE_CMD
+                | ATT_EXCHANGE_MTU_REQ
+                | ATT_FIND_BY_TYPE_VALUE_REQ
+                | ATT_FIND_INFORMATION_REQ
+                | ATT_PREPARE_WRITE_REQ
+                | ATT_EXECUTE_WRITE_REQ
+                | ATT_READ_MULTIPLE_REQ
+                | ATT_READ_MULTIPLE_VARIABLE_REQ
+                | ATT_READ_BLOB_REQ
+        )
+    }
+}
+
+/// Check if a PDU with this opcode expects a response from the server.
+///
+/// Requests have even opcodes, with the exception of the handle value confirmation. Commands are
+/// marked by the command flag, and are never answered.
+pub(crate) fn expects_response(opcode: u8) -> bool {
+    opcode & ATT_COMMAND_FLAG == 0 && opcode & 1 == 0 && opcode != ATT_HANDLE_VALUE_CFM
+}
+
+/// Whether a PDU is sent by a server, and so must be handled by the client on the other side of the link.
+///
+/// Responses, notifications and indications have odd opcodes, while requests, commands and confirmations, which are
+/// sent by clients, have even opcodes.
+pub(crate) fn from_server(opcode: u8) -> bool {
+    opcode & 1 == 1
+}
+
+/// Whether a PDU sent by a server carries attribute values the client didn't request.
+pub(crate) fn is_notification(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        ATT_HANDLE_VALUE_NTF | ATT_HANDLE_VALUE_IND | ATT_MULTIPLE_HANDLE_VALUE_NTF
+    )
+}
+
+/// Decode an attribute type, which is either a 16-bit or a 128-bit UUID.
+fn decode_uuid(src: &[u8]) -> Result<Uuid, codec::Error> {
+    match src.len() {
+        2 => Ok(Uuid::Uuid16([src[0], src[1]])),
+        16 => Ok(Uuid::Uuid128(src.try_into().map_err(|_| codec::Error::InvalidValue)?)),
+        _ => Err(codec::Error::InvalidValue),
+    }
 }
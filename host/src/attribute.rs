@@ -1,11 +1,18 @@
 use core::cell::RefCell;
 use core::fmt;
+use core::future::poll_fn;
+use core::ops::{Range, RangeInclusive};
+use core::task::Poll;
 
+use bt_hci::param::ConnHandle;
 use embassy_sync::blocking_mutex::raw::RawMutex;
 use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::MultiWakerRegistration;
 
 use crate::att::AttErrorCode;
+use crate::connection::Connection;
 use crate::cursor::WriteCursor;
+use crate::types::gatt_traits::GattValue;
 pub use crate::types::uuid::Uuid;
 use crate::Error;
 
@@ -16,9 +23,16 @@ pub const CHARACTERISTIC_APPEARANCE_UUID16: Uuid = Uuid::Uuid16(0x2A03u16.to_le_
 pub const GENERIC_ATTRIBUTE_SERVICE_UUID16: Uuid = Uuid::Uuid16(0x1801u16.to_le_bytes());
 
 pub const PRIMARY_SERVICE_UUID16: Uuid = Uuid::Uuid16(0x2800u16.to_le_bytes());
+pub const INCLUDE_SERVICE_UUID16: Uuid = Uuid::Uuid16(0x2802u16.to_le_bytes());
 pub const CHARACTERISTIC_UUID16: Uuid = Uuid::Uuid16(0x2803u16.to_le_bytes());
+pub const CHARACTERISTIC_EXTENDED_PROPERTIES_UUID16: Uuid = Uuid::Uuid16(0x2900u16.to_le_bytes());
+pub const CHARACTERISTIC_USER_DESCRIPTION_UUID16: Uuid = Uuid::Uuid16(0x2901u16.to_le_bytes());
 pub const CHARACTERISTIC_CCCD_UUID16: Uuid = Uuid::Uuid16(0x2902u16.to_le_bytes());
+pub const CHARACTERISTIC_SCCD_UUID16: Uuid = Uuid::Uuid16(0x2903u16.to_le_bytes());
+pub const CHARACTERISTIC_PRESENTATION_FORMAT_UUID16: Uuid = Uuid::Uuid16(0x2904u16.to_le_bytes());
+pub const CHARACTERISTIC_VALID_RANGE_UUID16: Uuid = Uuid::Uuid16(0x2906u16.to_le_bytes());
 pub const GENERIC_ATTRIBUTE_UUID16: Uuid = Uuid::Uuid16(0x1801u16.to_le_bytes());
+pub const CLIENT_SUPPORTED_FEATURES_UUID16: Uuid = Uuid::Uuid16(0x2B29u16.to_le_bytes());
 
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
@@ -33,6 +47,14 @@ pub enum CharacteristicProp {
     Extended = 0x80,
 }
 
+/// Bits of the Characteristic Extended Properties descriptor.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum ExtendedProp {
+    ReliableWrite = 0x01,
+    WritableAuxiliaries = 0x02,
+}
+
 pub struct Attribute<'a> {
     pub uuid: Uuid,
     pub handle: u16,
@@ -55,6 +77,10 @@ pub enum AttributeData<'d> {
     Data {
         props: CharacteristicProps,
         value: &'d mut [u8],
+        /// Number of valid bytes in `value`.
+        len: usize,
+        /// Whether writes may change the length of the value.
+        variable_len: bool,
     },
     Declaration {
         props: CharacteristicProps,
@@ -65,19 +91,41 @@ pub enum AttributeData<'d> {
         notifications: bool,
         indications: bool,
     },
+    ExtendedProperties {
+        props: u8,
+    },
+    UserDescription {
+        value: &'d mut [u8],
+        len: usize,
+    },
+    ServerConfiguration {
+        broadcast: bool,
+    },
+    PresentationFormat(PresentationFormat),
+    ValidRange(ValidRange),
+    /// Value with a separate copy for each connection.
+    PerConnection {
+        props: CharacteristicProps,
+        /// Values of all connections, one after the other.
+        values: &'d mut [u8],
+        /// Connection owning each of the values.
+        owners: &'d mut [Option<ConnHandle>],
+    },
 }
 
 impl<'d> AttributeData<'d> {
     pub fn readable(&self) -> bool {
         match self {
-            Self::Data { props, value } => props.0 & (CharacteristicProp::Read as u8) != 0,
+            Self::Data { props, .. } | Self::PerConnection { props, .. } => {
+                props.0 & (CharacteristicProp::Read as u8) != 0
+            }
             _ => true,
         }
     }
 
     pub fn writable(&self) -> bool {
         match self {
-            Self::Data { props, value } => {
+            Self::Data { props, .. } | Self::PerConnection { props, .. } => {
                 props.0
                     & (CharacteristicProp::Write as u8
                         | CharacteristicProp::WriteWithoutResponse as u8
@@ -88,6 +136,7 @@ impl<'d> AttributeData<'d> {
                 notifications,
                 indications,
             } => true,
+            Self::UserDescription { .. } | Self::ServerConfiguration { .. } => true,
             _ => false,
         }
     }
@@ -97,26 +146,8 @@ impl<'d> AttributeData<'d> {
             return Err(AttErrorCode::ReadNotPermitted);
         }
         match self {
-            Self::ReadOnlyData { props, value } => {
-                if offset > value.len() {
-                    return Ok(0);
-                }
-                let len = data.len().min(value.len() - offset);
-                if len > 0 {
-                    data[..len].copy_from_slice(&value[offset..offset + len]);
-                }
-                Ok(len)
-            }
-            Self::Data { props, value } => {
-                if offset > value.len() {
-                    return Ok(0);
-                }
-                let len = data.len().min(value.len() - offset);
-                if len > 0 {
-                    data[..len].copy_from_slice(&value[offset..offset + len]);
-                }
-                Ok(len)
-            }
+            Self::ReadOnlyData { value, .. } => read_bytes(value, offset, data),
+            Self::Data { value, len, .. } => read_bytes(&value[..*len], offset, data),
             Self::Service { uuid } => {
                 let val = uuid.as_raw();
                 if offset > val.len() {
@@ -171,6 +202,41 @@ impl<'d> AttributeData<'d> {
                 }
                 Ok(w.len())
             }
+            Self::ExtendedProperties { props } => read_bytes(&[*props, 0], offset, data),
+            Self::UserDescription { value, len } => read_bytes(&value[..*len], offset, data),
+            Self::ServerConfiguration { broadcast } => read_bytes(&[*broadcast as u8, 0], offset, data),
+            Self::PresentationFormat(format) => read_bytes(&format.to_bytes(), offset, data),
+            Self::ValidRange(range) => {
+                let mut buf = [0; 16];
+                let len = range.encode(&mut buf);
+                read_bytes(&buf[..len], offset, data)
+            }
+            // Without a connection, the value is the one of a connection that didn't write it yet
+            Self::PerConnection { values, owners, .. } => {
+                let size = values.len() / owners.len();
+                if offset > size {
+                    return Err(AttErrorCode::InvalidOffset);
+                }
+                let len = data.len().min(size - offset);
+                data[..len].fill(0);
+                Ok(len)
+            }
+        }
+    }
+
+    /// Read the value as seen by a connection.
+    pub(crate) fn read_for(&self, conn: ConnHandle, offset: usize, data: &mut [u8]) -> Result<usize, AttErrorCode> {
+        match self {
+            Self::PerConnection { values, owners, .. } if self.readable() => {
+                match owners.iter().position(|owner| *owner == Some(conn)) {
+                    Some(slot) => {
+                        let size = values.len() / owners.len();
+                        read_bytes(&values[slot * size..(slot + 1) * size], offset, data)
+                    }
+                    None => self.read(offset, data),
+                }
+            }
+            _ => self.read(offset, data),
         }
     }
 
@@ -178,17 +244,29 @@ impl<'d> AttributeData<'d> {
         let writable = self.writable();
 
         match self {
-            Self::Data { value, props } => {
+            Self::Data {
+                value,
+                len,
+                variable_len,
+                ..
+            } => {
                 if !writable {
                     return Err(AttErrorCode::WriteNotPermitted);
                 }
 
-                if offset + data.len() <= value.len() {
-                    value[offset..offset + data.len()].copy_from_slice(data);
-                    Ok(())
-                } else {
-                    Err(AttErrorCode::InvalidOffset)
+                if offset > *len {
+                    return Err(AttErrorCode::InvalidOffset);
+                }
+
+                if offset + data.len() > value.len() {
+                    return Err(AttErrorCode::InvalidAttributeValueLength);
+                }
+
+                value[offset..offset + data.len()].copy_from_slice(data);
+                if *variable_len {
+                    *len = offset + data.len();
                 }
+                Ok(())
             }
             Self::Cccd {
                 notifications,
@@ -206,11 +284,246 @@ impl<'d> AttributeData<'d> {
                 *indications = data[0] & 0x02 != 0;
                 Ok(())
             }
+            Self::UserDescription { value, len } => {
+                if offset > *len {
+                    return Err(AttErrorCode::InvalidOffset);
+                }
+                if offset + data.len() > value.len() {
+                    return Err(AttErrorCode::InvalidAttributeValueLength);
+                }
+                value[offset..offset + data.len()].copy_from_slice(data);
+                *len = offset + data.len();
+                Ok(())
+            }
+            Self::ServerConfiguration { broadcast } => {
+                if offset > 0 {
+                    return Err(AttErrorCode::InvalidOffset);
+                }
+
+                if data.is_empty() {
+                    return Err(AttErrorCode::UnlikelyError);
+                }
+
+                *broadcast = data[0] & 0x01 != 0;
+                Ok(())
+            }
             _ => Err(AttErrorCode::WriteNotPermitted),
         }
     }
+
+    /// Write the value as seen by a connection.
+    ///
+    /// The first write from a connection to a per-connection value claims a copy of the value for it,
+    /// which fails with `InsufficientResources` if all copies are in use.
+    pub(crate) fn write_for(&mut self, conn: ConnHandle, offset: usize, data: &[u8]) -> Result<(), AttErrorCode> {
+        if !self.writable() {
+            return Err(AttErrorCode::WriteNotPermitted);
+        }
+        match self {
+            Self::PerConnection { values, owners, .. } => {
+                let size = values.len() / owners.len();
+                if offset > size {
+                    return Err(AttErrorCode::InvalidOffset);
+                }
+                if offset + data.len() > size {
+                    return Err(AttErrorCode::InvalidAttributeValueLength);
+                }
+                let value = claim(values, owners, conn).ok_or(AttErrorCode::InsufficientResources)?;
+                value[offset..offset + data.len()].copy_from_slice(data);
+                Ok(())
+            }
+            _ => self.write(offset, data),
+        }
+    }
+
+    /// Release the value held for a connection that was closed.
+    fn release(&mut self, conn: ConnHandle) {
+        if let Self::PerConnection { owners, .. } = self {
+            for owner in owners.iter_mut() {
+                if *owner == Some(conn) {
+                    owner.take();
+                }
+            }
+        }
+    }
+
+    fn is_group_boundary(&self) -> bool {
+        matches!(self, Self::Service { .. } | Self::Declaration { .. })
+    }
+}
+
+/// Find the value of a connection in per-connection storage, or claim a free one and clear it.
+fn claim<'a>(values: &'a mut [u8], owners: &mut [Option<ConnHandle>], conn: ConnHandle) -> Option<&'a mut [u8]> {
+    let size = values.len() / owners.len();
+    let slot = match owners.iter().position(|owner| *owner == Some(conn)) {
+        Some(slot) => slot,
+        None => {
+            let slot = owners.iter().position(|owner| owner.is_none())?;
+            owners[slot] = Some(conn);
+            values[slot * size..(slot + 1) * size].fill(0);
+            slot
+        }
+    };
+    Some(&mut values[slot * size..(slot + 1) * size])
+}
+
+fn read_bytes(value: &[u8], offset: usize, data: &mut [u8]) -> Result<usize, AttErrorCode> {
+    if offset > value.len() {
+        return Err(AttErrorCode::InvalidOffset);
+    }
+    let len = data.len().min(value.len() - offset);
+    if len > 0 {
+        data[..len].copy_from_slice(&value[offset..offset + len]);
+    }
+    Ok(len)
 }
 
+/// Value format of a Characteristic Presentation Format descriptor.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum ValueFormat {
+    Boolean = 0x01,
+    U2 = 0x02,
+    U4 = 0x03,
+    U8 = 0x04,
+    U12 = 0x05,
+    U16 = 0x06,
+    U24 = 0x07,
+    U32 = 0x08,
+    U48 = 0x09,
+    U64 = 0x0A,
+    U128 = 0x0B,
+    S8 = 0x0C,
+    S12 = 0x0D,
+    S16 = 0x0E,
+    S24 = 0x0F,
+    S32 = 0x10,
+    S48 = 0x11,
+    S64 = 0x12,
+    S128 = 0x13,
+    Float32 = 0x14,
+    Float64 = 0x15,
+    SFloat = 0x16,
+    Float = 0x17,
+    Duint16 = 0x18,
+    Utf8s = 0x19,
+    Utf16s = 0x1A,
+    Struct = 0x1B,
+}
+
+/// Contents of a Characteristic Presentation Format descriptor.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PresentationFormat {
+    pub format: ValueFormat,
+    pub exponent: i8,
+    /// Assigned number of the unit, e.g. 0x27AD for percentage.
+    pub unit: u16,
+    pub namespace: u8,
+    pub description: u16,
+}
+
+impl PresentationFormat {
+    /// Namespace of Bluetooth SIG assigned description values.
+    pub const NAMESPACE_BLUETOOTH_SIG: u8 = 0x01;
+
+    /// Create a presentation format in the Bluetooth SIG namespace with an unknown description.
+    pub fn new(format: ValueFormat, exponent: i8, unit: u16) -> Self {
+        Self {
+            format,
+            exponent,
+            unit,
+            namespace: Self::NAMESPACE_BLUETOOTH_SIG,
+            description: 0,
+        }
+    }
+
+    fn to_bytes(&self) -> [u8; 7] {
+        let unit = self.unit.to_le_bytes();
+        let description = self.description.to_le_bytes();
+        [
+            self.format as u8,
+            self.exponent as u8,
+            unit[0],
+            unit[1],
+            self.namespace,
+            description[0],
+            description[1],
+        ]
+    }
+}
+
+/// Inclusive bounds of a Valid Range descriptor.
+///
+/// The bounds are encoded using `size` bytes, which must match the size of the characteristic value.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidRange {
+    Unsigned { size: u8, lower: u64, upper: u64 },
+    Signed { size: u8, lower: i64, upper: i64 },
+}
+
+impl ValidRange {
+    fn size(&self) -> usize {
+        match self {
+            Self::Unsigned { size, .. } | Self::Signed { size, .. } => *size as usize,
+        }
+    }
+
+    fn encode(&self, dest: &mut [u8; 16]) -> usize {
+        let size = self.size();
+        let (lower, upper) = match self {
+            Self::Unsigned { lower, upper, .. } => (lower.to_le_bytes(), upper.to_le_bytes()),
+            Self::Signed { lower, upper, .. } => (lower.to_le_bytes(), upper.to_le_bytes()),
+        };
+        dest[..size].copy_from_slice(&lower[..size]);
+        dest[size..2 * size].copy_from_slice(&upper[..size]);
+        2 * size
+    }
+
+    /// Check if a little endian encoded value is within the range.
+    fn contains(&self, value: &[u8]) -> bool {
+        let size = self.size();
+        if value.len() != size {
+            return false;
+        }
+        match self {
+            Self::Unsigned { lower, upper, .. } => {
+                let mut buf = [0; 8];
+                buf[..size].copy_from_slice(value);
+                let v = u64::from_le_bytes(buf);
+                v >= *lower && v <= *upper
+            }
+            Self::Signed { lower, upper, .. } => {
+                let mut buf = if value[size - 1] & 0x80 != 0 { [0xFF; 8] } else { [0; 8] };
+                buf[..size].copy_from_slice(value);
+                let v = i64::from_le_bytes(buf);
+                v >= *lower && v <= *upper
+            }
+        }
+    }
+}
+
+macro_rules! valid_range_from {
+    ($variant:ident, $repr:ty, $($t:ty),+) => {
+        $(
+            impl From<RangeInclusive<$t>> for ValidRange {
+                fn from(range: RangeInclusive<$t>) -> Self {
+                    Self::$variant {
+                        size: core::mem::size_of::<$t>() as u8,
+                        lower: *range.start() as $repr,
+                        upper: *range.end() as $repr,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+valid_range_from!(Unsigned, u64, u8, u16, u32, u64);
+valid_range_from!(Signed, i64, i8, i16, i32, i64);
+
 impl<'a> fmt::Debug for Attribute<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Attribute")
@@ -246,9 +559,15 @@ pub struct AttributeTable<'d, M: RawMutex, const MAX: usize> {
     handle: u16,
 }
 
+// Number of tasks that can wait for changes without being woken spuriously.
+const MAX_WATCHERS: usize = 4;
+
 pub struct InnerTable<'d, const MAX: usize> {
     attributes: [Option<Attribute<'d>>; MAX],
     len: usize,
+    // Number of writes from peers to each attribute
+    writes: [u32; MAX],
+    watchers: MultiWakerRegistration<MAX_WATCHERS>,
 }
 
 impl<'d, const MAX: usize> InnerTable<'d, MAX> {
@@ -259,6 +578,70 @@ impl<'d, const MAX: usize> InnerTable<'d, MAX> {
         self.attributes[self.len].replace(attribute);
         self.len += 1;
     }
+
+    fn index_of(&self, handle: u16) -> Option<usize> {
+        self.attributes[..self.len]
+            .iter()
+            .position(|att| att.as_ref().map(|att| att.handle) == Some(handle))
+    }
+
+    fn writes(&self, handle: u16) -> u32 {
+        self.index_of(handle).map(|index| self.writes[index]).unwrap_or(0)
+    }
+
+    fn data(&self, index: usize) -> Option<&AttributeData<'d>> {
+        self.attributes[index].as_ref().map(|att| &att.data)
+    }
+
+    /// Range of indices for the declaration, value and descriptors of the characteristic containing `index`.
+    fn characteristic_group(&self, index: usize) -> Range<usize> {
+        let mut start = index;
+        while start > 0 && !self.data(start).map(|d| d.is_group_boundary()).unwrap_or(true) {
+            start -= 1;
+        }
+        let mut end = index + 1;
+        while end < self.len && !self.data(end).map(|d| d.is_group_boundary()).unwrap_or(true) {
+            end += 1;
+        }
+        start..end
+    }
+
+    /// Enforce the constraints that standard descriptors place on a write from a peer.
+    fn check_write(&self, index: usize, offset: usize, data: &[u8]) -> Result<(), AttErrorCode> {
+        let group = self.characteristic_group(index);
+        let declared_props = match self.data(group.start) {
+            Some(AttributeData::Declaration { props, .. }) => *props,
+            _ => return Ok(()),
+        };
+        match self.data(index) {
+            Some(AttributeData::UserDescription { .. }) => {
+                let writable = group.clone().any(|i| {
+                    matches!(self.data(i), Some(AttributeData::ExtendedProperties { props })
+                        if props & ExtendedProp::WritableAuxiliaries as u8 != 0)
+                });
+                if !writable {
+                    return Err(AttErrorCode::WriteNotPermitted);
+                }
+            }
+            Some(AttributeData::ServerConfiguration { .. }) => {
+                if !declared_props.any(&[CharacteristicProp::Broadcast]) {
+                    return Err(AttErrorCode::WriteNotPermitted);
+                }
+            }
+            Some(AttributeData::Data { .. } | AttributeData::PerConnection { .. }) if index == group.start + 1 => {
+                for i in group {
+                    if let Some(AttributeData::ValidRange(range)) = self.data(i) {
+                        // Only complete values can be checked, partial writes are accepted as is.
+                        if offset == 0 && data.len() == range.size() && !range.contains(data) {
+                            return Err(AttErrorCode::OutOfRange);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
 }
 
 impl<'d, M: RawMutex, const MAX: usize> Default for AttributeTable<'d, M, MAX> {
@@ -274,6 +657,8 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
             inner: Mutex::new(RefCell::new(InnerTable {
                 len: 0,
                 attributes: [Attribute::EMPTY; MAX],
+                writes: [0; MAX],
+                watchers: MultiWakerRegistration::new(),
             })),
         }
     }
@@ -325,18 +710,136 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
         }
     }
 
+    /// Write a value received from a peer to an attribute.
+    ///
+    /// Checks the constraints of the characteristic descriptors before writing, and passes the
+    /// attribute data to the provided closure after a successful write.
+    pub(crate) fn write_attribute<F: FnOnce(&Attribute<'d>) -> R, R>(
+        &self,
+        conn: ConnHandle,
+        handle: u16,
+        offset: usize,
+        data: &[u8],
+        f: F,
+    ) -> Result<R, AttErrorCode> {
+        self.inner.lock(|inner| {
+            let mut table = inner.borrow_mut();
+            let index = table.index_of(handle).ok_or(AttErrorCode::AttributeNotFound)?;
+            table.check_write(index, offset, data)?;
+            let att = table.attributes[index].as_mut().unwrap();
+            if !att.data.writable() {
+                return Err(AttErrorCode::WriteNotPermitted);
+            }
+            att.data.write_for(conn, offset, data)?;
+            table.writes[index] = table.writes[index].wrapping_add(1);
+            table.watchers.wake();
+            let att = table.attributes[index].as_ref().unwrap();
+            Ok(f(att))
+        })
+    }
+
+    /// Watch a characteristic for values written by peers.
+    ///
+    /// This lets a task wait for writes to its own characteristics, without processing the events returned by
+    /// [`GattServer::next`](crate::gatt::GattServer::next).
+    pub fn watch(&self, handle: Characteristic) -> Watch<'_, 'd, M, MAX> {
+        let seen = self.inner.lock(|inner| inner.borrow().writes(handle.handle));
+        Watch {
+            table: self,
+            handle: handle.handle,
+            seen,
+        }
+    }
+
     /// Set the value of a characteristic
     ///
-    /// The provided data must exactly match the size of the storage for the characteristic,
-    /// otherwise this function will panic.
+    /// For fixed length characteristics, the provided data must exactly match the size of the storage for the
+    /// characteristic, otherwise this function will panic. Variable length characteristics accept any value
+    /// that fits in the storage.
     ///
     /// If the characteristic for the handle cannot be found, an error is returned.
     pub fn set(&self, handle: Characteristic, input: &[u8]) -> Result<(), Error> {
         self.iterate(|mut it| {
             while let Some(att) = it.next() {
                 if att.handle == handle.handle {
-                    if let AttributeData::Data { props, value } = &mut att.data {
-                        assert_eq!(value.len(), input.len());
+                    if let AttributeData::Data {
+                        value,
+                        len,
+                        variable_len,
+                        ..
+                    } = &mut att.data
+                    {
+                        if *variable_len {
+                            if input.len() > value.len() {
+                                return Err(Error::InsufficientSpace);
+                            }
+                        } else {
+                            assert_eq!(value.len(), input.len());
+                        }
+                        value[..input.len()].copy_from_slice(input);
+                        *len = input.len();
+                        return Ok(());
+                    }
+                }
+            }
+            Err(Error::NotFound)
+        })
+    }
+
+    /// Set the value of a characteristic from a typed value.
+    ///
+    /// If the encoded value is shorter than the storage of a fixed length characteristic, the remaining
+    /// bytes are zeroed.
+    ///
+    /// If the characteristic for the handle cannot be found, an error is returned.
+    pub fn set_value<T: GattValue>(&self, handle: Characteristic, input: &T) -> Result<(), Error> {
+        self.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                if att.handle == handle.handle {
+                    if let AttributeData::Data {
+                        value,
+                        len,
+                        variable_len,
+                        ..
+                    } = &mut att.data
+                    {
+                        let written = input.to_gatt(value)?;
+                        if *variable_len {
+                            *len = written;
+                        } else {
+                            value[written..].fill(0);
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+            Err(Error::NotFound)
+        })
+    }
+
+    /// Read the value of a characteristic as a typed value.
+    ///
+    /// If the characteristic for the handle cannot be found, or the value cannot be decoded, an error is returned.
+    pub fn get_value<T: GattValue>(&self, handle: Characteristic) -> Result<T, Error> {
+        self.get(handle, |value| T::from_gatt(value))?
+    }
+
+    /// Set the value of a per-connection characteristic for a connection.
+    ///
+    /// This can be used to restore the value of a bonded peer when it reconnects. The provided data must
+    /// exactly match the size of the value.
+    ///
+    /// If the characteristic for the handle cannot be found, or all copies of the value are in use by other
+    /// connections, an error is returned.
+    pub fn set_for(&self, handle: Characteristic, connection: &Connection<'_>, input: &[u8]) -> Result<(), Error> {
+        self.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                if att.handle == handle.handle {
+                    if let AttributeData::PerConnection { values, owners, .. } = &mut att.data {
+                        if input.len() != values.len() / owners.len() {
+                            return Err(Error::InvalidValue);
+                        }
+                        let value = claim(values, owners, connection.handle()).ok_or(Error::OutOfMemory)?;
                         value.copy_from_slice(input);
                         return Ok(());
                     }
@@ -346,6 +849,51 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
         })
     }
 
+    /// Read the value of a per-connection characteristic for a connection, and pass it to the provided closure.
+    ///
+    /// Connections that didn't write the value yet see a zeroed value, without a copy being claimed for them.
+    ///
+    /// If the characteristic for the handle cannot be found, or the connection has no copy of the value and all
+    /// copies are in use by other connections, an error is returned.
+    pub fn get_for<F: FnMut(&[u8]) -> T, T>(
+        &self,
+        handle: Characteristic,
+        connection: &Connection<'_>,
+        mut f: F,
+    ) -> Result<T, Error> {
+        let conn = connection.handle();
+        self.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                if att.handle == handle.handle {
+                    if let AttributeData::PerConnection { values, owners, .. } = &mut att.data {
+                        let size = values.len() / owners.len();
+                        if let Some(slot) = owners.iter().position(|owner| *owner == Some(conn)) {
+                            return Ok(f(&values[slot * size..(slot + 1) * size]));
+                        }
+                        // Lend the space of an unused copy to show the zeroed value
+                        let slot = owners
+                            .iter()
+                            .position(|owner| owner.is_none())
+                            .ok_or(Error::OutOfMemory)?;
+                        let value = &mut values[slot * size..(slot + 1) * size];
+                        value.fill(0);
+                        return Ok(f(value));
+                    }
+                }
+            }
+            Err(Error::NotFound)
+        })
+    }
+
+    /// Clear the per-connection values held for a connection that was closed.
+    pub(crate) fn disconnected(&self, conn: ConnHandle) {
+        self.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                att.data.release(conn);
+            }
+        })
+    }
+
     /// Read the value of the characteristic and pass the value to the provided closure.
     ///
     /// The return value of the closure is returned in this function and is assumed to be infallible.
@@ -355,8 +903,8 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
         self.iterate(|mut it| {
             while let Some(att) = it.next() {
                 if att.handle == handle.handle {
-                    if let AttributeData::Data { props, value } = &mut att.data {
-                        let v = f(value);
+                    if let AttributeData::Data { value, len, .. } = &mut att.data {
+                        let v = f(&value[..*len]);
                         return Ok(v);
                     }
                 }
@@ -366,6 +914,36 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
     }
 }
 
+/// Watch on the value of a characteristic, created with [`AttributeTable::watch`].
+pub struct Watch<'t, 'd, M: RawMutex, const MAX: usize> {
+    table: &'t AttributeTable<'d, M, MAX>,
+    handle: u16,
+    seen: u32,
+}
+
+impl<'t, 'd, M: RawMutex, const MAX: usize> Watch<'t, 'd, M, MAX> {
+    /// Wait until a peer writes the value of the characteristic.
+    ///
+    /// Returns immediately if the value was written since the watch was created or the last change was
+    /// returned. Several writes in a row may be reported as a single change.
+    pub async fn changed(&mut self) {
+        poll_fn(|cx| {
+            self.table.inner.lock(|inner| {
+                let mut table = inner.borrow_mut();
+                let writes = table.writes(self.handle);
+                if writes != self.seen {
+                    self.seen = writes;
+                    Poll::Ready(())
+                } else {
+                    table.watchers.register(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct AttributeHandle {
@@ -378,6 +956,27 @@ impl From<u16> for AttributeHandle {
     }
 }
 
+/// Storage for a characteristic holding a value of `N` bytes for each of up to `CONNS` connections.
+pub struct PerConnectionStorage<const N: usize, const CONNS: usize> {
+    values: [[u8; N]; CONNS],
+    owners: [Option<ConnHandle>; CONNS],
+}
+
+impl<const N: usize, const CONNS: usize> PerConnectionStorage<N, CONNS> {
+    pub const fn new() -> Self {
+        Self {
+            values: [[0; N]; CONNS],
+            owners: [None; CONNS],
+        }
+    }
+}
+
+impl<const N: usize, const CONNS: usize> Default for PerConnectionStorage<N, CONNS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct ServiceBuilder<'r, 'd, M: RawMutex, const MAX: usize> {
     handle: AttributeHandle,
     start: usize,
@@ -445,7 +1044,65 @@ impl<'r, 'd, M: RawMutex, const MAX: usize> ServiceBuilder<'r, 'd, M, MAX> {
         storage: &'d mut [u8],
     ) -> CharacteristicBuilder<'_, 'd, M, MAX> {
         let props = props.into();
-        self.add_characteristic_internal(uuid.into(), props, AttributeData::Data { props, value: storage })
+        let len = storage.len();
+        self.add_characteristic_internal(
+            uuid.into(),
+            props,
+            AttributeData::Data {
+                props,
+                value: storage,
+                len,
+                variable_len: false,
+            },
+        )
+    }
+
+    /// Add a characteristic whose value length can change, up to the size of `storage`.
+    ///
+    /// The first `len` bytes of `storage` hold the initial value. Reads and notifications only include the
+    /// current value, and writes from peers set the length to the end of the written data.
+    pub fn add_characteristic_variable<U: Into<Uuid>>(
+        &mut self,
+        uuid: U,
+        props: &[CharacteristicProp],
+        storage: &'d mut [u8],
+        len: usize,
+    ) -> CharacteristicBuilder<'_, 'd, M, MAX> {
+        assert!(len <= storage.len());
+        let props = props.into();
+        self.add_characteristic_internal(
+            uuid.into(),
+            props,
+            AttributeData::Data {
+                props,
+                value: storage,
+                len,
+                variable_len: true,
+            },
+        )
+    }
+
+    /// Add a characteristic holding a separate value for each connection.
+    ///
+    /// Each connection reads and writes its own copy of the value, which is cleared when the connection is
+    /// closed. The storage should hold a value for each connection the host supports.
+    pub fn add_characteristic_per_connection<U: Into<Uuid>, const N: usize, const CONNS: usize>(
+        &mut self,
+        uuid: U,
+        props: &[CharacteristicProp],
+        storage: &'d mut PerConnectionStorage<N, CONNS>,
+    ) -> CharacteristicBuilder<'_, 'd, M, MAX> {
+        assert!(CONNS > 0);
+        let props = props.into();
+        self.add_characteristic_internal(
+            uuid.into(),
+            props,
+            AttributeData::PerConnection {
+                props,
+                values: storage.values.as_flattened_mut(),
+                owners: &mut storage.owners[..],
+            },
+        )
     }
 
     pub fn add_characteristic_ro<U: Into<Uuid>>(
@@ -464,7 +1121,7 @@ impl<'r, 'd, M: RawMutex, const MAX: usize> ServiceBuilder<'r, 'd, M, MAX> {
 
 impl<'r, 'd, M: RawMutex, const MAX: usize> Drop for ServiceBuilder<'r, 'd, M, MAX> {
     fn drop(&mut self) {
-        let last_handle = self.table.handle + 1;
+        let last_handle = self.table.handle - 1;
         self.table.with_inner(|inner| {
             for item in inner.attributes[self.start..inner.len].iter_mut() {
                 item.as_mut().unwrap().last_handle_in_group = last_handle;
@@ -483,6 +1140,18 @@ pub struct Characteristic {
     pub(crate) handle: u16,
 }
 
+impl Characteristic {
+    /// Handle of the characteristic value.
+    pub fn handle(&self) -> u16 {
+        self.handle
+    }
+
+    /// Handle of the client characteristic configuration descriptor, if any.
+    pub fn cccd_handle(&self) -> Option<u16> {
+        self.cccd_handle
+    }
+}
+
 pub struct CharacteristicBuilder<'r, 'd, M: RawMutex, const MAX: usize> {
     handle: Characteristic,
     table: &'r mut AttributeTable<'d, M, MAX>,
@@ -513,7 +1182,17 @@ impl<'r, 'd, M: RawMutex, const MAX: usize> CharacteristicBuilder<'r, 'd, M, MAX
         data: &'d mut [u8],
     ) -> DescriptorHandle {
         let props = props.into();
-        self.add_descriptor_internal(uuid.into(), props, AttributeData::Data { props, value: data })
+        let len = data.len();
+        self.add_descriptor_internal(
+            uuid.into(),
+            props,
+            AttributeData::Data {
+                props,
+                value: data,
+                len,
+                variable_len: false,
+            },
+        )
     }
 
     pub fn add_descriptor_ro<U: Into<Uuid>>(&mut self, uuid: U, data: &'d [u8]) -> DescriptorHandle {
@@ -521,6 +1200,78 @@ impl<'r, 'd, M: RawMutex, const MAX: usize> CharacteristicBuilder<'r, 'd, M, MAX
         self.add_descriptor_internal(uuid.into(), props, AttributeData::ReadOnlyData { props, value: data })
     }
 
+    /// Add a Characteristic Extended Properties descriptor.
+    ///
+    /// This also sets the extended properties bit in the characteristic declaration.
+    pub fn add_extended_properties(&mut self, props: &[ExtendedProp]) -> DescriptorHandle {
+        let declaration = self.handle.handle - 1;
+        self.table.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                if att.handle == declaration {
+                    if let AttributeData::Declaration { props, .. } = &mut att.data {
+                        props.0 |= CharacteristicProp::Extended as u8;
+                    }
+                    break;
+                }
+            }
+        });
+        let props = props.iter().fold(0, |acc, p| acc | *p as u8);
+        self.add_descriptor_internal(
+            CHARACTERISTIC_EXTENDED_PROPERTIES_UUID16,
+            [CharacteristicProp::Read].into(),
+            AttributeData::ExtendedProperties { props },
+        )
+    }
+
+    /// Add a Characteristic User Description descriptor that peers can write.
+    ///
+    /// The first `len` bytes of `storage` hold the initial description. Writes from peers are only
+    /// accepted if the writable auxiliaries extended property is set.
+    pub fn add_user_description(&mut self, storage: &'d mut [u8], len: usize) -> DescriptorHandle {
+        assert!(len <= storage.len());
+        self.add_descriptor_internal(
+            CHARACTERISTIC_USER_DESCRIPTION_UUID16,
+            [CharacteristicProp::Read, CharacteristicProp::Write].into(),
+            AttributeData::UserDescription { value: storage, len },
+        )
+    }
+
+    /// Add a read-only Characteristic User Description descriptor.
+    pub fn add_user_description_ro(&mut self, description: &'d str) -> DescriptorHandle {
+        self.add_descriptor_ro(CHARACTERISTIC_USER_DESCRIPTION_UUID16, description.as_bytes())
+    }
+
+    /// Add a Server Characteristic Configuration descriptor.
+    ///
+    /// Peers may only write to it if the characteristic has the broadcast property.
+    pub fn add_server_configuration(&mut self) -> DescriptorHandle {
+        self.add_descriptor_internal(
+            CHARACTERISTIC_SCCD_UUID16,
+            [CharacteristicProp::Read, CharacteristicProp::Write].into(),
+            AttributeData::ServerConfiguration { broadcast: false },
+        )
+    }
+
+    /// Add a Characteristic Presentation Format descriptor.
+    pub fn add_presentation_format(&mut self, format: PresentationFormat) -> DescriptorHandle {
+        self.add_descriptor_internal(
+            CHARACTERISTIC_PRESENTATION_FORMAT_UUID16,
+            [CharacteristicProp::Read].into(),
+            AttributeData::PresentationFormat(format),
+        )
+    }
+
+    /// Add a Valid Range descriptor.
+    ///
+    /// Writes from peers with a complete value outside of the range are rejected.
+    pub fn add_valid_range<R: Into<ValidRange>>(&mut self, range: R) -> DescriptorHandle {
+        self.add_descriptor_internal(
+            CHARACTERISTIC_VALID_RANGE_UUID16,
+            [CharacteristicProp::Read].into(),
+            AttributeData::ValidRange(range.into()),
+        )
+    }
+
     pub fn build(self) -> Characteristic {
         self.handle
     }
@@ -560,9 +1311,22 @@ impl Service {
     }
 }
 
-#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct CharacteristicProps(u8);
 
+impl From<u8> for CharacteristicProps {
+    fn from(props: u8) -> Self {
+        CharacteristicProps(props)
+    }
+}
+
+impl From<CharacteristicProps> for u8 {
+    fn from(props: CharacteristicProps) -> Self {
+        props.0
+    }
+}
+
 impl<'a> From<&'a [CharacteristicProp]> for CharacteristicProps {
     fn from(props: &'a [CharacteristicProp]) -> Self {
         let mut val: u8 = 0;
@@ -584,7 +1348,7 @@ impl<const T: usize> From<[CharacteristicProp; T]> for CharacteristicProps {
 }
 
 impl CharacteristicProps {
-    fn any(&self, props: &[CharacteristicProp]) -> bool {
+    pub(crate) fn any(&self, props: &[CharacteristicProp]) -> bool {
         for p in props {
             if (*p as u8) & self.0 != 0 {
                 return true;
@@ -5,7 +5,7 @@ use embassy_sync::blocking_mutex::raw::RawMutex;
 use embassy_sync::blocking_mutex::Mutex;
 
 use crate::att::{self, AttErrorCode, AttReq};
-use crate::attribute::{AttributeData, AttributeTable};
+use crate::attribute::{AttributeData, AttributeTable, CLIENT_SUPPORTED_FEATURES_UUID16};
 use crate::codec;
 use crate::cursor::WriteCursor;
 use crate::types::uuid::Uuid;
@@ -21,9 +21,16 @@ pub struct NotificationTable<const ENTRIES: usize> {
     state: [(u16, ConnHandle); ENTRIES],
 }
 
+/// Client Supported Features bit for Multiple Handle Value Notifications.
+pub(crate) const CLIENT_FEATURE_MULTIPLE_NOTIFICATIONS: u8 = 0x04;
+
+const MAX_CLIENTS: usize = 4;
+
 pub struct AttributeServer<'c, 'd, M: RawMutex, const MAX: usize> {
     pub(crate) table: &'c AttributeTable<'d, M, MAX>,
     pub(crate) notification: Mutex<M, RefCell<NotificationTable<MAX_NOTIFICATIONS>>>,
+    // Features declared by each client through the Client Supported Features characteristic
+    client_features: Mutex<M, RefCell<[Option<(ConnHandle, u8)>; MAX_CLIENTS]>>,
 }
 
 impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
@@ -34,9 +41,56 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
             notification: Mutex::new(RefCell::new(NotificationTable {
                 state: [(0, ConnHandle::new(0)); 4],
             })),
+            client_features: Mutex::new(RefCell::new([None; MAX_CLIENTS])),
         }
     }
 
+    /// Check if a client has declared support for Multiple Handle Value Notifications.
+    pub(crate) fn supports_multiple_notifications(&self, conn: ConnHandle) -> bool {
+        self.client_features.lock(|f| {
+            f.borrow()
+                .iter()
+                .flatten()
+                .any(|(c, features)| *c == conn && features & CLIENT_FEATURE_MULTIPLE_NOTIFICATIONS != 0)
+        })
+    }
+
+    fn set_client_features(&self, conn: ConnHandle, features: u8) {
+        self.client_features.lock(|f| {
+            let mut f = f.borrow_mut();
+            if let Some(entry) = f.iter_mut().flatten().find(|(c, _)| *c == conn) {
+                // Features can not be disabled once enabled by a client
+                entry.1 |= features;
+            } else if let Some(entry) = f.iter_mut().find(|e| e.is_none()) {
+                entry.replace((conn, features));
+            } else {
+                warn!("[gatt] no space to store client supported features");
+            }
+        })
+    }
+
+    /// Clear the state held for a connection that was closed.
+    ///
+    /// This includes the subscriptions and features of the client, and the values of per-connection attributes.
+    pub(crate) fn disconnected(&self, conn: ConnHandle) {
+        self.notification.lock(|n| {
+            for entry in n.borrow_mut().state.iter_mut() {
+                if entry.0 != 0 && entry.1 == conn {
+                    entry.0 = 0;
+                    entry.1 = ConnHandle::new(0);
+                }
+            }
+        });
+        self.client_features.lock(|f| {
+            for entry in f.borrow_mut().iter_mut() {
+                if entry.is_some_and(|(c, _)| c == conn) {
+                    entry.take();
+                }
+            }
+        });
+        self.table.disconnected(conn);
+    }
+
     pub(crate) fn should_notify(&self, conn: ConnHandle, cccd_handle: u16) -> bool {
         self.notification.lock(|n| {
             let n = n.borrow();
@@ -74,6 +128,7 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
 
     fn handle_read_by_type_req(
         &self,
+        conn: ConnHandle,
         buf: &mut [u8],
         start: u16,
         end: u16,
@@ -81,6 +136,9 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
     ) -> Result<usize, codec::Error> {
         let mut handle = start;
         let mut data = WriteCursor::new(buf);
+        if !valid_range(start, end) {
+            return Self::error_response(data, att::ATT_READ_BY_TYPE_REQ, start, AttErrorCode::InvalidHandle);
+        }
 
         let (mut header, mut body) = data.split(2)?;
         let err = self.table.iterate(|mut it| {
@@ -92,7 +150,7 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
                     handle = att.handle;
 
                     if att.data.readable() {
-                        err = att.data.read(0, body.write_buf());
+                        err = att.data.read_for(conn, 0, body.write_buf());
                         if let Ok(len) = &err {
                             body.commit(*len)?;
                         }
@@ -117,6 +175,7 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
 
     fn handle_read_by_group_type_req(
         &self,
+        conn: ConnHandle,
         buf: &mut [u8],
         start: u16,
         end: u16,
@@ -125,6 +184,14 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         // TODO respond with all finds - not just one
         let mut handle = start;
         let mut data = WriteCursor::new(buf);
+        if !valid_range(start, end) {
+            return Self::error_response(
+                data,
+                att::ATT_READ_BY_GROUP_TYPE_REQ,
+                start,
+                AttErrorCode::InvalidHandle,
+            );
+        }
 
         let (mut header, mut body) = data.split(2)?;
         let err = self.table.iterate(|mut it| {
@@ -139,7 +206,7 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
                     body.write(att.last_handle_in_group)?;
 
                     if att.data.readable() {
-                        err = att.data.read(0, body.write_buf());
+                        err = att.data.read_for(conn, 0, body.write_buf());
                         if let Ok(len) = &err {
                             body.commit(*len)?;
                         }
@@ -160,7 +227,7 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         }
     }
 
-    fn handle_read_req(&self, buf: &mut [u8], handle: u16) -> Result<usize, codec::Error> {
+    fn handle_read_req(&self, conn: ConnHandle, buf: &mut [u8], handle: u16) -> Result<usize, codec::Error> {
         let mut data = WriteCursor::new(buf);
 
         data.write(att::ATT_READ_RSP)?;
@@ -170,7 +237,7 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
             while let Some(att) = it.next() {
                 if att.handle == handle {
                     if att.data.readable() {
-                        err = att.data.read(0, data.write_buf());
+                        err = att.data.read_for(conn, 0, data.write_buf());
                         if let Ok(len) = err {
                             data.commit(len)?;
                         }
@@ -187,20 +254,21 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         }
     }
 
-    fn handle_write_cmd(&self, buf: &mut [u8], handle: u16, data: &[u8]) -> Result<usize, codec::Error> {
+    fn handle_write_cmd(
+        &self,
+        conn: ConnHandle,
+        buf: &mut [u8],
+        handle: u16,
+        data: &[u8],
+    ) -> Result<usize, codec::Error> {
         // TODO: Generate event
-        self.table.iterate(|mut it| {
-            while let Some(att) = it.next() {
-                if att.handle == handle {
-                    if att.data.writable() {
-                        // Write commands can't respond with an error.
-                        att.data.write(0, data).unwrap();
-                    }
-                    break;
-                }
+        // Write commands can't respond with an error.
+        let _ = self.table.write_attribute(conn, handle, 0, data, |att| {
+            if att.uuid == CLIENT_SUPPORTED_FEATURES_UUID16 {
+                self.set_client_features(conn, data.first().copied().unwrap_or(0));
             }
-            Ok(0)
-        })
+        });
+        Ok(0)
     }
 
     fn handle_write_req(
@@ -210,26 +278,12 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         handle: u16,
         data: &[u8],
     ) -> Result<usize, codec::Error> {
-        let err = self.table.iterate(|mut it| {
-            let mut err = Err(AttErrorCode::AttributeNotFound);
-            while let Some(att) = it.next() {
-                if att.handle == handle {
-                    if att.data.writable() {
-                        err = att.data.write(0, data);
-                        if err.is_ok() {
-                            if let AttributeData::Cccd {
-                                notifications,
-                                indications,
-                            } = att.data
-                            {
-                                self.set_notify(conn, handle, notifications);
-                            }
-                        }
-                    }
-                    break;
-                }
+        let err = self.table.write_attribute(conn, handle, 0, data, |att| {
+            if let AttributeData::Cccd { notifications, .. } = &att.data {
+                self.set_notify(conn, handle, *notifications);
+            } else if att.uuid == CLIENT_SUPPORTED_FEATURES_UUID16 {
+                self.set_client_features(conn, data.first().copied().unwrap_or(0));
             }
-            err
         });
 
         let mut w = WriteCursor::new(buf);
@@ -251,6 +305,9 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         attr_value: &[u8],
     ) -> Result<usize, codec::Error> {
         let mut w = WriteCursor::new(buf);
+        if !valid_range(start, end) {
+            return Self::error_response(w, att::ATT_FIND_BY_TYPE_VALUE_REQ, start, AttErrorCode::InvalidHandle);
+        }
         let attr_type = Uuid::new_short(attr_type);
 
         w.write(att::ATT_FIND_BY_TYPE_VALUE_RSP)?;
@@ -285,6 +342,9 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
 
     fn handle_find_information(&self, buf: &mut [u8], start: u16, end: u16) -> Result<usize, codec::Error> {
         let mut w = WriteCursor::new(buf);
+        if !valid_range(start, end) {
+            return Self::error_response(w, att::ATT_FIND_INFORMATION_REQ, start, AttErrorCode::InvalidHandle);
+        }
 
         let (mut header, mut body) = w.split(2)?;
 
@@ -335,6 +395,7 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
 
     fn handle_prepare_write(
         &self,
+        conn: ConnHandle,
         buf: &mut [u8],
         handle: u16,
         offset: u16,
@@ -345,22 +406,13 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         w.write(handle)?;
         w.write(offset)?;
 
-        let err = self.table.iterate(|mut it| {
-            let mut err = Err(AttErrorCode::AttributeNotFound);
-            while let Some(att) = it.next() {
-                if att.handle == handle {
-                    if att.data.writable() {
-                        err = att.data.write(offset as usize, value);
-                    }
-                    w.append(value)?;
-                    break;
-                }
-            }
-            err
-        });
+        let err = self.table.write_attribute(conn, handle, offset as usize, value, |_| {});
 
         match err {
-            Ok(()) => Ok(w.len()),
+            Ok(()) => {
+                w.append(value)?;
+                Ok(w.len())
+            }
             Err(e) => Ok(Self::error_response(w, att::ATT_PREPARE_WRITE_REQ, handle, e)?),
         }
     }
@@ -371,7 +423,13 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         Ok(w.len())
     }
 
-    fn handle_read_blob(&self, buf: &mut [u8], handle: u16, offset: u16) -> Result<usize, codec::Error> {
+    fn handle_read_blob(
+        &self,
+        conn: ConnHandle,
+        buf: &mut [u8],
+        handle: u16,
+        offset: u16,
+    ) -> Result<usize, codec::Error> {
         let mut w = WriteCursor::new(buf);
         w.write(att::ATT_READ_BLOB_RSP)?;
 
@@ -380,7 +438,7 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
             while let Some(att) = it.next() {
                 if att.handle == handle {
                     if att.data.readable() {
-                        err = att.data.read(offset as usize, w.write_buf());
+                        err = att.data.read_for(conn, offset as usize, w.write_buf());
                         if let Ok(n) = &err {
                             w.commit(*n)?;
                         }
@@ -397,14 +455,94 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         }
     }
 
-    fn handle_read_multiple(&self, buf: &mut [u8], handles: &[u8]) -> Result<usize, codec::Error> {
-        let w = WriteCursor::new(buf);
-        Self::error_response(
-            w,
-            att::ATT_READ_MULTIPLE_REQ,
-            u16::from_le_bytes([handles[0], handles[1]]),
-            AttErrorCode::AttributeNotFound,
-        )
+    fn handle_read_multiple(
+        &self,
+        conn: ConnHandle,
+        buf: &mut [u8],
+        opcode: u8,
+        handles: &[u8],
+    ) -> Result<usize, codec::Error> {
+        let handles = handles.chunks_exact(2).map(|h| u16::from_le_bytes([h[0], h[1]]));
+
+        // The whole request is rejected with the first handle that can't be read
+        for handle in handles.clone() {
+            let err = self.table.iterate(|mut it| {
+                while let Some(att) = it.next() {
+                    if att.handle == handle {
+                        return match att.data.readable() {
+                            true => Ok(()),
+                            false => Err(AttErrorCode::ReadNotPermitted),
+                        };
+                    }
+                }
+                Err(AttErrorCode::InvalidHandle)
+            });
+            if let Err(e) = err {
+                return Self::error_response(WriteCursor::new(buf), opcode, handle, e);
+            }
+        }
+
+        // Values are concatenated, or prefixed with their length for Read Multiple Variable, and the last one is
+        // truncated to fit the response
+        let variable = opcode == att::ATT_READ_MULTIPLE_VARIABLE_REQ;
+        let mut w = WriteCursor::new(buf);
+        w.write(if variable {
+            att::ATT_READ_MULTIPLE_VARIABLE_RSP
+        } else {
+            att::ATT_READ_MULTIPLE_RSP
+        })?;
+        let mut failed = None;
+        for handle in handles {
+            let err = self.table.iterate(|mut it| {
+                while let Some(att) = it.next() {
+                    if att.handle == handle {
+                        if !variable {
+                            let len = att.data.read_for(conn, 0, w.write_buf())?;
+                            w.commit(len)?;
+                        } else if w.available() > 2 {
+                            let (mut header, mut body) = w.split(2)?;
+                            let len = att.data.read_for(conn, 0, body.write_buf())?;
+                            header.write(value_len(&att.data, conn, len) as u16)?;
+                            w.commit(2 + len)?;
+                        }
+                        break;
+                    }
+                }
+                Ok(())
+            });
+            if let Err(e) = err {
+                failed.replace((handle, e));
+                break;
+            }
+            if w.available() == 0 {
+                break;
+            }
+        }
+
+        let len = w.len();
+        match failed {
+            Some((handle, e)) => Self::error_response(WriteCursor::new(buf), opcode, handle, e),
+            None => Ok(len),
+        }
+    }
+
+    /// Produce a response for a PDU that could not be decoded as a request.
+    ///
+    /// Unknown requests are rejected with `RequestNotSupported`, and malformed ones with `InvalidPdu`.
+    /// Commands and other PDUs that don't expect a response are ignored.
+    pub(crate) fn reject(&self, pdu: &[u8], rx: &mut [u8]) -> Result<Option<usize>, codec::Error> {
+        let Some(&opcode) = pdu.first() else {
+            return Ok(None);
+        };
+        if !att::expects_response(opcode) {
+            return Ok(None);
+        }
+        let code = if AttReq::is_known(opcode) {
+            AttErrorCode::InvalidPdu
+        } else {
+            AttErrorCode::RequestNotSupported
+        };
+        Ok(Some(Self::error_response(WriteCursor::new(rx), opcode, 0, code)?))
     }
 
     /// Process an event and produce a response if necessary
@@ -414,20 +552,20 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
                 start,
                 end,
                 attribute_type,
-            } => self.handle_read_by_type_req(rx, *start, *end, attribute_type)?,
+            } => self.handle_read_by_type_req(conn, rx, *start, *end, attribute_type)?,
 
             AttReq::ReadByGroupType { start, end, group_type } => {
-                self.handle_read_by_group_type_req(rx, *start, *end, group_type)?
+                self.handle_read_by_group_type_req(conn, rx, *start, *end, group_type)?
             }
             AttReq::FindInformation {
                 start_handle,
                 end_handle,
             } => self.handle_find_information(rx, *start_handle, *end_handle)?,
 
-            AttReq::Read { handle } => self.handle_read_req(rx, *handle)?,
+            AttReq::Read { handle } => self.handle_read_req(conn, rx, *handle)?,
 
             AttReq::WriteCmd { handle, data } => {
-                self.handle_write_cmd(rx, *handle, data)?;
+                self.handle_write_cmd(conn, rx, *handle, data)?;
                 0
             }
 
@@ -442,13 +580,20 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
                 att_value,
             } => self.handle_find_type_value(rx, *start_handle, *end_handle, *att_type, att_value)?,
 
-            AttReq::PrepareWrite { handle, offset, value } => self.handle_prepare_write(rx, *handle, *offset, value)?,
+            AttReq::PrepareWrite { handle, offset, value } => {
+                self.handle_prepare_write(conn, rx, *handle, *offset, value)?
+            }
 
             AttReq::ExecuteWrite { flags } => self.handle_execute_write(rx, *flags)?,
 
-            AttReq::ReadBlob { handle, offset } => self.handle_read_blob(rx, *handle, *offset)?,
+            AttReq::ReadBlob { handle, offset } => self.handle_read_blob(conn, rx, *handle, *offset)?,
 
-            AttReq::ReadMultiple { handles } => self.handle_read_multiple(rx, handles)?,
+            AttReq::ReadMultiple { handles } => {
+                self.handle_read_multiple(conn, rx, att::ATT_READ_MULTIPLE_REQ, handles)?
+            }
+            AttReq::ReadMultipleVariable { handles } => {
+                self.handle_read_multiple(conn, rx, att::ATT_READ_MULTIPLE_VARIABLE_REQ, handles)?
+            }
         };
         if len > 0 {
             Ok(Some(len))
@@ -457,3 +602,354 @@ impl<'c, 'd, M: RawMutex, const MAX: usize> AttributeServer<'c, 'd, M, MAX> {
         }
     }
 }
+
+/// Full length of the value of an attribute as seen by a connection, of which the first `read` bytes were read.
+fn value_len(data: &AttributeData<'_>, conn: ConnHandle, mut read: usize) -> usize {
+    let mut chunk = [0; 32];
+    loop {
+        match data.read_for(conn, read, &mut chunk) {
+            Ok(len) if len > 0 => {
+                read += len;
+                if len < chunk.len() {
+                    return read;
+                }
+            }
+            _ => return read,
+        }
+    }
+}
+
+/// Check the handle range of a discovery request, which must start at a valid handle.
+fn valid_range(start: u16, end: u16) -> bool {
+    start != 0 && start <= end
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::poll_once;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use super::*;
+    use crate::attribute::{CharacteristicProp, ExtendedProp, PerConnectionStorage, Service};
+
+    /// Decode and process a request the way the GATT server does.
+    fn exchange<'a, const MAX: usize>(
+        server: &AttributeServer<'_, '_, NoopRawMutex, MAX>,
+        req: &[u8],
+        rsp: &'a mut [u8],
+    ) -> Option<&'a [u8]> {
+        let conn = ConnHandle::new(1);
+        let len = match AttReq::decode(req) {
+            Ok(att) => server.process(conn, &att, rsp).unwrap(),
+            Err(_) => server.reject(req, rsp).unwrap(),
+        }?;
+        Some(&rsp[..len])
+    }
+
+    #[test]
+    fn requests() {
+        // Handles: service 1, characteristic declaration 2, value 3, CCCD 4, declaration 5, write-only value 6
+        let mut value = [0x55];
+        let mut secret = [0x00];
+        let mut table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(0x180f));
+        service
+            .add_characteristic(
+                0x2a19,
+                &[CharacteristicProp::Read, CharacteristicProp::Notify],
+                &mut value,
+            )
+            .build();
+        service
+            .add_characteristic(0x2a1a, &[CharacteristicProp::Write], &mut secret)
+            .build();
+        drop(service);
+        let server = AttributeServer::new(&table);
+
+        #[rustfmt::skip]
+        let cases: &[(&str, &[u8], Option<&[u8]>)] = &[
+            ("read", &[0x0a, 0x03, 0x00], Some(&[0x0b, 0x55])),
+            ("read unknown handle", &[0x0a, 0x09, 0x00], Some(&[0x01, 0x0a, 0x09, 0x00, 0x0a])),
+            ("read truncated", &[0x0a, 0x03], Some(&[0x01, 0x0a, 0x00, 0x00, 0x04])),
+            ("read blob truncated", &[0x0c, 0x03, 0x00, 0x01], Some(&[0x01, 0x0c, 0x00, 0x00, 0x04])),
+            ("write cccd", &[0x12, 0x04, 0x00, 0x01, 0x00], Some(&[0x13])),
+            ("write truncated", &[0x12, 0x04], Some(&[0x01, 0x12, 0x00, 0x00, 0x04])),
+            ("write command truncated", &[0x52, 0x04], None),
+            ("read multiple single handle", &[0x0e, 0x03, 0x00], Some(&[0x01, 0x0e, 0x00, 0x00, 0x04])),
+            ("read multiple variable odd length", &[0x20, 0x03, 0x00, 0x04], Some(&[0x01, 0x20, 0x00, 0x00, 0x04])),
+            ("read multiple", &[0x0e, 0x03, 0x00, 0x02, 0x00], Some(&[0x0f, 0x55, 0x12, 0x03, 0x00, 0x19, 0x2a])),
+            ("read multiple not permitted", &[0x0e, 0x03, 0x00, 0x06, 0x00], Some(&[0x01, 0x0e, 0x06, 0x00, 0x02])),
+            ("read multiple unknown handle", &[0x0e, 0x09, 0x00, 0x06, 0x00], Some(&[0x01, 0x0e, 0x09, 0x00, 0x01])),
+            ("read multiple variable", &[0x20, 0x03, 0x00, 0x02, 0x00], Some(&[0x21, 0x01, 0x00, 0x55, 0x05, 0x00, 0x12, 0x03, 0x00, 0x19, 0x2a])),
+            ("read multiple variable not permitted", &[0x20, 0x06, 0x00, 0x03, 0x00], Some(&[0x01, 0x20, 0x06, 0x00, 0x02])),
+            ("read by type", &[0x08, 0x01, 0x00, 0xff, 0xff, 0x03, 0x28], Some(&[0x09, 0x07, 0x02, 0x00, 0x12, 0x03, 0x00, 0x19, 0x2a])),
+            ("read by group type bad uuid", &[0x10, 0x01, 0x00, 0xff, 0xff, 0x00], Some(&[0x01, 0x10, 0x00, 0x00, 0x04])),
+            ("read by group type start 0", &[0x10, 0x00, 0x00, 0xff, 0xff, 0x00, 0x28], Some(&[0x01, 0x10, 0x00, 0x00, 0x01])),
+            ("read by type start > end", &[0x08, 0x05, 0x00, 0x01, 0x00, 0x03, 0x28], Some(&[0x01, 0x08, 0x05, 0x00, 0x01])),
+            ("find information start 0", &[0x04, 0x00, 0x00, 0xff, 0xff], Some(&[0x01, 0x04, 0x00, 0x00, 0x01])),
+            ("find information start > end", &[0x04, 0x03, 0x00, 0x02, 0x00], Some(&[0x01, 0x04, 0x03, 0x00, 0x01])),
+            ("find by type value start 0", &[0x06, 0x00, 0x00, 0xff, 0xff, 0x00, 0x28, 0x0f, 0x18], Some(&[0x01, 0x06, 0x00, 0x00, 0x01])),
+            ("unknown request", &[0x30, 0x01, 0x00], Some(&[0x01, 0x30, 0x00, 0x00, 0x06])),
+            ("unknown command", &[0x70, 0x01, 0x00], None),
+            ("confirmation", &[0x1e], None),
+            ("response", &[0x0b, 0x55], None),
+            ("empty", &[], None),
+        ];
+
+        for (name, req, expected) in cases {
+            let mut rsp = [0; 32];
+            assert_eq!(exchange(&server, req, &mut rsp), *expected, "{}", name);
+        }
+
+        // Values are truncated to the MTU, with the full length of a truncated variable length value
+        let mut rsp = [0; 3];
+        let read_multiple = [0x0e, 0x03, 0x00, 0x01, 0x00];
+        assert_eq!(
+            exchange(&server, &read_multiple, &mut rsp),
+            Some(&[0x0f, 0x55, 0x0f][..])
+        );
+        let mut rsp = [0; 7];
+        let read_multiple_variable = [0x20, 0x03, 0x00, 0x01, 0x00];
+        assert_eq!(
+            exchange(&server, &read_multiple_variable, &mut rsp),
+            Some(&[0x21, 0x01, 0x00, 0x55, 0x02, 0x00, 0x0f][..])
+        );
+    }
+
+    #[test]
+    fn descriptors_enforced() {
+        // Handles: service 1
+        // - declaration 2, value 3, valid range 4, extended properties 5, user description 6
+        // - declaration 7, value 8, user description 9, server configuration 10
+        // - declaration 11, value 12, server configuration 13
+        let mut level = [0x10];
+        let mut description = *b"bat\0\0\0\0\0";
+        let mut other = [0x00];
+        let mut other_description = *b"other\0\0\0";
+        let mut broadcast = [0x00];
+        let mut table: AttributeTable<'_, NoopRawMutex, 16> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(0x180f));
+        let mut characteristic = service.add_characteristic(
+            0x2a19,
+            &[CharacteristicProp::Read, CharacteristicProp::Write],
+            &mut level,
+        );
+        characteristic.add_valid_range(0x10u8..=0x20);
+        characteristic.add_extended_properties(&[ExtendedProp::WritableAuxiliaries]);
+        characteristic.add_user_description(&mut description, 3);
+        characteristic.build();
+        let mut characteristic = service.add_characteristic(
+            0x2a1a,
+            &[CharacteristicProp::Read, CharacteristicProp::Write],
+            &mut other,
+        );
+        characteristic.add_user_description(&mut other_description, 5);
+        characteristic.add_server_configuration();
+        characteristic.build();
+        let mut characteristic = service.add_characteristic(
+            0x2a1b,
+            &[CharacteristicProp::Read, CharacteristicProp::Broadcast],
+            &mut broadcast,
+        );
+        characteristic.add_server_configuration();
+        characteristic.build();
+        drop(service);
+        let server = AttributeServer::new(&table);
+
+        #[rustfmt::skip]
+        let steps: &[(&str, &[u8], Option<&[u8]>)] = &[
+            ("read valid range", &[0x0a, 0x04, 0x00], Some(&[0x0b, 0x10, 0x20])),
+            ("write in range", &[0x12, 0x03, 0x00, 0x15], Some(&[0x13])),
+            ("write out of range", &[0x12, 0x03, 0x00, 0xff], Some(&[0x01, 0x12, 0x03, 0x00, 0xff])),
+            ("read in range", &[0x0a, 0x03, 0x00], Some(&[0x0b, 0x15])),
+            ("read extended properties", &[0x0a, 0x05, 0x00], Some(&[0x0b, 0x02, 0x00])),
+            ("write user description with writable auxiliaries", &[0x12, 0x06, 0x00, b'c', b'e', b'l', b'l'], Some(&[0x13])),
+            ("read user description", &[0x0a, 0x06, 0x00], Some(&[0x0b, b'c', b'e', b'l', b'l'])),
+            ("write user description without writable auxiliaries", &[0x12, 0x09, 0x00, b'x'], Some(&[0x01, 0x12, 0x09, 0x00, 0x03])),
+            ("read user description unchanged", &[0x0a, 0x09, 0x00], Some(&[0x0b, b'o', b't', b'h', b'e', b'r'])),
+            ("write server configuration without broadcast", &[0x12, 0x0a, 0x00, 0x01, 0x00], Some(&[0x01, 0x12, 0x0a, 0x00, 0x03])),
+            ("read server configuration", &[0x0a, 0x0a, 0x00], Some(&[0x0b, 0x00, 0x00])),
+            ("write server configuration with broadcast", &[0x12, 0x0d, 0x00, 0x01, 0x00], Some(&[0x13])),
+            ("read broadcast enabled", &[0x0a, 0x0d, 0x00], Some(&[0x0b, 0x01, 0x00])),
+        ];
+
+        for (name, req, expected) in steps {
+            let mut rsp = [0; 32];
+            assert_eq!(exchange(&server, req, &mut rsp), *expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn variable_length_values() {
+        // Handles: service 1, characteristic declaration 2, value 3
+        let mut storage = [0; 20];
+        let mut table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(0x180f));
+        service
+            .add_characteristic_variable(
+                0x2a00,
+                &[CharacteristicProp::Read, CharacteristicProp::Write],
+                &mut storage,
+                0,
+            )
+            .build();
+        drop(service);
+        let server = AttributeServer::new(&table);
+
+        // Each step depends on the value left by the previous ones
+        #[rustfmt::skip]
+        let steps: &[(&str, &[u8], Option<&[u8]>)] = &[
+            ("read empty", &[0x0a, 0x03, 0x00], Some(&[0x0b])),
+            ("write", &[0x12, 0x03, 0x00, b'a', b'b', b'c'], Some(&[0x13])),
+            ("read written", &[0x0a, 0x03, 0x00], Some(&[0x0b, b'a', b'b', b'c'])),
+            ("write too long", &[0x12, 0x03, 0x00, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21], Some(&[0x01, 0x12, 0x03, 0x00, 0x0d])),
+            ("read unchanged", &[0x0a, 0x03, 0x00], Some(&[0x0b, b'a', b'b', b'c'])),
+            ("prepare write at offset", &[0x16, 0x03, 0x00, 0x03, 0x00, b'd', b'e'], Some(&[0x17, 0x03, 0x00, 0x03, 0x00, b'd', b'e'])),
+            ("execute write", &[0x18, 0x01], Some(&[0x19])),
+            ("read extended", &[0x0a, 0x03, 0x00], Some(&[0x0b, b'a', b'b', b'c', b'd', b'e'])),
+            ("read blob", &[0x0c, 0x03, 0x00, 0x02, 0x00], Some(&[0x0d, b'c', b'd', b'e'])),
+            ("prepare write past the end", &[0x16, 0x03, 0x00, 0x06, 0x00, b'x'], Some(&[0x01, 0x16, 0x03, 0x00, 0x07])),
+            ("prepare write beyond the storage", &[0x16, 0x03, 0x00, 0x05, 0x00, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16], Some(&[0x01, 0x16, 0x03, 0x00, 0x0d])),
+            ("write shorter", &[0x12, 0x03, 0x00, b'z'], Some(&[0x13])),
+            ("read shortened", &[0x0a, 0x03, 0x00], Some(&[0x0b, b'z'])),
+        ];
+
+        for (name, req, expected) in steps {
+            let mut rsp = [0; 32];
+            assert_eq!(exchange(&server, req, &mut rsp), *expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn service_groups() {
+        // Handles: service 1 with characteristic declaration 2 and value 3, then service 0x10 with 0x11 and 0x12
+        let mut level = [0x55];
+        let mut name = [0; 1];
+        let mut table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(0x180f));
+        service
+            .add_characteristic(0x2a19, &[CharacteristicProp::Read], &mut level)
+            .build();
+        drop(service);
+        let mut service = table.add_service(Service::new(0x180a));
+        service
+            .add_characteristic(0x2a29, &[CharacteristicProp::Read], &mut name)
+            .build();
+        drop(service);
+        let server = AttributeServer::new(&table);
+
+        // Each service group ends at the last handle of the service
+        let mut rsp = [0; 32];
+        let req = [0x10, 0x01, 0x00, 0xff, 0xff, 0x00, 0x28];
+        let expected: &[u8] = &[0x11, 0x06, 0x01, 0x00, 0x03, 0x00, 0x0f, 0x18];
+        assert_eq!(exchange(&server, &req, &mut rsp), Some(expected));
+        let req = [0x10, 0x04, 0x00, 0xff, 0xff, 0x00, 0x28];
+        let expected: &[u8] = &[0x11, 0x06, 0x10, 0x00, 0x12, 0x00, 0x0a, 0x18];
+        assert_eq!(exchange(&server, &req, &mut rsp), Some(expected));
+    }
+
+    #[test]
+    fn watch_changes() {
+        // Handles: service 1, characteristic declaration 2, value 3
+        let mut value = [0; 1];
+        let mut table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(0x180f));
+        let characteristic = service
+            .add_characteristic(
+                0x2a19,
+                &[CharacteristicProp::Read, CharacteristicProp::Write],
+                &mut value,
+            )
+            .build();
+        drop(service);
+        let server = AttributeServer::new(&table);
+
+        let mut watch = table.watch(characteristic);
+        assert!(poll_once(watch.changed()).is_pending());
+
+        let mut rsp = [0; 32];
+        let write = AttReq::Write {
+            handle: 3,
+            data: &[0x42],
+        };
+        server.process(ConnHandle::new(1), &write, &mut rsp).unwrap();
+        server.process(ConnHandle::new(1), &write, &mut rsp).unwrap();
+        assert!(poll_once(watch.changed()).is_ready());
+        assert!(poll_once(watch.changed()).is_pending());
+    }
+
+    #[test]
+    fn subscriptions_released_on_disconnect() {
+        // Handles: service 1, characteristic declaration 2, value 3, CCCD 4
+        let mut value = [0; 1];
+        let mut table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(0x180f));
+        service
+            .add_characteristic(
+                0x2a19,
+                &[CharacteristicProp::Read, CharacteristicProp::Notify],
+                &mut value,
+            )
+            .build();
+        drop(service);
+        let server = AttributeServer::new(&table);
+
+        let (conn1, conn2) = (ConnHandle::new(1), ConnHandle::new(2));
+        let mut rsp = [0; 32];
+        let subscribe = AttReq::Write {
+            handle: 4,
+            data: &[0x01, 0x00],
+        };
+        server.process(conn1, &subscribe, &mut rsp).unwrap();
+        server.process(conn2, &subscribe, &mut rsp).unwrap();
+        assert!(server.should_notify(conn1, 4));
+        assert!(server.should_notify(conn2, 4));
+
+        // Only the subscription of the closed connection is released
+        server.disconnected(conn1);
+        assert!(!server.should_notify(conn1, 4));
+        assert!(server.should_notify(conn2, 4));
+    }
+
+    #[test]
+    fn per_connection_values() {
+        // Handles: service 1, characteristic declaration 2, value 3
+        let mut storage: PerConnectionStorage<2, 2> = PerConnectionStorage::new();
+        let mut table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(0x180f));
+        service
+            .add_characteristic_per_connection(
+                0x2a19,
+                &[CharacteristicProp::Read, CharacteristicProp::Write],
+                &mut storage,
+            )
+            .build();
+        drop(service);
+        let server = AttributeServer::new(&table);
+
+        let (conn1, conn2, conn3) = (ConnHandle::new(1), ConnHandle::new(2), ConnHandle::new(3));
+        let mut rsp = [0; 32];
+        let write = AttReq::Write {
+            handle: 3,
+            data: &[0x12, 0x34],
+        };
+        let read = AttReq::Read { handle: 3 };
+
+        assert_eq!(server.process(conn1, &write, &mut rsp).unwrap(), Some(1));
+        assert_eq!(server.process(conn1, &read, &mut rsp).unwrap(), Some(3));
+        assert_eq!(&rsp[..3], &[0x0b, 0x12, 0x34]);
+        assert_eq!(server.process(conn2, &read, &mut rsp).unwrap(), Some(3));
+        assert_eq!(&rsp[..3], &[0x0b, 0x00, 0x00]);
+
+        // All values are in use
+        assert_eq!(server.process(conn2, &write, &mut rsp).unwrap(), Some(1));
+        assert_eq!(server.process(conn3, &write, &mut rsp).unwrap(), Some(5));
+        assert_eq!(rsp[4], AttErrorCode::InsufficientResources as u8);
+
+        // The value is cleared once the connection is closed
+        server.disconnected(conn1);
+        assert_eq!(server.process(conn3, &write, &mut rsp).unwrap(), Some(1));
+        assert_eq!(server.process(conn1, &read, &mut rsp).unwrap(), Some(3));
+        assert_eq!(&rsp[..3], &[0x0b, 0x00, 0x00]);
+    }
+}
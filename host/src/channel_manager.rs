@@ -3,31 +3,64 @@ use core::future::poll_fn;
 use core::task::{Context, Poll};
 
 use bt_hci::controller::{blocking, Controller};
-use bt_hci::param::ConnHandle;
+use bt_hci::param::{ConnHandle, LeConnRole};
 use bt_hci::FromHciBytes;
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_sync::channel::Channel;
 use embassy_sync::waitqueue::WakerRegistration;
+use embassy_time::{with_timeout, Duration};
+use heapless::{Deque, Vec};
 
+use crate::connection::ConnectParams;
 use crate::cursor::WriteCursor;
 use crate::host::BleHost;
 use crate::l2cap::L2capChannel;
 use crate::packet_pool::{AllocId, GlobalPacketPool, Packet};
 use crate::pdu::Pdu;
 use crate::types::l2cap::{
-    CommandRejectRes, DisconnectionReq, DisconnectionRes, L2capHeader, L2capSignalCode, L2capSignalHeader,
-    LeCreditConnReq, LeCreditConnRes, LeCreditConnResultCode, LeCreditFlowInd,
+    CommandRejectRes, ConnParamUpdateReq, ConnParamUpdateRes, ConnParamUpdateResultCode, CreditConnReconfigReq,
+    CreditConnReconfigRes, CreditConnReconfigResultCode, CreditConnReq, CreditConnRes, DisconnectionReq,
+    DisconnectionRes, L2capHeader, L2capSignalCode, L2capSignalHeader, LeCreditConnReq, LeCreditConnRes,
+    LeCreditConnResultCode, LeCreditFlowInd, L2CAP_ENHANCED_MAX_CHANNELS, L2CAP_ENHANCED_MIN_MTU,
 };
 use crate::{AclSender, BleHostError, Error};
 
 const BASE_ID: u16 = 0x40;
 
+// How long to wait for the response to a signaling request.
+const SIGNAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Number of responses to signaling requests of the peer that can wait to be sent.
+const SIGNAL_RESPONSE_QUEUE_SIZE: usize = 4;
+
+// Number of connection parameter update requests of peripherals that can wait to be answered, one per connection.
+const CONN_PARAM_REQUEST_QUEUE_SIZE: usize = 4;
+
+// Number of our connection parameter update requests that can wait for a response, one per connection.
+const CONN_PARAM_RESPONSE_QUEUE_SIZE: usize = 4;
+
+// Number of PSMs that tasks can be accepting channels for at the same time, across connections.
+const MAX_LISTENING: usize = 8;
+
 struct State<'d> {
     next_req_id: u8,
     channels: &'d mut [ChannelStorage],
     accept_waker: WakerRegistration,
     create_waker: WakerRegistration,
     disconnect_waker: WakerRegistration,
+    reconfigure_waker: WakerRegistration,
+    // PSMs that a task is accepting channels for, with their connection
+    listening: Vec<(ConnHandle, u16), MAX_LISTENING>,
+    // Responses to signaling requests of the peer, waiting to be sent
+    signal_responses: Deque<SignalResponse, SIGNAL_RESPONSE_QUEUE_SIZE>,
+    signal_response_waker: WakerRegistration,
+    // Connection parameter update requests of peripherals, waiting to be answered
+    conn_param_requests: Deque<ConnParamUpdateRequest, CONN_PARAM_REQUEST_QUEUE_SIZE>,
+    conn_param_request_waker: WakerRegistration,
+    // Our connection parameter update requests waiting for a response, with their identifier and the result once
+    // received
+    conn_param_responses: Vec<(ConnHandle, u8, Option<ConnParamUpdateResultCode>), CONN_PARAM_RESPONSE_QUEUE_SIZE>,
+    conn_param_response_waker: WakerRegistration,
 }
 
 /// Channel manager for L2CAP channels used directly by clients.
@@ -91,6 +124,131 @@ impl<'d> State<'d> {
         let state = &mut self.channels[index.0 as usize];
         state.refcount = unwrap!(state.refcount.checked_add(1), "Too many references to the same channel");
     }
+
+    fn is_listening(&self, conn: ConnHandle, psm: u16) -> bool {
+        self.listening.contains(&(conn, psm))
+    }
+
+    // Queue a response to a signaling request of the peer, to be sent by the host.
+    fn respond(&mut self, response: SignalResponse) -> Result<(), Error> {
+        if self.signal_responses.push_back(response).is_err() {
+            warn!("[l2cap] no space to queue signal response");
+            return Err(Error::OutOfMemory);
+        }
+        self.signal_response_waker.wake();
+        Ok(())
+    }
+
+    // Claim up to `N` channels of the first pending connection request for one of the PSMs on the connection,
+    // refusing the others opened by the same request.
+    fn claim<const N: usize>(
+        &mut self,
+        conn: ConnHandle,
+        psm: &[u16],
+        mtu: u16,
+        credit_flow: CreditFlowPolicy,
+        initial_credits: Option<u16>,
+        pool: &dyn GlobalPacketPool,
+    ) -> Option<Claimed<N>> {
+        let (req_id, enhanced) = self.channels.iter().find_map(|chan| match chan.state {
+            ChannelState::PeerConnecting(req_id) if chan.conn == Some(conn) && psm.contains(&chan.psm) => {
+                Some((req_id, chan.enhanced))
+            }
+            _ => None,
+        })?;
+
+        let mut claimed = Claimed {
+            indices: Vec::new(),
+            dcids: Vec::new(),
+            req_id,
+            enhanced,
+            mps: 0,
+            credits: 0,
+        };
+        let mut credits = None;
+        // Channels are allocated in order, which matches the order of the CIDs in the request
+        for idx in 0..self.channels.len() {
+            let chan = &mut self.channels[idx];
+            if chan.state != ChannelState::PeerConnecting(req_id) || chan.conn != Some(conn) {
+                continue;
+            }
+            if claimed.indices.is_full() {
+                chan.close();
+                unwrap!(claimed.dcids.push(0));
+                continue;
+            }
+            let available = *credits.get_or_insert_with(|| {
+                initial_credits.unwrap_or(pool.min_available(AllocId::from_channel(chan.cid)) as u16)
+            });
+            chan.mps = chan.mps.min(pool.mtu() as u16 - 4);
+            chan.mtu = mtu;
+            chan.flow_control = CreditFlowControl::new(credit_flow, available);
+            chan.state = ChannelState::Connected;
+            claimed.mps = chan.mps;
+            unwrap!(claimed.dcids.push(chan.cid));
+            assert_eq!(chan.refcount, 0);
+            let index = ChannelIndex(idx as u8);
+
+            self.inc_ref(index);
+            unwrap!(claimed.indices.push(index));
+        }
+        claimed.credits = credits.unwrap_or(0);
+        Some(claimed)
+    }
+
+    // Wait for the response of the central to our connection parameter update request with the given identifier.
+    //
+    // A peripheral only has one request outstanding, a new one replaces the unanswered one.
+    fn expect_conn_param_response(&mut self, conn: ConnHandle, identifier: u8) -> Result<(), Error> {
+        self.conn_param_responses.retain(|(handle, ..)| *handle != conn);
+        self.conn_param_responses
+            .push((conn, identifier, None))
+            .map_err(|_| Error::OutOfMemory)
+    }
+
+    // Take the response of the central to our connection parameter update request with the given identifier.
+    fn take_conn_param_response(&mut self, conn: ConnHandle, identifier: u8) -> Option<ConnParamUpdateResultCode> {
+        let index = self
+            .conn_param_responses
+            .iter()
+            .position(|(handle, id, result)| *handle == conn && *id == identifier && result.is_some())?;
+        self.conn_param_responses.swap_remove(index).2
+    }
+
+    // Stop waiting for the response to our connection parameter update request with the given identifier.
+    fn cancel_conn_param_response(&mut self, conn: ConnHandle, identifier: u8) {
+        self.conn_param_responses
+            .retain(|(handle, id, _)| *handle != conn || *id != identifier);
+    }
+
+    // Check whether a reconfigure request of the peer can be applied to its channels.
+    fn check_reconfigure(
+        &self,
+        conn: ConnHandle,
+        req: &CreditConnReconfigReq,
+        dcids: &[u16],
+    ) -> CreditConnReconfigResultCode {
+        if req.mtu < L2CAP_ENHANCED_MIN_MTU || req.mps < L2CAP_ENHANCED_MIN_MTU {
+            return CreditConnReconfigResultCode::UnacceptableParameters;
+        }
+        for dcid in dcids {
+            let Some(chan) = self.channels.iter().find(|chan| {
+                chan.state == ChannelState::Connected
+                    && chan.enhanced
+                    && chan.conn == Some(conn)
+                    && chan.peer_cid == *dcid
+            }) else {
+                return CreditConnReconfigResultCode::InvalidDestinationCid;
+            };
+            if req.mtu < chan.peer_mtu {
+                return CreditConnReconfigResultCode::MtuReductionNotAllowed;
+            }
+            if dcids.len() > 1 && req.mps < chan.peer_mps {
+                return CreditConnReconfigResultCode::MpsReductionNotAllowed;
+            }
+        }
+        CreditConnReconfigResultCode::Success
+    }
 }
 
 impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
@@ -107,6 +265,14 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
                 accept_waker: WakerRegistration::new(),
                 create_waker: WakerRegistration::new(),
                 disconnect_waker: WakerRegistration::new(),
+                reconfigure_waker: WakerRegistration::new(),
+                listening: Vec::new(),
+                signal_responses: Deque::new(),
+                signal_response_waker: WakerRegistration::new(),
+                conn_param_requests: Deque::new(),
+                conn_param_request_waker: WakerRegistration::new(),
+                conn_param_responses: Vec::new(),
+                conn_param_response_waker: WakerRegistration::new(),
             }),
             inbound,
         }
@@ -137,6 +303,9 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
         }
         state.accept_waker.wake();
         state.create_waker.wake();
+        state.reconfigure_waker.wake();
+        state.conn_param_responses.retain(|(handle, ..)| *handle != conn);
+        state.conn_param_response_waker.wake();
         Ok(())
     }
 
@@ -149,6 +318,8 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
                 let cid: u16 = BASE_ID + idx as u16;
                 storage.conn = Some(conn);
                 storage.cid = cid;
+                storage.enhanced = false;
+                storage.reconfigure = Reconfigure::Idle;
                 f(storage);
                 return Ok(ChannelIndex(idx as u8));
             }
@@ -165,54 +336,86 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
         initial_credits: Option<u16>,
         ble: &BleHost<'_, T>,
     ) -> Result<L2capChannel<'_>, BleHostError<T::Error>> {
-        // Wait until we find a channel for our connection in the connecting state matching our PSM.
-        let (channel, req_id, mps, mtu, cid, credits) = poll_fn(|cx| {
+        let mut channels = self
+            .accept_many::<T, 1>(conn, psm, mtu, credit_flow, initial_credits, ble)
+            .await?;
+        Ok(channels.pop().ok_or(Error::NotFound)?)
+    }
+
+    /// Accept the channels opened by the next connection request matching the list of PSM.
+    ///
+    /// Channels requested in enhanced credit based flow control mode beyond the first `N` are refused.
+    pub(crate) async fn accept_many<T: Controller, const N: usize>(
+        &self,
+        conn: ConnHandle,
+        psm: &[u16],
+        mtu: u16,
+        credit_flow: CreditFlowPolicy,
+        initial_credits: Option<u16>,
+        ble: &BleHost<'_, T>,
+    ) -> Result<Vec<L2capChannel<'_>, N>, BleHostError<T::Error>> {
+        // Requests for PSMs that no task is accepting are refused, so register ours while waiting.
+        let _listener = Listener::new(&self.state, conn, psm)?;
+
+        // Wait until we find a channel for our connection in the connecting state matching our PSM, and accept it
+        // along with the other channels of the same request.
+        let Claimed {
+            indices,
+            dcids,
+            req_id,
+            enhanced,
+            mps,
+            credits,
+        } = poll_fn(|cx| {
             let mut state = self.state.borrow_mut();
-            for (idx, chan) in state.channels.iter_mut().enumerate() {
-                match chan.state {
-                    ChannelState::PeerConnecting(req_id) if chan.conn == Some(conn) && psm.contains(&chan.psm) => {
-                        chan.mps = chan.mps.min(self.pool.mtu() as u16 - 4);
-                        chan.mtu = chan.mtu.min(mtu);
-                        chan.mtu = mtu;
-                        chan.flow_control = CreditFlowControl::new(
-                            credit_flow,
-                            initial_credits.unwrap_or(self.pool.min_available(AllocId::from_channel(chan.cid)) as u16),
-                        );
-                        chan.state = ChannelState::Connected;
-                        let mps = chan.mps;
-                        let mtu = chan.mtu;
-                        let cid = chan.cid;
-                        let available = chan.flow_control.available();
-                        assert_eq!(chan.refcount, 0);
-                        let index = ChannelIndex(idx as u8);
-
-                        state.inc_ref(index);
-                        return Poll::Ready((L2capChannel::new(index, self), req_id, mps, mtu, cid, available));
-                    }
-                    _ => {}
+            match state.claim::<N>(conn, psm, mtu, credit_flow, initial_credits, self.pool) {
+                Some(claimed) => Poll::Ready(claimed),
+                None => {
+                    state.accept_waker.register(cx.waker());
+                    Poll::Pending
                 }
             }
-            state.accept_waker.register(cx.waker());
-            Poll::Pending
         })
         .await;
+        let channels: Vec<L2capChannel<'_>, N> = indices.iter().map(|idx| L2capChannel::new(*idx, self)).collect();
 
-        let mut tx = [0; 18];
-        // Respond that we accept the channel.
+        let mut tx = [0; 28];
+        // Respond that we accept the channels, advertising our own MTU.
         let mut hci = ble.acl(conn, 1).await?;
-        hci.signal(
-            req_id,
-            &LeCreditConnRes {
-                mps,
-                dcid: cid,
-                mtu,
-                credits,
-                result: LeCreditConnResultCode::Success,
-            },
-            &mut tx[..],
-        )
-        .await?;
-        Ok(channel)
+        if enhanced {
+            let result = if dcids.contains(&0) {
+                // Some channels were refused
+                LeCreditConnResultCode::NoResources
+            } else {
+                LeCreditConnResultCode::Success
+            };
+            hci.signal_with_cids(
+                req_id,
+                &CreditConnRes {
+                    mtu,
+                    mps,
+                    credits,
+                    result,
+                },
+                &dcids,
+                &mut tx[..],
+            )
+            .await?;
+        } else {
+            hci.signal(
+                req_id,
+                &LeCreditConnRes {
+                    mps,
+                    dcid: dcids[0],
+                    mtu,
+                    credits,
+                    result: LeCreditConnResultCode::Success,
+                },
+                &mut tx[..],
+            )
+            .await?;
+        }
+        Ok(channels)
     }
 
     pub(crate) async fn create<T: Controller>(
@@ -273,6 +476,234 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
         .await
     }
 
+    /// Open `N` channels, up to 5, with a single connection request in enhanced credit based flow control mode.
+    ///
+    /// The channels accepted by the peer are returned, which may be fewer than requested. If the peer refuses all
+    /// of them, the reason it gave is returned as [`Error::ChannelRefused`].
+    pub(crate) async fn create_many<T: Controller, const N: usize>(
+        &self,
+        conn: ConnHandle,
+        psm: u16,
+        mtu: u16,
+        credit_flow: CreditFlowPolicy,
+        initial_credits: Option<u16>,
+        ble: &BleHost<'_, T>,
+    ) -> Result<Vec<L2capChannel<'_>, N>, BleHostError<T::Error>> {
+        if N == 0 || N > L2CAP_ENHANCED_MAX_CHANNELS || mtu < L2CAP_ENHANCED_MIN_MTU {
+            return Err(Error::InvalidValue.into());
+        }
+        let mps = self.pool.mtu() as u16 - 4;
+        if mps < L2CAP_ENHANCED_MIN_MTU {
+            return Err(Error::NotSupported.into());
+        }
+
+        let req_id = self.next_request_id();
+        let mut credits = None;
+        let mut indices: Vec<ChannelIndex, N> = Vec::new();
+        let mut scids: Vec<u16, N> = Vec::new();
+
+        // Allocate space for our new channels, all sharing the same parameters.
+        for _ in 0..N {
+            let allocated = self.alloc(conn, |storage| {
+                let available = *credits.get_or_insert_with(|| {
+                    initial_credits.unwrap_or(self.pool.min_available(AllocId::from_channel(storage.cid)) as u16)
+                });
+                unwrap!(scids.push(storage.cid));
+                storage.psm = psm;
+                storage.mps = mps;
+                storage.mtu = mtu;
+                storage.enhanced = true;
+                storage.flow_control = CreditFlowControl::new(credit_flow, available);
+                storage.state = ChannelState::Connecting(req_id);
+            });
+            match allocated {
+                Ok(idx) => unwrap!(indices.push(idx)),
+                Err(e) => {
+                    self.with_mut(|state| {
+                        for idx in indices.iter() {
+                            state.channels[idx.0 as usize].close();
+                        }
+                    });
+                    return Err(e.into());
+                }
+            }
+        }
+
+        let request = async {
+            let mut tx = [0; 28];
+            // Send the connect request for all channels.
+            let command = CreditConnReq {
+                psm,
+                mtu,
+                mps,
+                credits: credits.unwrap_or(0),
+            };
+            let mut hci = ble.acl(conn, 1).await?;
+            hci.signal_with_cids(req_id, &command, &scids, &mut tx[..]).await?;
+
+            // Wait until the response is handled, connecting the accepted channels and closing the refused ones.
+            poll_fn(|cx| {
+                let mut state = self.state.borrow_mut();
+                state.create_waker.register(cx.waker());
+                let mut channels = Vec::new();
+                let mut refused = None;
+                for (idx, cid) in indices.iter().zip(scids.iter()) {
+                    let storage = &mut state.channels[idx.0 as usize];
+                    if storage.conn != Some(conn) || storage.cid != *cid {
+                        continue;
+                    }
+                    match storage.state {
+                        ChannelState::Connecting(id) if id == req_id => return Poll::Pending,
+                        ChannelState::Refused(result) => {
+                            refused = Some(result);
+                            storage.close();
+                        }
+                        ChannelState::Connected if storage.refcount == 0 => {
+                            state.inc_ref(*idx);
+                            unwrap!(channels.push(L2capChannel::new(*idx, self)).ok());
+                        }
+                        _ => {}
+                    }
+                }
+                if channels.is_empty() {
+                    let error = refused.map_or(Error::NotSupported, Error::ChannelRefused);
+                    return Poll::Ready(Err(error.into()));
+                }
+                Poll::Ready(Ok(channels))
+            })
+            .await
+        };
+        let result: Result<Vec<L2capChannel<'_>, N>, BleHostError<T::Error>> =
+            match with_timeout(SIGNAL_TIMEOUT, request).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::Timeout.into()),
+            };
+
+        // Release the channels of a request that failed, or that the peer never answered
+        if result.is_err() {
+            self.with_mut(|state| {
+                for (idx, cid) in indices.iter().zip(scids.iter()) {
+                    let storage = &mut state.channels[idx.0 as usize];
+                    if storage.conn == Some(conn) && storage.cid == *cid && storage.refcount == 0 {
+                        storage.close();
+                    }
+                }
+            });
+        }
+        result
+    }
+
+    /// Change the MTU of channels opened in enhanced credit based flow control mode on the same connection.
+    ///
+    /// The MTU may only be increased.
+    pub(crate) async fn reconfigure<T: Controller>(
+        &self,
+        indices: &[ChannelIndex],
+        mtu: u16,
+        ble: &BleHost<'_, T>,
+    ) -> Result<(), BleHostError<T::Error>> {
+        if indices.is_empty() || indices.len() > L2CAP_ENHANCED_MAX_CHANNELS {
+            return Err(Error::InvalidValue.into());
+        }
+        let req_id = self.next_request_id();
+        let (conn, cids) = self.with_mut(|state| {
+            let mut conn = None;
+            let mut cids: Vec<u16, L2CAP_ENHANCED_MAX_CHANNELS> = Vec::new();
+            for idx in indices {
+                let chan = &state.channels[idx.0 as usize];
+                if chan.state != ChannelState::Connected
+                    || !chan.enhanced
+                    || chan.reconfigure != Reconfigure::Idle
+                    || conn.is_some_and(|conn| chan.conn != Some(conn))
+                {
+                    return Err(Error::InvalidState);
+                }
+                if mtu < chan.mtu {
+                    return Err(Error::InvalidValue);
+                }
+                conn = chan.conn;
+                unwrap!(cids.push(chan.cid));
+            }
+            for idx in indices {
+                state.channels[idx.0 as usize].reconfigure = Reconfigure::Pending(req_id, mtu);
+            }
+            Ok((unwrap!(conn), cids))
+        })?;
+
+        let mut tx = [0; 28];
+        // Our MPS is bound by the packet pool, so it stays the same
+        let command = CreditConnReconfigReq {
+            mtu,
+            mps: self.pool.mtu() as u16 - 4,
+        };
+        let mut hci = ble.acl(conn, 1).await?;
+        hci.signal_with_cids(req_id, &command, &cids, &mut tx[..]).await?;
+
+        let result = poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            state.reconfigure_waker.register(cx.waker());
+            let chan = &state.channels[indices[0].0 as usize];
+            match chan.reconfigure {
+                Reconfigure::Done(result) => Poll::Ready(Ok(result)),
+                Reconfigure::Pending(..) if chan.state == ChannelState::Connected => Poll::Pending,
+                _ => Poll::Ready(Err(Error::Disconnected)),
+            }
+        })
+        .await;
+
+        self.with_mut(|state| {
+            for idx in indices {
+                state.channels[idx.0 as usize].reconfigure = Reconfigure::Idle;
+            }
+        });
+        match result? {
+            CreditConnReconfigResultCode::Success => Ok(()),
+            other => {
+                warn!("[l2cap] reconfigure request failed: {:?}", other);
+                Err(Error::NotSupported.into())
+            }
+        }
+    }
+
+    /// Request new connection parameters from the central, returning whether it accepted them.
+    pub(crate) async fn request_conn_param_update<T: Controller>(
+        &self,
+        conn: ConnHandle,
+        req: &ConnParamUpdateReq,
+        ble: &BleHost<'_, T>,
+    ) -> Result<bool, BleHostError<T::Error>> {
+        let req_id = self.with_mut(|state| {
+            let req_id = state.next_request_id();
+            state.expect_conn_param_response(conn, req_id)?;
+            Ok::<_, Error>(req_id)
+        })?;
+
+        let result: Result<_, BleHostError<T::Error>> = async {
+            {
+                let mut tx = [0; 18];
+                let mut hci = ble.acl(conn, 1).await?;
+                hci.signal(req_id, req, &mut tx[..]).await?;
+            }
+
+            let response = poll_fn(|cx| {
+                let mut state = self.state.borrow_mut();
+                state.conn_param_response_waker.register(cx.waker());
+                match state.take_conn_param_response(conn, req_id) {
+                    Some(result) => Poll::Ready(Ok(result)),
+                    None if !ble.connections.is_handle_connected(conn) => Poll::Ready(Err(Error::Disconnected)),
+                    None => Poll::Pending,
+                }
+            });
+            match with_timeout(SIGNAL_TIMEOUT, response).await {
+                Ok(result) => Ok(result?),
+                Err(_) => Err(Error::Timeout.into()),
+            }
+        }
+        .await;
+        self.with_mut(|state| state.cancel_conn_param_response(conn, req_id));
+        Ok(result? == ConnParamUpdateResultCode::Accepted)
+    }
+
     /// Dispatch an incoming L2CAP packet to the appropriate channel.
     pub(crate) fn dispatch(&self, header: L2capHeader, packet: Packet) -> Result<(), Error> {
         if header.channel < BASE_ID {
@@ -321,6 +752,30 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
                 let res = LeCreditConnRes::from_hci_bytes_complete(data)?;
                 self.handle_connect_response(conn, header.identifier, &res)
             }
+            L2capSignalCode::CreditConnReq => {
+                let (req, scids) = CreditConnReq::from_hci_bytes(data)?;
+                self.handle_credit_connect_request(conn, header.identifier, &req, &decode_cids(scids)?)
+            }
+            L2capSignalCode::CreditConnRes => {
+                let (res, dcids) = CreditConnRes::from_hci_bytes(data)?;
+                self.handle_credit_connect_response(conn, header.identifier, &res, &decode_cids(dcids)?)
+            }
+            L2capSignalCode::CreditConnReconfigReq => {
+                let (req, dcids) = CreditConnReconfigReq::from_hci_bytes(data)?;
+                self.handle_reconfigure_request(conn, header.identifier, &req, &decode_cids(dcids)?)
+            }
+            L2capSignalCode::CreditConnReconfigRes => {
+                let res = CreditConnReconfigRes::from_hci_bytes_complete(data)?;
+                self.handle_reconfigure_response(conn, header.identifier, &res)
+            }
+            L2capSignalCode::ConnParamUpdateReq => {
+                let req = ConnParamUpdateReq::from_hci_bytes_complete(data)?;
+                self.handle_conn_param_update_request(conn, header.identifier, &req)
+            }
+            L2capSignalCode::ConnParamUpdateRes => {
+                let res = ConnParamUpdateRes::from_hci_bytes_complete(data)?;
+                self.handle_conn_param_update_response(conn, header.identifier, &res)
+            }
             L2capSignalCode::LeCreditFlowInd => {
                 let req = LeCreditFlowInd::from_hci_bytes_complete(data)?;
                 //trace!("[l2cap] credit flow: {:?}", req);
@@ -347,17 +802,102 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
     }
 
     fn handle_connect_request(&self, conn: ConnHandle, identifier: u8, req: &LeCreditConnReq) -> Result<(), Error> {
-        self.alloc(conn, |storage| {
-            storage.conn = Some(conn);
-            storage.psm = req.psm;
-            storage.peer_cid = req.scid;
-            storage.peer_credits = req.credits;
-            storage.mps = req.mps;
-            storage.mtu = req.mtu;
-            storage.state = ChannelState::PeerConnecting(identifier);
-        })?;
-        self.state.borrow_mut().accept_waker.wake();
-        Ok(())
+        let listening = self.state.borrow().is_listening(conn, req.psm);
+        let refused = if !listening {
+            Some(LeCreditConnResultCode::SpsmNotSupported)
+        } else {
+            self.alloc(conn, |storage| {
+                storage.conn = Some(conn);
+                storage.psm = req.psm;
+                storage.peer_cid = req.scid;
+                storage.peer_credits = req.credits;
+                storage.mps = req.mps;
+                storage.peer_mps = req.mps;
+                storage.peer_mtu = req.mtu;
+                storage.state = ChannelState::PeerConnecting(identifier);
+            })
+            .err()
+            .map(|_| LeCreditConnResultCode::NoResources)
+        };
+
+        let mut state = self.state.borrow_mut();
+        match refused {
+            Some(result) => {
+                warn!("[l2cap] refusing connection request for psm {}: {:?}", req.psm, result);
+                state.respond(SignalResponse {
+                    handle: conn,
+                    identifier,
+                    kind: SignalResponseKind::LeCreditConnRefused(result),
+                })
+            }
+            None => {
+                state.accept_waker.wake();
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_credit_connect_request(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        req: &CreditConnReq,
+        scids: &[u16],
+    ) -> Result<(), Error> {
+        let listening = self.state.borrow().is_listening(conn, req.psm);
+        let refused = if !listening {
+            Some(LeCreditConnResultCode::SpsmNotSupported)
+        } else if req.mtu < L2CAP_ENHANCED_MIN_MTU || req.mps < L2CAP_ENHANCED_MIN_MTU {
+            Some(LeCreditConnResultCode::UnacceptableParameters)
+        } else {
+            // All channels of the request are allocated, or none
+            let mut allocated: Vec<ChannelIndex, L2CAP_ENHANCED_MAX_CHANNELS> = Vec::new();
+            let mut refused = None;
+            for scid in scids {
+                let res = self.alloc(conn, |storage| {
+                    storage.psm = req.psm;
+                    storage.peer_cid = *scid;
+                    storage.peer_credits = req.credits;
+                    storage.mps = req.mps;
+                    storage.peer_mps = req.mps;
+                    storage.peer_mtu = req.mtu;
+                    storage.enhanced = true;
+                    storage.state = ChannelState::PeerConnecting(identifier);
+                });
+                match res {
+                    Ok(idx) => unwrap!(allocated.push(idx)),
+                    Err(_) => {
+                        self.with_mut(|state| {
+                            for idx in allocated.iter() {
+                                state.channels[idx.0 as usize].close();
+                            }
+                        });
+                        refused = Some(LeCreditConnResultCode::NoResources);
+                        break;
+                    }
+                }
+            }
+            refused
+        };
+
+        let mut state = self.state.borrow_mut();
+        match refused {
+            Some(result) => {
+                warn!(
+                    "[l2cap] refusing credit based connection request for psm {}: {:?}",
+                    req.psm, result
+                );
+                state.respond(SignalResponse {
+                    handle: conn,
+                    identifier,
+                    kind: SignalResponseKind::CreditConnRefused(result, scids.len() as u8),
+                })
+            }
+            None => {
+                state.accept_waker.wake();
+                Ok(())
+            }
+        }
     }
 
     fn handle_connect_response(&self, conn: ConnHandle, identifier: u8, res: &LeCreditConnRes) -> Result<(), Error> {
@@ -371,7 +911,8 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
                             storage.peer_cid = res.dcid;
                             storage.peer_credits = res.credits;
                             storage.mps = storage.mps.min(res.mps);
-                            storage.mtu = storage.mtu.min(res.mtu);
+                            storage.peer_mps = res.mps;
+                            storage.peer_mtu = res.mtu;
                             storage.state = ChannelState::Connected;
                             state.create_waker.wake();
                             return Ok(());
@@ -393,6 +934,162 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
         }
     }
 
+    fn handle_credit_connect_response(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        res: &CreditConnRes,
+        dcids: &[u16],
+    ) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        let mut dcids = dcids.iter();
+        let mut found = false;
+        // Channels are allocated in order, which matches the order of the CIDs in the response
+        for storage in state.channels.iter_mut() {
+            match storage.state {
+                ChannelState::Connecting(req_id)
+                    if identifier == req_id && Some(conn) == storage.conn && storage.enhanced =>
+                {
+                    found = true;
+                    match dcids.next() {
+                        Some(dcid) if *dcid != 0 => {
+                            storage.peer_cid = *dcid;
+                            storage.peer_credits = res.credits;
+                            storage.mps = storage.mps.min(res.mps);
+                            storage.peer_mps = res.mps;
+                            storage.peer_mtu = res.mtu;
+                            storage.state = ChannelState::Connected;
+                        }
+                        _ => storage.state = ChannelState::Refused(res.result),
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !found {
+            trace!(
+                "[l2cap][handle_credit_connect_response][link = {}] request with id {} not found",
+                conn.raw(),
+                identifier
+            );
+            return Err(Error::NotFound);
+        }
+        if !matches!(res.result, LeCreditConnResultCode::Success) {
+            warn!("Channel open request failed: {:?}", res.result);
+        }
+        state.create_waker.wake();
+        Ok(())
+    }
+
+    fn handle_reconfigure_request(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        req: &CreditConnReconfigReq,
+        dcids: &[u16],
+    ) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        if state.signal_responses.is_full() {
+            warn!("[l2cap] no space to queue reconfigure response");
+            return Err(Error::OutOfMemory);
+        }
+        let result = state.check_reconfigure(conn, req, dcids);
+        if result == CreditConnReconfigResultCode::Success {
+            let mps = self.pool.mtu() as u16 - 4;
+            for storage in state.channels.iter_mut() {
+                if storage.state == ChannelState::Connected
+                    && storage.conn == Some(conn)
+                    && dcids.contains(&storage.peer_cid)
+                {
+                    storage.peer_mtu = req.mtu;
+                    storage.peer_mps = req.mps;
+                    storage.mps = mps.min(req.mps);
+                }
+            }
+        }
+        state.respond(SignalResponse {
+            handle: conn,
+            identifier,
+            kind: SignalResponseKind::Reconfigure(result),
+        })
+    }
+
+    fn handle_reconfigure_response(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        res: &CreditConnReconfigRes,
+    ) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        let mut found = false;
+        for storage in state.channels.iter_mut() {
+            match storage.reconfigure {
+                Reconfigure::Pending(req_id, mtu) if req_id == identifier && storage.conn == Some(conn) => {
+                    if res.result == CreditConnReconfigResultCode::Success {
+                        storage.mtu = mtu;
+                    }
+                    storage.reconfigure = Reconfigure::Done(res.result);
+                    found = true;
+                }
+                _ => {}
+            }
+        }
+        if !found {
+            return Err(Error::NotFound);
+        }
+        state.reconfigure_waker.wake();
+        Ok(())
+    }
+
+    fn handle_conn_param_update_request(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        req: &ConnParamUpdateReq,
+    ) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        let request = ConnParamUpdateRequest {
+            handle: conn,
+            identifier,
+            req: *req,
+        };
+        // A peripheral only has one request outstanding, a new one replaces the unanswered one
+        if let Some(pending) = state
+            .conn_param_requests
+            .iter_mut()
+            .find(|pending| pending.handle == conn)
+        {
+            warn!("[l2cap] replacing unanswered connection parameter update request");
+            *pending = request;
+        } else if state.conn_param_requests.push_back(request).is_err() {
+            warn!("[l2cap] no space to queue connection parameter update request");
+            return Err(Error::OutOfMemory);
+        }
+        state.conn_param_request_waker.wake();
+        Ok(())
+    }
+
+    fn handle_conn_param_update_response(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        res: &ConnParamUpdateRes,
+    ) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        match state
+            .conn_param_responses
+            .iter_mut()
+            .find(|(handle, id, result)| *handle == conn && *id == identifier && result.is_none())
+        {
+            Some((.., result)) => {
+                result.replace(res.result);
+                state.conn_param_response_waker.wake();
+            }
+            None => warn!("[l2cap] ignoring unexpected connection parameter update response"),
+        }
+        Ok(())
+    }
+
     fn handle_credit_flow(&self, conn: ConnHandle, req: &LeCreditFlowInd) -> Result<(), Error> {
         let mut state = self.state.borrow_mut();
         for storage in state.channels.iter_mut() {
@@ -505,7 +1202,8 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
         let n_packets = 1 + ((buf.len() as u16).saturating_sub(mps - 2)).div_ceil(mps);
 
         let mut grant = poll_fn(|cx| self.poll_request_to_send(index, n_packets, Some(cx))).await?;
-        let mut hci = ble.acl(conn, n_packets).await?;
+        // Each k-frame may need several ACL packets if the controller buffers are smaller than the mps
+        let mut hci = ble.acl(conn, n_packets * ble.acl_packets(mps as usize + 4)).await?;
 
         // Segment using mps
         let (first, remaining) = buf.split_at(buf.len().min(mps as usize - 2));
@@ -549,7 +1247,7 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
             }
         };
 
-        let mut hci = ble.try_acl(conn, n_packets)?;
+        let mut hci = ble.try_acl(conn, n_packets * ble.acl_packets(mps as usize + 4))?;
 
         // Segment using mps
         let (first, remaining) = buf.split_at(buf.len().min(mps as usize - 2));
@@ -668,6 +1366,24 @@ impl<'d, const RXQ: usize> ChannelManager<'d, RXQ> {
         Poll::Pending
     }
 
+    pub(crate) fn poll_signal_response(&self, cx: &mut Context<'_>) -> Poll<SignalResponse> {
+        let mut state = self.state.borrow_mut();
+        state.signal_response_waker.register(cx.waker());
+        match state.signal_responses.pop_front() {
+            Some(response) => Poll::Ready(response),
+            None => Poll::Pending,
+        }
+    }
+
+    pub(crate) fn poll_conn_param_update_request(&self, cx: &mut Context<'_>) -> Poll<ConnParamUpdateRequest> {
+        let mut state = self.state.borrow_mut();
+        state.conn_param_request_waker.register(cx.waker());
+        match state.conn_param_requests.pop_front() {
+            Some(request) => Poll::Ready(request),
+            None => Poll::Pending,
+        }
+    }
+
     pub(crate) fn inc_ref(&self, index: ChannelIndex) {
         self.with_mut(|state| {
             state.inc_ref(index);
@@ -735,6 +1451,167 @@ impl<'a, 'd> DisconnectRequest<'a, 'd> {
     }
 }
 
+// Channels claimed from a connection request of the peer.
+struct Claimed<const N: usize> {
+    indices: Vec<ChannelIndex, N>,
+    // CIDs of all channels of the request, 0 for those refused
+    dcids: Vec<u16, L2CAP_ENHANCED_MAX_CHANNELS>,
+    req_id: u8,
+    enhanced: bool,
+    mps: u16,
+    credits: u16,
+}
+
+// PSMs a task is accepting channels for on a connection, registered until dropped.
+struct Listener<'a, 'd> {
+    state: &'a RefCell<State<'d>>,
+    conn: ConnHandle,
+    psm: &'a [u16],
+}
+
+impl<'a, 'd> Listener<'a, 'd> {
+    fn new(state: &'a RefCell<State<'d>>, conn: ConnHandle, psm: &'a [u16]) -> Result<Self, Error> {
+        let mut s = state.borrow_mut();
+        if s.listening.capacity() - s.listening.len() < psm.len() {
+            return Err(Error::OutOfMemory);
+        }
+        for psm in psm {
+            unwrap!(s.listening.push((conn, *psm)));
+        }
+        Ok(Self { state, conn, psm })
+    }
+}
+
+impl Drop for Listener<'_, '_> {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        for psm in self.psm {
+            if let Some(pos) = state.listening.iter().position(|l| *l == (self.conn, *psm)) {
+                state.listening.swap_remove(pos);
+            }
+        }
+    }
+}
+
+/// Response to a signaling request of the peer.
+pub struct SignalResponse {
+    handle: ConnHandle,
+    identifier: u8,
+    kind: SignalResponseKind,
+}
+
+enum SignalResponseKind {
+    Reconfigure(CreditConnReconfigResultCode),
+    LeCreditConnRefused(LeCreditConnResultCode),
+    // Refusal of all the channels of the request, with their count
+    CreditConnRefused(LeCreditConnResultCode, u8),
+}
+
+impl SignalResponse {
+    pub fn handle(&self) -> ConnHandle {
+        self.handle
+    }
+
+    pub async fn send<T: Controller>(&self, hci: &mut AclSender<'_, '_, T>) -> Result<(), BleHostError<T::Error>> {
+        let mut tx = [0; 28];
+        match self.kind {
+            SignalResponseKind::Reconfigure(result) => {
+                hci.signal(self.identifier, &CreditConnReconfigRes { result }, &mut tx[..])
+                    .await
+            }
+            SignalResponseKind::LeCreditConnRefused(result) => {
+                let res = LeCreditConnRes {
+                    mps: 0,
+                    dcid: 0,
+                    mtu: 0,
+                    credits: 0,
+                    result,
+                };
+                hci.signal(self.identifier, &res, &mut tx[..]).await
+            }
+            SignalResponseKind::CreditConnRefused(result, count) => {
+                let res = CreditConnRes {
+                    mtu: 0,
+                    mps: 0,
+                    credits: 0,
+                    result,
+                };
+                let dcids = [0; L2CAP_ENHANCED_MAX_CHANNELS];
+                hci.signal_with_cids(self.identifier, &res, &dcids[..count as usize], &mut tx[..])
+                    .await
+            }
+        }
+    }
+}
+
+/// Connection parameter update request of a peripheral.
+pub struct ConnParamUpdateRequest {
+    handle: ConnHandle,
+    identifier: u8,
+    req: ConnParamUpdateReq,
+}
+
+impl ConnParamUpdateRequest {
+    pub fn handle(&self) -> ConnHandle {
+        self.handle
+    }
+
+    /// The requested parameters.
+    pub fn params(&self) -> ConnectParams {
+        ConnectParams::from_l2cap(&self.req)
+    }
+
+    /// Whether the requested parameters are within the ranges allowed by the specification.
+    pub fn is_valid(&self) -> bool {
+        self.req.in_range()
+    }
+
+    /// Decide the answer to the request for the role of the connection, returning whether the parameters are
+    /// accepted, or `None` if the request must be rejected as only the central may answer it.
+    pub(crate) fn decide(&self, role: LeConnRole, policy: fn(&ConnectParams) -> bool) -> Option<bool> {
+        if !matches!(role, LeConnRole::Central) {
+            return None;
+        }
+        Some(self.is_valid() && policy(&self.params()))
+    }
+
+    /// Tell the peripheral whether the parameters are accepted.
+    pub async fn respond<T: Controller>(
+        &self,
+        hci: &mut AclSender<'_, '_, T>,
+        accepted: bool,
+    ) -> Result<(), BleHostError<T::Error>> {
+        let result = if accepted {
+            ConnParamUpdateResultCode::Accepted
+        } else {
+            ConnParamUpdateResultCode::Rejected
+        };
+        let mut tx = [0; 18];
+        hci.signal(self.identifier, &ConnParamUpdateRes { result }, &mut tx[..])
+            .await
+    }
+
+    /// Reject the request as not understood, as only the central may answer it.
+    pub async fn reject<T: Controller>(&self, hci: &mut AclSender<'_, '_, T>) -> Result<(), BleHostError<T::Error>> {
+        let mut tx = [0; 18];
+        hci.signal(self.identifier, &CommandRejectRes { reason: 0x0000 }, &mut tx[..])
+            .await
+    }
+}
+
+// Decode the list of channel identifiers following an enhanced credit based flow control signal.
+fn decode_cids(data: &[u8]) -> Result<Vec<u16, L2CAP_ENHANCED_MAX_CHANNELS>, Error> {
+    if data.is_empty() || data.len() % 2 != 0 {
+        return Err(Error::InvalidValue);
+    }
+    let mut cids = Vec::new();
+    for cid in data.chunks_exact(2) {
+        cids.push(u16::from_le_bytes([cid[0], cid[1]]))
+            .map_err(|_| Error::InvalidValue)?;
+    }
+    Ok(cids)
+}
+
 fn encode(data: &[u8], packet: &mut [u8], peer_cid: u16, header: Option<u16>) -> Result<usize, Error> {
     let mut w = WriteCursor::new(packet);
     if header.is_some() {
@@ -756,6 +1633,7 @@ pub(crate) trait DynamicChannelManager {
     fn inc_ref(&self, index: ChannelIndex);
     fn dec_ref(&self, index: ChannelIndex);
     fn disconnect(&self, index: ChannelIndex);
+    fn mtu(&self, index: ChannelIndex) -> u16;
     #[cfg(feature = "defmt")]
     fn print(&self, index: ChannelIndex, f: defmt::Formatter);
 }
@@ -770,6 +1648,12 @@ impl<'d, const RXQ: usize> DynamicChannelManager for ChannelManager<'d, RXQ> {
     fn disconnect(&self, index: ChannelIndex) {
         ChannelManager::disconnect(self, index)
     }
+    fn mtu(&self, index: ChannelIndex) -> u16 {
+        self.with_mut(|state| {
+            let chan = &state.channels[index.0 as usize];
+            chan.mtu.min(chan.peer_mtu)
+        })
+    }
     #[cfg(feature = "defmt")]
     fn print(&self, index: ChannelIndex, f: defmt::Formatter) {
         use defmt::Format;
@@ -790,8 +1674,14 @@ pub struct ChannelStorage {
     mtu: u16,
     flow_control: CreditFlowControl,
     refcount: u8,
+    // Opened in enhanced credit based flow control mode
+    enhanced: bool,
+    reconfigure: Reconfigure,
 
     peer_cid: u16,
+    peer_mtu: u16,
+    // MPS advertised by the peer, which `mps` is bound by
+    peer_mps: u16,
     peer_credits: u16,
     credit_waker: WakerRegistration,
 }
@@ -801,13 +1691,15 @@ impl defmt::Format for ChannelStorage {
     fn format(&self, f: defmt::Formatter<'_>) {
         defmt::write!(
             f,
-            "state = {}, c = {}, cid = {}, peer = {}, mps = {}, mtu = {}, cred out {}, cred in = {}, ref = {}",
+            "state = {}, c = {}, cid = {}, peer = {}, mps = {}, mtu = {}, peer mtu = {}, peer mps = {}, cred out {}, cred in = {}, ref = {}",
             self.state,
             self.conn,
             self.cid,
             self.peer_cid,
             self.mps,
             self.mtu,
+            self.peer_mtu,
+            self.peer_mps,
             self.peer_credits,
             self.flow_control.available(),
             self.refcount,
@@ -825,7 +1717,11 @@ impl ChannelStorage {
         psm: 0,
 
         flow_control: CreditFlowControl::new(CreditFlowPolicy::Every(1), 0),
+        enhanced: false,
+        reconfigure: Reconfigure::Idle,
         peer_cid: 0,
+        peer_mtu: 0,
+        peer_mps: 0,
         peer_credits: 0,
         credit_waker: WakerRegistration::new(),
         refcount: 0,
@@ -838,18 +1734,33 @@ impl ChannelStorage {
         self.mps = 0;
         self.mtu = 0;
         self.psm = 0;
+        self.enhanced = false;
+        self.reconfigure = Reconfigure::Idle;
         self.peer_cid = 0;
+        self.peer_mtu = 0;
+        self.peer_mps = 0;
         self.flow_control = CreditFlowControl::new(CreditFlowPolicy::Every(1), 0);
         self.peer_credits = 0;
     }
 }
 
+// Progress of a reconfigure request sent for a channel.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Reconfigure {
+    Idle,
+    // Identifier of the request and the requested MTU
+    Pending(u8, u16),
+    Done(CreditConnReconfigResultCode),
+}
+
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ChannelState {
     Disconnected,
     Connecting(u8),
     PeerConnecting(u8),
+    // Refused by the peer, until the task opening it learns why
+    Refused(LeCreditConnResultCode),
     Connected,
     PeerDisconnecting,
     Disconnecting,
@@ -961,3 +1872,364 @@ impl<'reference, 'state> Drop for CreditGrant<'reference, 'state> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use static_cell::StaticCell;
+
+    use super::*;
+    use crate::packet_pool::{PacketPool, Qos};
+
+    const PSM: u16 = 0x0081;
+
+    type Pool = PacketPool<NoopRawMutex, 128, 8, 1>;
+
+    // Encode a signal from its 16-bit fields, followed by raw bytes such as a list of channel identifiers.
+    fn encode(code: L2capSignalCode, identifier: u8, fields: &[u16], extra: &[u8]) -> Vec<u8, 64> {
+        let mut data = Vec::new();
+        let len = (fields.len() * 2 + extra.len()) as u16;
+        unwrap!(data.extend_from_slice(&[code as u8, identifier]));
+        unwrap!(data.extend_from_slice(&len.to_le_bytes()));
+        for field in fields {
+            unwrap!(data.extend_from_slice(&field.to_le_bytes()));
+        }
+        unwrap!(data.extend_from_slice(extra));
+        data
+    }
+
+    fn cids(cids: &[u16]) -> Vec<u8, 16> {
+        let mut data = Vec::new();
+        for cid in cids {
+            unwrap!(data.extend_from_slice(&cid.to_le_bytes()));
+        }
+        data
+    }
+
+    fn credit_conn_req(identifier: u8, mtu: u16, mps: u16, scids: &[u16]) -> Vec<u8, 64> {
+        encode(
+            L2capSignalCode::CreditConnReq,
+            identifier,
+            &[PSM, mtu, mps, 10],
+            &cids(scids),
+        )
+    }
+
+    fn next_response<const RXQ: usize>(mgr: &ChannelManager<'_, RXQ>) -> Option<SignalResponse> {
+        mgr.state.borrow_mut().signal_responses.pop_front()
+    }
+
+    // Accept every channel of the pending credit based connection request, up to `N`.
+    fn claim<const N: usize, const RXQ: usize>(mgr: &ChannelManager<'_, RXQ>, conn: ConnHandle) -> Claimed<N> {
+        let mut state = mgr.state.borrow_mut();
+        unwrap!(state.claim::<N>(conn, &[PSM], 100, CreditFlowPolicy::Every(1), Some(10), mgr.pool))
+    }
+
+    #[test]
+    fn credit_connect_request_accepted_up_to_n() {
+        static POOL: StaticCell<Pool> = StaticCell::new();
+        let pool: &'static Pool = POOL.init(PacketPool::new(Qos::None));
+        let mut channels = [ChannelStorage::DISCONNECTED; 4];
+        let mut inbound = [PacketChannel::<1>::NEW; 4];
+        let mgr = ChannelManager::new(pool, &mut channels[..], &mut inbound[..]);
+        let conn = ConnHandle::new(0);
+
+        let _listener = unwrap!(Listener::new(&mgr.state, conn, &[PSM]));
+        unwrap!(mgr.signal(conn, &credit_conn_req(1, 100, 100, &[0x60, 0x61, 0x62])));
+        assert!(next_response(&mgr).is_none());
+
+        let claimed = claim::<2, 1>(&mgr, conn);
+        assert_eq!(claimed.req_id, 1);
+        assert!(claimed.enhanced);
+        assert_eq!(claimed.indices.len(), 2);
+        // The channel beyond N is refused with a null CID
+        assert_eq!(&claimed.dcids[..], &[0x40, 0x41, 0]);
+
+        let state = mgr.state.borrow();
+        assert_eq!(state.channels[0].state, ChannelState::Connected);
+        assert_eq!(state.channels[0].peer_cid, 0x60);
+        assert_eq!(state.channels[1].state, ChannelState::Connected);
+        assert_eq!(state.channels[1].peer_cid, 0x61);
+        assert_eq!(state.channels[2].state, ChannelState::Disconnected);
+    }
+
+    #[test]
+    fn credit_connect_request_refused() {
+        static POOL: StaticCell<Pool> = StaticCell::new();
+        let pool: &'static Pool = POOL.init(PacketPool::new(Qos::None));
+        let mut channels = [ChannelStorage::DISCONNECTED; 2];
+        let mut inbound = [PacketChannel::<1>::NEW; 2];
+        let mgr = ChannelManager::new(pool, &mut channels[..], &mut inbound[..]);
+        let conn = ConnHandle::new(0);
+
+        // Nobody is accepting the PSM
+        unwrap!(mgr.signal(conn, &credit_conn_req(1, 100, 100, &[0x60, 0x61])));
+        let response = unwrap!(next_response(&mgr));
+        assert_eq!(response.identifier, 1);
+        assert!(matches!(
+            response.kind,
+            SignalResponseKind::CreditConnRefused(LeCreditConnResultCode::SpsmNotSupported, 2)
+        ));
+
+        let listener = unwrap!(Listener::new(&mgr.state, conn, &[PSM]));
+        // MTU below the minimum of enhanced mode
+        unwrap!(mgr.signal(conn, &credit_conn_req(2, 23, 100, &[0x60])));
+        let response = unwrap!(next_response(&mgr));
+        assert!(matches!(
+            response.kind,
+            SignalResponseKind::CreditConnRefused(LeCreditConnResultCode::UnacceptableParameters, 1)
+        ));
+
+        // More channels than available, none of them is allocated
+        unwrap!(mgr.signal(conn, &credit_conn_req(3, 100, 100, &[0x60, 0x61, 0x62])));
+        let response = unwrap!(next_response(&mgr));
+        assert_eq!(response.identifier, 3);
+        assert!(matches!(
+            response.kind,
+            SignalResponseKind::CreditConnRefused(LeCreditConnResultCode::NoResources, 3)
+        ));
+        assert!(mgr
+            .state
+            .borrow()
+            .channels
+            .iter()
+            .all(|chan| chan.state == ChannelState::Disconnected));
+
+        // Once the listener is gone the PSM is refused again
+        drop(listener);
+        let req = encode(L2capSignalCode::LeCreditConnReq, 4, &[PSM, 0x60, 23, 23, 10], &[]);
+        unwrap!(mgr.signal(conn, &req));
+        let response = unwrap!(next_response(&mgr));
+        assert!(matches!(
+            response.kind,
+            SignalResponseKind::LeCreditConnRefused(LeCreditConnResultCode::SpsmNotSupported)
+        ));
+    }
+
+    #[test]
+    fn credit_connect_response_partially_refused() {
+        static POOL: StaticCell<Pool> = StaticCell::new();
+        let pool: &'static Pool = POOL.init(PacketPool::new(Qos::None));
+        let mut channels = [ChannelStorage::DISCONNECTED; 3];
+        let mut inbound = [PacketChannel::<1>::NEW; 3];
+        let mgr = ChannelManager::new(pool, &mut channels[..], &mut inbound[..]);
+        let conn = ConnHandle::new(0);
+
+        for _ in 0..2 {
+            unwrap!(mgr.alloc(conn, |storage| {
+                storage.psm = PSM;
+                storage.mps = 124;
+                storage.mtu = 100;
+                storage.enhanced = true;
+                storage.state = ChannelState::Connecting(7);
+            }));
+        }
+
+        let res = encode(
+            L2capSignalCode::CreditConnRes,
+            7,
+            &[200, 80, 5, LeCreditConnResultCode::NoResources as u16],
+            &cids(&[0x70, 0]),
+        );
+        unwrap!(mgr.signal(conn, &res));
+
+        let state = mgr.state.borrow();
+        assert_eq!(state.channels[0].state, ChannelState::Connected);
+        assert_eq!(state.channels[0].peer_cid, 0x70);
+        assert_eq!(state.channels[0].peer_mtu, 200);
+        assert_eq!(state.channels[0].peer_mps, 80);
+        assert_eq!(state.channels[0].mps, 80);
+        assert_eq!(
+            state.channels[1].state,
+            ChannelState::Refused(LeCreditConnResultCode::NoResources)
+        );
+    }
+
+    #[test]
+    fn reconfigure_request_checked() {
+        static POOL: StaticCell<Pool> = StaticCell::new();
+        let pool: &'static Pool = POOL.init(PacketPool::new(Qos::None));
+        let mut channels = [ChannelStorage::DISCONNECTED; 2];
+        let mut inbound = [PacketChannel::<1>::NEW; 2];
+        let mgr = ChannelManager::new(pool, &mut channels[..], &mut inbound[..]);
+        let conn = ConnHandle::new(0);
+
+        let _listener = unwrap!(Listener::new(&mgr.state, conn, &[PSM]));
+        unwrap!(mgr.signal(conn, &credit_conn_req(1, 100, 100, &[0x60, 0x61])));
+        claim::<2, 1>(&mgr, conn);
+        let result = |mgr: &ChannelManager<'_, 1>| match unwrap!(next_response(mgr)).kind {
+            SignalResponseKind::Reconfigure(result) => result,
+            _ => panic!("expected a reconfigure response"),
+        };
+
+        // Reducing the MTU is not allowed
+        let req = encode(L2capSignalCode::CreditConnReconfigReq, 2, &[80, 100], &cids(&[0x60]));
+        unwrap!(mgr.signal(conn, &req));
+        assert_eq!(result(&mgr), CreditConnReconfigResultCode::MtuReductionNotAllowed);
+        assert_eq!(mgr.state.borrow().channels[0].peer_mtu, 100);
+
+        // Reducing the MPS is not allowed for several channels, compared to what the peer advertised
+        let req = encode(
+            L2capSignalCode::CreditConnReconfigReq,
+            3,
+            &[120, 90],
+            &cids(&[0x60, 0x61]),
+        );
+        unwrap!(mgr.signal(conn, &req));
+        assert_eq!(result(&mgr), CreditConnReconfigResultCode::MpsReductionNotAllowed);
+
+        // Unknown channel
+        let req = encode(L2capSignalCode::CreditConnReconfigReq, 4, &[120, 100], &cids(&[0x62]));
+        unwrap!(mgr.signal(conn, &req));
+        assert_eq!(result(&mgr), CreditConnReconfigResultCode::InvalidDestinationCid);
+
+        let req = encode(
+            L2capSignalCode::CreditConnReconfigReq,
+            5,
+            &[120, 110],
+            &cids(&[0x60, 0x61]),
+        );
+        unwrap!(mgr.signal(conn, &req));
+        assert_eq!(result(&mgr), CreditConnReconfigResultCode::Success);
+        let state = mgr.state.borrow();
+        for chan in state.channels.iter() {
+            assert_eq!(chan.peer_mtu, 120);
+            assert_eq!(chan.peer_mps, 110);
+            // Still bound by our own MPS
+            assert_eq!(chan.mps, 110);
+        }
+    }
+
+    #[test]
+    fn malformed_cid_lists_rejected() {
+        static POOL: StaticCell<Pool> = StaticCell::new();
+        let pool: &'static Pool = POOL.init(PacketPool::new(Qos::None));
+        let mut channels = [ChannelStorage::DISCONNECTED; 8];
+        let mut inbound = [PacketChannel::<1>::NEW; 8];
+        let mgr = ChannelManager::new(pool, &mut channels[..], &mut inbound[..]);
+        let conn = ConnHandle::new(0);
+        let _listener = unwrap!(Listener::new(&mgr.state, conn, &[PSM]));
+
+        let malformed = [
+            // No channel
+            credit_conn_req(1, 100, 100, &[]),
+            // Odd length
+            encode(
+                L2capSignalCode::CreditConnReq,
+                2,
+                &[PSM, 100, 100, 10],
+                &[0x60, 0x00, 0x61],
+            ),
+            // More channels than allowed in a single request
+            credit_conn_req(3, 100, 100, &[0x60, 0x61, 0x62, 0x63, 0x64, 0x65]),
+        ];
+        for req in malformed.iter() {
+            assert!(matches!(mgr.signal(conn, req), Err(Error::InvalidValue)));
+        }
+        let req = encode(L2capSignalCode::CreditConnReconfigReq, 4, &[120, 100], &[0x60]);
+        assert!(matches!(mgr.signal(conn, &req), Err(Error::InvalidValue)));
+
+        assert!(next_response(&mgr).is_none());
+        assert!(mgr
+            .state
+            .borrow()
+            .channels
+            .iter()
+            .all(|chan| chan.state == ChannelState::Disconnected));
+    }
+
+    #[test]
+    fn conn_param_update_req_in_range() {
+        let req = |interval_min, interval_max, latency, timeout| ConnParamUpdateReq {
+            interval_min,
+            interval_max,
+            latency,
+            timeout,
+        };
+        assert!(req(6, 3200, 0, 3200).in_range());
+        assert!(req(24, 40, 4, 100).in_range());
+        // Intervals out of range or inverted
+        assert!(!req(5, 40, 0, 100).in_range());
+        assert!(!req(24, 3201, 0, 3200).in_range());
+        assert!(!req(40, 24, 0, 100).in_range());
+        // Latency above the maximum
+        assert!(!req(6, 6, 500, 3200).in_range());
+        // Timeout out of range
+        assert!(!req(6, 6, 0, 9).in_range());
+        assert!(!req(6, 6, 0, 3201).in_range());
+        // Timeout not longer than twice the effective interval
+        assert!(!req(80, 80, 4, 100).in_range());
+        assert!(req(80, 80, 4, 101).in_range());
+    }
+
+    #[test]
+    fn conn_param_update_requests_decided() {
+        static POOL: StaticCell<Pool> = StaticCell::new();
+        let pool: &'static Pool = POOL.init(PacketPool::new(Qos::None));
+        let mut channels = [ChannelStorage::DISCONNECTED; 1];
+        let mut inbound = [PacketChannel::<1>::NEW; 1];
+        let mgr = ChannelManager::new(pool, &mut channels[..], &mut inbound[..]);
+        let (conn1, conn2) = (ConnHandle::new(1), ConnHandle::new(2));
+
+        let valid = [24, 40, 4, 100];
+        let invalid = [24, 40, 4, 10];
+        unwrap!(mgr.signal(conn1, &encode(L2capSignalCode::ConnParamUpdateReq, 1, &invalid, &[])));
+        unwrap!(mgr.signal(conn2, &encode(L2capSignalCode::ConnParamUpdateReq, 2, &valid, &[])));
+        // Replaces the unanswered request of the same connection
+        unwrap!(mgr.signal(conn1, &encode(L2capSignalCode::ConnParamUpdateReq, 3, &valid, &[])));
+
+        let first = unwrap!(mgr.state.borrow_mut().conn_param_requests.pop_front());
+        let second = unwrap!(mgr.state.borrow_mut().conn_param_requests.pop_front());
+        assert!(mgr.state.borrow().conn_param_requests.is_empty());
+        assert_eq!((first.handle, first.identifier), (conn1, 3));
+        assert_eq!((second.handle, second.identifier), (conn2, 2));
+
+        // The central answers according to its policy
+        assert_eq!(first.decide(LeConnRole::Central, |_| true), Some(true));
+        assert_eq!(first.decide(LeConnRole::Central, |_| false), Some(false));
+        // A peripheral rejects the request as not understood
+        assert_eq!(first.decide(LeConnRole::Peripheral, |_| true), None);
+
+        // Parameters out of range are rejected whatever the policy
+        unwrap!(mgr.signal(conn1, &encode(L2capSignalCode::ConnParamUpdateReq, 4, &invalid, &[])));
+        let request = unwrap!(mgr.state.borrow_mut().conn_param_requests.pop_front());
+        assert_eq!(request.decide(LeConnRole::Central, |_| true), Some(false));
+    }
+
+    #[test]
+    fn conn_param_update_response_matched_by_identifier() {
+        static POOL: StaticCell<Pool> = StaticCell::new();
+        let pool: &'static Pool = POOL.init(PacketPool::new(Qos::None));
+        let mut channels = [ChannelStorage::DISCONNECTED; 1];
+        let mut inbound = [PacketChannel::<1>::NEW; 1];
+        let mgr = ChannelManager::new(pool, &mut channels[..], &mut inbound[..]);
+        let (conn1, conn2) = (ConnHandle::new(1), ConnHandle::new(2));
+        let res = |identifier, result: ConnParamUpdateResultCode| {
+            encode(L2capSignalCode::ConnParamUpdateRes, identifier, &[result as u16], &[])
+        };
+
+        // Responses nobody is waiting for are ignored
+        unwrap!(mgr.signal(conn1, &res(3, ConnParamUpdateResultCode::Accepted)));
+        unwrap!(mgr.state.borrow_mut().expect_conn_param_response(conn1, 3));
+        assert_eq!(mgr.state.borrow_mut().take_conn_param_response(conn1, 3), None);
+
+        // Each connection has its own request, matched by identifier
+        unwrap!(mgr.state.borrow_mut().expect_conn_param_response(conn2, 4));
+        unwrap!(mgr.signal(conn1, &res(4, ConnParamUpdateResultCode::Accepted)));
+        unwrap!(mgr.signal(conn2, &res(4, ConnParamUpdateResultCode::Rejected)));
+        unwrap!(mgr.signal(conn1, &res(3, ConnParamUpdateResultCode::Rejected)));
+
+        let mut state = mgr.state.borrow_mut();
+        assert_eq!(state.take_conn_param_response(conn1, 4), None);
+        assert_eq!(
+            state.take_conn_param_response(conn2, 4),
+            Some(ConnParamUpdateResultCode::Rejected)
+        );
+        assert_eq!(
+            state.take_conn_param_response(conn1, 3),
+            Some(ConnParamUpdateResultCode::Rejected)
+        );
+        assert_eq!(state.take_conn_param_response(conn1, 3), None);
+        assert!(state.conn_param_responses.is_empty());
+    }
+}
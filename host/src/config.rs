@@ -53,5 +53,24 @@ pub const L2CAP_RX_QUEUE_SIZE: usize = raw::L2CAP_RX_QUEUE_SIZE;
 /// Ensuring fair access to the pool is done configuring the QoS policy when creating
 /// the host resources.
 ///
+/// GATT requests and responses are allocated from the pool as well, so a GATT server or
+/// client needs at least 2 packets: one for the inbound PDU and one for the outbound.
+///
 /// Default: 1.
 pub const L2CAP_RX_PACKET_POOL_SIZE: usize = raw::L2CAP_RX_PACKET_POOL_SIZE;
+
+// ======== GATT parameters
+
+/// GATT client notification queue size
+///
+/// This is the number of notifications and indications kept for the GATT clients of every
+/// connection until one of them receives it. Every queued notification holds a packet from
+/// the rx packet pool.
+///
+/// When the queue of a connection is full, further notifications are dropped, so that the
+/// host keeps receiving for other connections and for the responses of the clients. An
+/// indication is kept instead of the oldest notification, as the peer sends no other until
+/// it is confirmed. Clients that subscribe should therefore keep receiving notifications.
+///
+/// Default: 1.
+pub const GATT_CLIENT_NOTIFICATION_QUEUE_SIZE: usize = raw::GATT_CLIENT_NOTIFICATION_QUEUE_SIZE;
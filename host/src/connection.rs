@@ -1,14 +1,15 @@
 //! BLE connection.
 use bt_hci::cmd::le::LeConnUpdate;
 use bt_hci::cmd::status::ReadRssi;
-use bt_hci::controller::{ControllerCmdAsync, ControllerCmdSync};
+use bt_hci::controller::{Controller, ControllerCmdAsync, ControllerCmdSync};
 use bt_hci::param::{BdAddr, ConnHandle, DisconnectReason, LeConnRole};
 use embassy_time::Duration;
 
 use crate::connection_manager::DynamicConnectionManager;
 use crate::host::BleHost;
 use crate::scan::ScanConfig;
-use crate::BleHostError;
+use crate::types::l2cap::ConnParamUpdateReq;
+use crate::{Address, BleHostError, Error};
 
 pub struct ConnectConfig<'d> {
     pub scan_config: ScanConfig<'d>,
@@ -35,6 +36,43 @@ impl Default for ConnectParams {
     }
 }
 
+impl ConnectParams {
+    // Parameters requested with the L2CAP connection parameter update procedure.
+    pub(crate) fn from_l2cap(req: &ConnParamUpdateReq) -> Self {
+        Self {
+            min_connection_interval: Duration::from_micros(req.interval_min as u64 * 1250),
+            max_connection_interval: Duration::from_micros(req.interval_max as u64 * 1250),
+            max_latency: req.latency,
+            event_length: Duration::from_secs(0),
+            supervision_timeout: Duration::from_millis(req.timeout as u64 * 10),
+        }
+    }
+
+    // Parameters to request with the L2CAP connection parameter update procedure. Values out of range are
+    // saturated, to be caught by validation.
+    pub(crate) fn to_l2cap(&self) -> ConnParamUpdateReq {
+        let units = |value: u64| u16::try_from(value).unwrap_or(u16::MAX);
+        ConnParamUpdateReq {
+            interval_min: units(self.min_connection_interval.as_micros() / 1250),
+            interval_max: units(self.max_connection_interval.as_micros() / 1250),
+            latency: self.max_latency,
+            timeout: units(self.supervision_timeout.as_millis() / 10),
+        }
+    }
+
+    pub(crate) fn conn_update(&self, handle: ConnHandle) -> LeConnUpdate {
+        LeConnUpdate::new(
+            handle,
+            self.min_connection_interval.into(),
+            self.max_connection_interval.into(),
+            self.max_latency,
+            self.supervision_timeout.into(),
+            self.event_length.into(),
+            self.event_length.into(),
+        )
+    }
+}
+
 pub struct Connection<'d> {
     index: u8,
     manager: &'d dyn DynamicConnectionManager,
@@ -62,6 +100,11 @@ impl<'d> Connection<'d> {
         self.manager.set_att_mtu(self.index, mtu);
     }
 
+    /// The ATT MTU currently in use for this connection.
+    pub fn att_mtu(&self) -> u16 {
+        self.manager.get_att_mtu(self.handle())
+    }
+
     /// Check if still connected
     pub fn is_connected(&self) -> bool {
         self.manager.is_connected(self.index)
@@ -82,6 +125,14 @@ impl<'d> Connection<'d> {
         self.manager.peer_address(self.index)
     }
 
+    /// The identity address of the peer, if known.
+    ///
+    /// This is the public or static random address of the peer. It is unknown if the peer connected with a
+    /// private address that the controller did not resolve.
+    pub fn peer_identity(&self) -> Option<Address> {
+        self.manager.peer_identity(self.index)
+    }
+
     pub fn disconnect(&self) {
         self.manager
             .disconnect(self.index, DisconnectReason::RemoteUserTerminatedConn);
@@ -108,18 +159,7 @@ impl<'d> Connection<'d> {
     {
         let handle = self.handle();
         trace!("[host] updating connection params for {:?}", handle);
-        match ble
-            .async_command(LeConnUpdate::new(
-                handle,
-                params.min_connection_interval.into(),
-                params.max_connection_interval.into(),
-                params.max_latency,
-                params.supervision_timeout.into(),
-                params.event_length.into(),
-                params.event_length.into(),
-            ))
-            .await
-        {
+        match ble.async_command(params.conn_update(handle)).await {
             Ok(_) => Ok(()),
             Err(BleHostError::BleHost(crate::Error::HciEncode(bt_hci::param::Error::UNKNOWN_CONN_IDENTIFIER))) => {
                 Err(crate::Error::Disconnected.into())
@@ -127,4 +167,24 @@ impl<'d> Connection<'d> {
             Err(e) => Err(e),
         }
     }
+
+    /// Request new connection parameters from the central, using the L2CAP connection parameter update procedure.
+    ///
+    /// Only a peripheral may send the request. Returns whether the central accepted the parameters, which it then
+    /// applies with a connection update.
+    pub async fn request_connection_params<T: Controller>(
+        &self,
+        ble: &BleHost<'_, T>,
+        params: &ConnectParams,
+    ) -> Result<bool, BleHostError<T::Error>> {
+        if !matches!(self.role(), LeConnRole::Peripheral) {
+            return Err(Error::InvalidState.into());
+        }
+        let req = params.to_l2cap();
+        if !req.in_range() {
+            return Err(Error::InvalidValue.into());
+        }
+        trace!("[host] requesting connection params for {:?}", self.handle());
+        ble.channels.request_conn_param_update(self.handle(), &req, ble).await
+    }
 }
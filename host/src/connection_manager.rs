@@ -6,16 +6,18 @@ use bt_hci::param::{AddrKind, BdAddr, ConnHandle, DisconnectReason, LeConnRole};
 use embassy_sync::waitqueue::WakerRegistration;
 
 use crate::connection::Connection;
-use crate::Error;
+use crate::pdu::Pdu;
+use crate::{att, Address, Error};
 
-struct State<'d> {
-    connections: &'d mut [ConnectionStorage],
+struct State<'d, const ATT_QLEN: usize> {
+    connections: &'d mut [ConnectionStorage<ATT_QLEN>],
     accept_waker: WakerRegistration,
     disconnect_waker: WakerRegistration,
     default_link_credits: usize,
+    default_att_mtu: u16,
 }
 
-impl<'d> State<'d> {
+impl<'d, const ATT_QLEN: usize> State<'d, ATT_QLEN> {
     fn print(&self, verbose: bool) {
         for (idx, storage) in self.connections.iter().enumerate() {
             if verbose || storage.state != ConnectionState::Disconnected {
@@ -33,18 +35,20 @@ impl<'d> State<'d> {
     }
 }
 
-pub(crate) struct ConnectionManager<'d> {
-    state: RefCell<State<'d>>,
+pub(crate) struct ConnectionManager<'d, const ATT_QLEN: usize> {
+    state: RefCell<State<'d, ATT_QLEN>>,
 }
 
-impl<'d> ConnectionManager<'d> {
-    pub(crate) fn new(connections: &'d mut [ConnectionStorage]) -> Self {
+impl<'d, const ATT_QLEN: usize> ConnectionManager<'d, ATT_QLEN> {
+    /// Create a connection manager, where `default_att_mtu` is the largest ATT MTU this host can handle.
+    pub(crate) fn new(connections: &'d mut [ConnectionStorage<ATT_QLEN>], default_att_mtu: u16) -> Self {
         Self {
             state: RefCell::new(State {
                 connections,
                 accept_waker: WakerRegistration::new(),
                 disconnect_waker: WakerRegistration::new(),
                 default_link_credits: 0,
+                default_att_mtu,
             }),
         }
     }
@@ -56,6 +60,14 @@ impl<'d> ConnectionManager<'d> {
         })
     }
 
+    /// Role of a connection identified by its handle.
+    pub(crate) fn role_of(&self, handle: ConnHandle) -> Result<LeConnRole, Error> {
+        self.with_connected(handle, |storage| storage.role)
+            .ok()
+            .flatten()
+            .ok_or(Error::NotFound)
+    }
+
     pub(crate) fn handle(&self, index: u8) -> ConnHandle {
         self.with_mut(|state| {
             let state = &mut state.connections[index as usize];
@@ -77,12 +89,24 @@ impl<'d> ConnectionManager<'d> {
         })
     }
 
+    pub(crate) fn peer_identity(&self, index: u8) -> Option<Address> {
+        self.with_mut(|state| {
+            let state = &mut state.connections[index as usize];
+            identity_address(state.peer_addr_kind.unwrap(), state.peer_addr.unwrap())
+        })
+    }
+
     pub(crate) fn set_att_mtu(&self, index: u8, mtu: u16) {
         self.with_mut(|state| {
             state.connections[index as usize].att_mtu = mtu;
         })
     }
 
+    /// The largest ATT MTU supported by this host.
+    pub(crate) fn default_att_mtu(&self) -> u16 {
+        self.state.borrow().default_att_mtu
+    }
+
     pub(crate) fn request_disconnect(&self, index: u8, reason: DisconnectReason) {
         self.with_mut(|state| {
             let entry = &mut state.connections[index as usize];
@@ -93,7 +117,10 @@ impl<'d> ConnectionManager<'d> {
         })
     }
 
-    pub(crate) fn poll_disconnecting<'m>(&'m self, cx: Option<&mut Context<'_>>) -> Poll<DisconnectRequest<'m, 'd>> {
+    pub(crate) fn poll_disconnecting<'m>(
+        &'m self,
+        cx: Option<&mut Context<'_>>,
+    ) -> Poll<DisconnectRequest<'m, 'd, ATT_QLEN>> {
         let mut state = self.state.borrow_mut();
         if let Some(cx) = cx {
             state.disconnect_waker.register(cx.waker());
@@ -144,6 +171,13 @@ impl<'d> ConnectionManager<'d> {
             if let Some(handle) = storage.handle {
                 if handle == h && storage.state != ConnectionState::Disconnected {
                     storage.state = ConnectionState::Disconnected;
+                    // Return the PDUs to the pool, and let waiting clients see the connection is gone
+                    storage.att_response.take();
+                    storage.att_notifications.clear();
+                    storage.att_busy = false;
+                    storage.att_response_waker.wake();
+                    storage.att_notification_waker.wake();
+                    storage.att_busy_waker.wake();
                     return Ok(());
                 }
             }
@@ -166,6 +200,7 @@ impl<'d> ConnectionManager<'d> {
                 storage.state = ConnectionState::Connecting;
                 storage.link_credits = default_credits;
                 storage.att_mtu = 23;
+                storage.att_timed_out = false;
                 storage.handle.replace(handle);
                 storage.peer_addr_kind.replace(peer_addr_kind);
                 storage.peer_addr.replace(peer_addr);
@@ -226,11 +261,116 @@ impl<'d> ConnectionManager<'d> {
         Poll::Pending
     }
 
-    fn with_mut<F: FnOnce(&mut State<'d>) -> R, R>(&self, f: F) -> R {
+    fn with_mut<F: FnOnce(&mut State<'d, ATT_QLEN>) -> R, R>(&self, f: F) -> R {
         let mut state = self.state.borrow_mut();
         f(&mut state)
     }
 
+    fn with_connected<F: FnOnce(&mut ConnectionStorage<ATT_QLEN>) -> R, R>(
+        &self,
+        handle: ConnHandle,
+        f: F,
+    ) -> Result<R, Error> {
+        let mut state = self.state.borrow_mut();
+        for storage in state.connections.iter_mut() {
+            if storage.state == ConnectionState::Connected && storage.handle == Some(handle) {
+                return Ok(f(storage));
+            }
+        }
+        Err(Error::Disconnected)
+    }
+
+    /// Keep a PDU sent by the server of the peer until a GATT client of the connection receives it.
+    ///
+    /// Responses and notifications are kept apart, so that waiting for one never blocks the other. If a response is
+    /// still waiting, the PDU is refused. Notifications are queued as with [`queue_notification`].
+    pub(crate) fn post_att(&self, handle: ConnHandle, pdu: Pdu) -> Result<(), Error> {
+        self.with_connected(handle, |storage| match pdu.as_ref().first() {
+            Some(&opcode) if att::is_notification(opcode) => {
+                if queue_notification(handle, &mut storage.att_notifications, pdu) {
+                    storage.att_notification_waker.wake();
+                }
+                Ok(())
+            }
+            _ => {
+                if storage.att_response.is_some() {
+                    return Err(Error::OutOfMemory);
+                }
+                storage.att_response.replace(pdu);
+                storage.att_response_waker.wake();
+                Ok(())
+            }
+        })?
+    }
+
+    /// Poll for the response to the request of the client whose turn it is.
+    pub(crate) fn poll_att_response(&self, handle: ConnHandle, cx: &mut Context<'_>) -> Poll<Result<Pdu, Error>> {
+        match self.with_connected(handle, |storage| match storage.att_response.take() {
+            Some(pdu) => Poll::Ready(pdu),
+            None => {
+                storage.att_response_waker.register(cx.waker());
+                Poll::Pending
+            }
+        }) {
+            Ok(poll) => poll.map(Ok),
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Poll for a notification or indication, which is received by one of the clients of the connection.
+    pub(crate) fn poll_att_notification(&self, handle: ConnHandle, cx: &mut Context<'_>) -> Poll<Result<Pdu, Error>> {
+        match self.with_connected(handle, |storage| match storage.att_notifications.pop_front() {
+            Some(pdu) => Poll::Ready(pdu),
+            None => {
+                storage.att_notification_waker.register(cx.waker());
+                Poll::Pending
+            }
+        }) {
+            Ok(poll) => poll.map(Ok),
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Poll for the turn to send a request on the fixed ATT channel, which allows one outstanding request at a time.
+    pub(crate) fn poll_att_turn(
+        &self,
+        handle: ConnHandle,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<AttTurn<'_, 'd, ATT_QLEN>, Error>> {
+        match self.with_connected(handle, |storage| {
+            if storage.att_busy {
+                storage.att_busy_waker.register(cx.waker());
+                return false;
+            }
+            storage.att_busy = true;
+            // A response arriving after its client gave up must not be taken for the next one
+            storage.att_response.take();
+            true
+        }) {
+            Ok(true) => Poll::Ready(Ok(AttTurn {
+                state: &self.state,
+                handle,
+            })),
+            Ok(false) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Wait for the turn to send a request on the fixed ATT channel, which ends when the returned turn is dropped.
+    pub(crate) async fn att_turn(&self, handle: ConnHandle) -> Result<AttTurn<'_, 'd, ATT_QLEN>, Error> {
+        poll_fn(|cx| self.poll_att_turn(handle, cx)).await
+    }
+
+    /// Whether a transaction on the fixed ATT channel timed out, after which no more PDUs may be sent on it.
+    pub(crate) fn att_timed_out(&self, handle: ConnHandle) -> bool {
+        self.with_connected(handle, |storage| storage.att_timed_out)
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn set_att_timed_out(&self, handle: ConnHandle) {
+        let _ = self.with_connected(handle, |storage| storage.att_timed_out = true);
+    }
+
     pub(crate) fn log_status(&self, verbose: bool) {
         let state = self.state.borrow();
         state.print(verbose);
@@ -287,7 +427,7 @@ impl<'d> ConnectionManager<'d> {
         handle: ConnHandle,
         packets: usize,
         cx: Option<&mut Context<'_>>,
-    ) -> Poll<Result<PacketGrant<'_, 'd>, Error>> {
+    ) -> Poll<Result<PacketGrant<'_, 'd, ATT_QLEN>, Error>> {
         let mut state = self.state.borrow_mut();
         for storage in state.connections.iter_mut() {
             match storage.state {
@@ -322,6 +462,7 @@ pub(crate) trait DynamicConnectionManager {
     fn is_connected(&self, index: u8) -> bool;
     fn handle(&self, index: u8) -> ConnHandle;
     fn peer_address(&self, index: u8) -> BdAddr;
+    fn peer_identity(&self, index: u8) -> Option<Address>;
     fn set_att_mtu(&self, index: u8, mtu: u16);
     fn inc_ref(&self, index: u8);
     fn dec_ref(&self, index: u8);
@@ -330,7 +471,7 @@ pub(crate) trait DynamicConnectionManager {
     fn exchange_att_mtu(&self, conn: ConnHandle, mtu: u16) -> u16;
 }
 
-impl<'d> DynamicConnectionManager for ConnectionManager<'d> {
+impl<'d, const ATT_QLEN: usize> DynamicConnectionManager for ConnectionManager<'d, ATT_QLEN> {
     fn role(&self, index: u8) -> LeConnRole {
         ConnectionManager::role(self, index)
     }
@@ -343,6 +484,9 @@ impl<'d> DynamicConnectionManager for ConnectionManager<'d> {
     fn peer_address(&self, index: u8) -> BdAddr {
         ConnectionManager::peer_address(self, index)
     }
+    fn peer_identity(&self, index: u8) -> Option<Address> {
+        ConnectionManager::peer_identity(self, index)
+    }
     fn inc_ref(&self, index: u8) {
         ConnectionManager::inc_ref(self, index)
     }
@@ -369,10 +513,12 @@ impl<'d> DynamicConnectionManager for ConnectionManager<'d> {
     }
     fn exchange_att_mtu(&self, conn: ConnHandle, mtu: u16) -> u16 {
         let mut state = self.state.borrow_mut();
+        let default_mtu = state.default_att_mtu;
         for storage in state.connections.iter_mut() {
             match storage.state {
                 ConnectionState::Connected if storage.handle.unwrap() == conn => {
-                    storage.att_mtu = storage.att_mtu.min(mtu);
+                    // The peer MTU is never below the ATT default of 23
+                    storage.att_mtu = default_mtu.min(mtu).max(23);
                     return storage.att_mtu;
                 }
                 _ => {}
@@ -382,14 +528,14 @@ impl<'d> DynamicConnectionManager for ConnectionManager<'d> {
     }
 }
 
-pub struct DisconnectRequest<'a, 'd> {
+pub struct DisconnectRequest<'a, 'd, const ATT_QLEN: usize> {
     index: usize,
     handle: ConnHandle,
     reason: DisconnectReason,
-    state: &'a RefCell<State<'d>>,
+    state: &'a RefCell<State<'d, ATT_QLEN>>,
 }
 
-impl<'a, 'd> DisconnectRequest<'a, 'd> {
+impl<'a, 'd, const ATT_QLEN: usize> DisconnectRequest<'a, 'd, ATT_QLEN> {
     pub fn handle(&self) -> ConnHandle {
         self.handle
     }
@@ -405,7 +551,7 @@ impl<'a, 'd> DisconnectRequest<'a, 'd> {
 }
 
 #[derive(Debug)]
-pub struct ConnectionStorage {
+pub struct ConnectionStorage<const ATT_QLEN: usize> {
     pub state: ConnectionState,
     pub handle: Option<ConnHandle>,
     pub role: Option<LeConnRole>,
@@ -415,10 +561,21 @@ pub struct ConnectionStorage {
     pub link_credits: usize,
     pub link_credit_waker: WakerRegistration,
     pub refcount: u8,
+    // Response from the server of the peer, for the client whose turn it is
+    pub(crate) att_response: Option<Pdu>,
+    pub(crate) att_response_waker: WakerRegistration,
+    // Notifications and indications from the server of the peer, for any client of the connection
+    pub(crate) att_notifications: heapless::Deque<Pdu, ATT_QLEN>,
+    pub(crate) att_notification_waker: WakerRegistration,
+    // Set while a client has a request outstanding on the fixed ATT channel
+    pub(crate) att_busy: bool,
+    pub(crate) att_busy_waker: WakerRegistration,
+    // Set when a transaction on the fixed ATT channel timed out
+    pub(crate) att_timed_out: bool,
 }
 
-impl ConnectionStorage {
-    pub(crate) const DISCONNECTED: ConnectionStorage = ConnectionStorage {
+impl<const ATT_QLEN: usize> ConnectionStorage<ATT_QLEN> {
+    pub(crate) const DISCONNECTED: ConnectionStorage<ATT_QLEN> = ConnectionStorage {
         state: ConnectionState::Disconnected,
         handle: None,
         role: None,
@@ -428,11 +585,18 @@ impl ConnectionStorage {
         link_credits: 0,
         link_credit_waker: WakerRegistration::new(),
         refcount: 0,
+        att_response: None,
+        att_response_waker: WakerRegistration::new(),
+        att_notifications: heapless::Deque::new(),
+        att_notification_waker: WakerRegistration::new(),
+        att_busy: false,
+        att_busy_waker: WakerRegistration::new(),
+        att_timed_out: false,
     };
 }
 
 #[cfg(feature = "defmt")]
-impl defmt::Format for ConnectionStorage {
+impl<const ATT_QLEN: usize> defmt::Format for ConnectionStorage<ATT_QLEN> {
     fn format(&self, f: defmt::Formatter<'_>) {
         defmt::write!(
             f,
@@ -457,14 +621,14 @@ pub enum ConnectionState {
     Connected,
 }
 
-pub struct PacketGrant<'a, 'd> {
-    state: &'a RefCell<State<'d>>,
+pub struct PacketGrant<'a, 'd, const ATT_QLEN: usize> {
+    state: &'a RefCell<State<'d, ATT_QLEN>>,
     handle: ConnHandle,
     packets: usize,
 }
 
-impl<'a, 'd> PacketGrant<'a, 'd> {
-    fn new(state: &'a RefCell<State<'d>>, handle: ConnHandle, packets: usize) -> Self {
+impl<'a, 'd, const ATT_QLEN: usize> PacketGrant<'a, 'd, ATT_QLEN> {
+    fn new(state: &'a RefCell<State<'d, ATT_QLEN>>, handle: ConnHandle, packets: usize) -> Self {
         Self { state, handle, packets }
     }
 
@@ -473,7 +637,7 @@ impl<'a, 'd> PacketGrant<'a, 'd> {
     }
 }
 
-impl<'a, 'd> Drop for PacketGrant<'a, 'd> {
+impl<'a, 'd, const ATT_QLEN: usize> Drop for PacketGrant<'a, 'd, ATT_QLEN> {
     fn drop(&mut self) {
         if self.packets > 0 {
             let mut state = self.state.borrow_mut();
@@ -493,17 +657,95 @@ impl<'a, 'd> Drop for PacketGrant<'a, 'd> {
     }
 }
 
+/// Turn of a GATT client to send a request on the fixed ATT channel of a connection, ended when dropped.
+pub(crate) struct AttTurn<'a, 'd, const ATT_QLEN: usize> {
+    state: &'a RefCell<State<'d, ATT_QLEN>>,
+    handle: ConnHandle,
+}
+
+impl<'a, 'd, const ATT_QLEN: usize> Drop for AttTurn<'a, 'd, ATT_QLEN> {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        for storage in state.connections.iter_mut() {
+            if storage.state == ConnectionState::Connected && storage.handle == Some(self.handle) {
+                storage.att_busy = false;
+                storage.att_busy_waker.wake();
+                break;
+            }
+        }
+    }
+}
+
+/// Queue a notification or indication of connection `handle`, returning whether it was queued.
+///
+/// Notifications are dropped when the queue is full rather than holding back reception. An indication is kept
+/// instead of the oldest notification then, as the server sends no other indication until it is confirmed.
+pub(crate) fn queue_notification<const N: usize>(
+    handle: ConnHandle,
+    queue: &mut heapless::Deque<Pdu, N>,
+    pdu: Pdu,
+) -> bool {
+    let opcode = pdu.as_ref().first().copied().unwrap_or_default();
+    if queue.is_full() && opcode == att::ATT_HANDLE_VALUE_IND {
+        let mut dropped = false;
+        for _ in 0..queue.len() {
+            let Some(queued) = queue.pop_front() else {
+                break;
+            };
+            if !dropped && queued.as_ref().first() != Some(&att::ATT_HANDLE_VALUE_IND) {
+                warn!(
+                    "[gatt] notification queue of connection {:?} full, dropping a notification",
+                    handle
+                );
+                dropped = true;
+            } else {
+                let _ = queue.push_back(queued);
+            }
+        }
+    }
+    match queue.push_back(pdu) {
+        Ok(()) => true,
+        Err(_) => {
+            warn!(
+                "[gatt] notification queue of connection {:?} full, dropping {:x}",
+                handle, opcode
+            );
+            false
+        }
+    }
+}
+
+// Identity address of a peer connected with an address of `kind`, if it is one or the controller resolved it.
+fn identity_address(kind: AddrKind, addr: BdAddr) -> Option<Address> {
+    let kind = if kind == AddrKind::PUBLIC || kind == AddrKind::RESOLVABLE_PRIVATE_OR_PUBLIC {
+        AddrKind::PUBLIC
+    } else if kind == AddrKind::RESOLVABLE_PRIVATE_OR_RANDOM {
+        AddrKind::RANDOM
+    } else if kind == AddrKind::RANDOM && addr.raw()[5] & 0xc0 == 0xc0 {
+        // Static random addresses have the two most significant bits set, private addresses don't
+        AddrKind::RANDOM
+    } else {
+        return None;
+    };
+    Some(Address { kind, addr })
+}
+
 #[cfg(test)]
 mod tests {
+    use embassy_futures::poll_once;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+    use static_cell::StaticCell;
+
     use super::*;
+    use crate::packet_pool::{PacketPool, Qos, ATT_ID};
 
     const ADDR_1: [u8; 6] = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66];
     const ADDR_2: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
 
     #[test]
     fn peripheral_connection_established() {
-        let mut storage = [ConnectionStorage::DISCONNECTED; 3];
-        let mgr = ConnectionManager::new(&mut storage[..]);
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
 
         assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
 
@@ -519,14 +761,16 @@ mod tests {
         };
         assert_eq!(handle.role(), LeConnRole::Peripheral);
         assert_eq!(handle.peer_address(), BdAddr::new(ADDR_1));
+        // A resolvable private address is not an identity address
+        assert_eq!(handle.peer_identity(), None);
 
         handle.disconnect();
     }
 
     #[test]
     fn central_connection_established() {
-        let mut storage = [ConnectionStorage::DISCONNECTED; 3];
-        let mgr = ConnectionManager::new(&mut storage[..]);
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
 
         assert!(mgr.poll_accept(LeConnRole::Central, &[], None).is_pending());
 
@@ -542,12 +786,43 @@ mod tests {
         };
         assert_eq!(handle.role(), LeConnRole::Central);
         assert_eq!(handle.peer_address(), BdAddr::new(ADDR_2));
+        assert_eq!(handle.peer_identity(), Some(Address::random(ADDR_2)));
+    }
+
+    #[test]
+    fn identity_addresses() {
+        let public = Address {
+            kind: AddrKind::PUBLIC,
+            addr: BdAddr::new(ADDR_1),
+        };
+        assert_eq!(identity_address(AddrKind::PUBLIC, BdAddr::new(ADDR_1)), Some(public));
+        assert_eq!(
+            identity_address(AddrKind::RANDOM, BdAddr::new(ADDR_2)),
+            Some(Address::random(ADDR_2))
+        );
+
+        // Private addresses resolved by the controller are reported with the kind of the identity address
+        assert_eq!(
+            identity_address(AddrKind::RESOLVABLE_PRIVATE_OR_PUBLIC, BdAddr::new(ADDR_1)),
+            Some(public)
+        );
+        assert_eq!(
+            identity_address(AddrKind::RESOLVABLE_PRIVATE_OR_RANDOM, BdAddr::new(ADDR_1)),
+            Some(Address::random(ADDR_1))
+        );
+
+        // Resolvable and non-resolvable private addresses are not
+        assert_eq!(identity_address(AddrKind::RANDOM, BdAddr::new(ADDR_1)), None);
+        assert_eq!(
+            identity_address(AddrKind::RANDOM, BdAddr::new([1, 2, 3, 4, 5, 0x3f])),
+            None
+        );
     }
 
     #[test]
     fn controller_disconnects_before_host() {
-        let mut storage = [ConnectionStorage::DISCONNECTED; 3];
-        let mgr = ConnectionManager::new(&mut storage[..]);
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
 
         unwrap!(mgr.connect(
             ConnHandle::new(3),
@@ -599,8 +874,8 @@ mod tests {
 
     #[test]
     fn controller_disconnects_after_host() {
-        let mut storage = [ConnectionStorage::DISCONNECTED; 3];
-        let mgr = ConnectionManager::new(&mut storage[..]);
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
 
         unwrap!(mgr.connect(
             ConnHandle::new(3),
@@ -650,8 +925,8 @@ mod tests {
 
     #[test]
     fn referenced_handle_not_reused() {
-        let mut storage = [ConnectionStorage::DISCONNECTED; 3];
-        let mgr = ConnectionManager::new(&mut storage[..]);
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
 
         assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
 
@@ -688,8 +963,8 @@ mod tests {
 
     #[test]
     fn disconnect_correct_handle() {
-        let mut storage = [ConnectionStorage::DISCONNECTED; 3];
-        let mgr = ConnectionManager::new(&mut storage[..]);
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
 
         assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
 
@@ -722,10 +997,33 @@ mod tests {
         assert!(!conn2.is_connected());
     }
 
+    #[test]
+    fn att_mtu_exchange() {
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 247);
+
+        let handle = ConnHandle::new(1);
+        unwrap!(mgr.connect(handle, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Peripheral));
+        let Poll::Ready(conn) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+        assert_eq!(conn.att_mtu(), 23);
+
+        // Peer supports more than us
+        assert_eq!(mgr.exchange_att_mtu(handle, 517), 247);
+        assert_eq!(conn.att_mtu(), 247);
+
+        // Peer supports less than us
+        assert_eq!(mgr.exchange_att_mtu(handle, 100), 100);
+
+        // Invalid values are clamped to the default MTU
+        assert_eq!(mgr.exchange_att_mtu(handle, 5), 23);
+    }
+
     #[test]
     fn disconnecting_iterator_invalid() {
-        let mut storage = [ConnectionStorage::DISCONNECTED; 3];
-        let mgr = ConnectionManager::new(&mut storage[..]);
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
 
         assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
 
@@ -758,4 +1056,119 @@ mod tests {
 
         assert!(mgr.poll_disconnecting(None).is_pending());
     }
+
+    #[test]
+    fn att_routed_per_connection() {
+        static POOL: StaticCell<PacketPool<NoopRawMutex, 8, 4, 1>> = StaticCell::new();
+        let pool: &'static PacketPool<NoopRawMutex, 8, 4, 1> = POOL.init(PacketPool::new(Qos::None));
+        let pdu = |data: &[u8]| {
+            let mut packet = unwrap!(pool.alloc(ATT_ID));
+            packet.as_mut()[..data.len()].copy_from_slice(data);
+            Pdu::new(packet, data.len())
+        };
+
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
+        let (h1, h2) = (ConnHandle::new(1), ConnHandle::new(2));
+        unwrap!(mgr.connect(h1, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Central));
+        unwrap!(mgr.connect(h2, AddrKind::RANDOM, BdAddr::new(ADDR_2), LeConnRole::Central));
+        let Poll::Ready(_c1) = mgr.poll_accept(LeConnRole::Central, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+        let Poll::Ready(_c2) = mgr.poll_accept(LeConnRole::Central, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+
+        let response = |h| poll_once(poll_fn(|cx| mgr.poll_att_response(h, cx)));
+        let notification = |h| poll_once(poll_fn(|cx| mgr.poll_att_notification(h, cx)));
+
+        // Responses and notifications are kept apart, per connection
+        unwrap!(mgr.post_att(h2, pdu(&[0x1b, 0x03, 0x00, 0x01])));
+        unwrap!(mgr.post_att(h1, pdu(&[0x13])));
+        assert!(matches!(mgr.post_att(h1, pdu(&[0x13])), Err(Error::OutOfMemory)));
+        assert!(response(h2).is_pending());
+        assert!(notification(h1).is_pending());
+        let Poll::Ready(Ok(rsp)) = response(h1) else {
+            panic!("expected a response");
+        };
+        assert_eq!(rsp.as_ref(), &[0x13]);
+        let Poll::Ready(Ok(ntf)) = notification(h2) else {
+            panic!("expected a notification");
+        };
+        assert_eq!(ntf.as_ref(), &[0x1b, 0x03, 0x00, 0x01]);
+
+        // Clients of a connection take turns, and a stale response is discarded when a turn starts
+        unwrap!(mgr.post_att(h1, pdu(&[0x13])));
+        let Poll::Ready(Ok(turn)) = poll_once(mgr.att_turn(h1)) else {
+            panic!("expected a turn");
+        };
+        assert!(response(h1).is_pending());
+        assert!(poll_once(mgr.att_turn(h1)).is_pending());
+        assert!(poll_once(mgr.att_turn(h2)).is_ready());
+        drop(turn);
+        assert!(poll_once(mgr.att_turn(h1)).is_ready());
+
+        // Waiting clients see the connection is gone
+        unwrap!(mgr.disconnected(h1));
+        assert!(matches!(response(h1), Poll::Ready(Err(Error::Disconnected))));
+    }
+
+    #[test]
+    fn att_timed_out_per_connection() {
+        let mut storage = [ConnectionStorage::<1>::DISCONNECTED; 3];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
+        let (conn1, conn2) = (ConnHandle::new(1), ConnHandle::new(2));
+        unwrap!(mgr.connect(conn1, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Central));
+        unwrap!(mgr.connect(conn2, AddrKind::RANDOM, BdAddr::new(ADDR_2), LeConnRole::Central));
+
+        // Clients refuse further transactions on the connection once one timed out
+        mgr.set_att_timed_out(conn1);
+        assert!(mgr.att_timed_out(conn1));
+        assert!(!mgr.att_timed_out(conn2));
+
+        // A new connection reusing the handle starts afresh
+        unwrap!(mgr.disconnected(conn1));
+        assert!(!mgr.att_timed_out(conn1));
+        unwrap!(mgr.connect(conn1, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Central));
+        assert!(!mgr.att_timed_out(conn1));
+    }
+
+    #[test]
+    fn att_notifications_queued() {
+        static POOL: StaticCell<PacketPool<NoopRawMutex, 8, 4, 1>> = StaticCell::new();
+        let pool: &'static PacketPool<NoopRawMutex, 8, 4, 1> = POOL.init(PacketPool::new(Qos::None));
+        let pdu = |data: &[u8]| {
+            let mut packet = unwrap!(pool.alloc(ATT_ID));
+            packet.as_mut()[..data.len()].copy_from_slice(data);
+            Pdu::new(packet, data.len())
+        };
+
+        let mut storage = [ConnectionStorage::<2>::DISCONNECTED; 1];
+        let mgr = ConnectionManager::new(&mut storage[..], 23);
+        let h = ConnHandle::new(1);
+        unwrap!(mgr.connect(h, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Central));
+        let Poll::Ready(_c) = mgr.poll_accept(LeConnRole::Central, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+
+        let notification = || poll_once(poll_fn(|cx| mgr.poll_att_notification(h, cx)));
+        let next = || {
+            let Poll::Ready(Ok(pdu)) = notification() else {
+                panic!("expected a notification");
+            };
+            pdu
+        };
+
+        // Two notifications sent back to back are both kept
+        unwrap!(mgr.post_att(h, pdu(&[0x1b, 0x03, 0x00, 0x01])));
+        unwrap!(mgr.post_att(h, pdu(&[0x1b, 0x05, 0x00, 0x02])));
+
+        // Once the queue is full, notifications are dropped, but an indication is kept instead of the oldest one
+        unwrap!(mgr.post_att(h, pdu(&[0x1b, 0x07, 0x00, 0x03])));
+        unwrap!(mgr.post_att(h, pdu(&[0x1d, 0x09, 0x00, 0x04])));
+
+        assert_eq!(next().as_ref(), &[0x1b, 0x05, 0x00, 0x02]);
+        assert_eq!(next().as_ref(), &[0x1d, 0x09, 0x00, 0x04]);
+        assert!(notification().is_pending());
+    }
 }
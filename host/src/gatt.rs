@@ -1,111 +1,210 @@
-use bt_hci::controller::Controller;
-use bt_hci::param::ConnHandle;
+use core::cell::{Cell, RefCell};
+use core::future::{poll_fn, Future};
+use core::iter::Peekable;
+use core::ops::Range;
+use core::task::{Context, Poll};
+
+use bt_hci::controller::{blocking, Controller};
+use bt_hci::param::{AddrKind, BdAddr, ConnHandle};
+use bt_hci::FromHciBytes;
+use embassy_futures::select::{select, Either};
 use embassy_sync::blocking_mutex::raw::RawMutex;
 use embassy_sync::channel::DynamicReceiver;
+use embassy_sync::waitqueue::MultiWakerRegistration;
+use embassy_time::{Duration, Timer};
 use heapless::Vec;
 
-use crate::att::{self, AttReq, AttRsp, ATT_HANDLE_VALUE_NTF};
-use crate::attribute::{Characteristic, Uuid, CHARACTERISTIC_UUID16, PRIMARY_SERVICE_UUID16};
+use crate::att::{
+    self, AttReq, AttRsp, ATT_HANDLE_VALUE_CFM, ATT_HANDLE_VALUE_IND, ATT_HANDLE_VALUE_NTF,
+    ATT_MULTIPLE_HANDLE_VALUE_NTF, ATT_READ_MULTIPLE_REQ, ATT_READ_MULTIPLE_VARIABLE_REQ, ATT_WRITE_REQ,
+};
+use crate::attribute::{
+    Characteristic, CharacteristicProp, CharacteristicProps, Uuid, CHARACTERISTIC_CCCD_UUID16, CHARACTERISTIC_UUID16,
+    INCLUDE_SERVICE_UUID16, PRIMARY_SERVICE_UUID16,
+};
 use crate::attribute_server::AttributeServer;
 use crate::connection::Connection;
-use crate::connection_manager::DynamicConnectionManager;
+use crate::connection_manager::{queue_notification, DynamicConnectionManager};
 use crate::cursor::{ReadCursor, WriteCursor};
 use crate::host::BleHost;
+use crate::l2cap::{L2capChannel, L2capChannelConfig};
+use crate::packet_pool::Packet;
 use crate::pdu::Pdu;
-use crate::types::l2cap::L2capHeader;
-use crate::{BleHostError, Error};
-
-pub struct GattServer<
-    'reference,
-    'values,
-    'resources,
-    M: RawMutex,
-    T: Controller,
-    const MAX: usize,
-    // Including l2cap header
-    const MTU: usize = 27,
-> {
+use crate::types::gatt_traits::GattValue;
+use crate::types::l2cap::{L2capHeader, EATT_PSM, L2CAP_CID_ATT};
+use crate::{codec, config, Address, BleHostError, Error};
+
+// Minimum ATT MTU of an Enhanced ATT bearer.
+const EATT_MIN_MTU: u16 = 64;
+
+// Time allowed for the server to respond to a request, after which the bearer can no longer be used.
+pub(crate) const ATT_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Number of descriptors kept for each discovered characteristic.
+const MAX_DESCRIPTORS: usize = 4;
+
+// Number of dropped subscriptions that can wait to be disabled.
+const MAX_UNSUBSCRIBED: usize = 4;
+
+// Number of subscriptions a client can hold at the same time.
+const MAX_SUBSCRIPTIONS: usize = 8;
+
+// Number of tasks waiting on a client that are woken individually, beyond which all are woken.
+const MAX_WAITERS: usize = 4;
+
+// UUIDs of the GATT service characteristics used to keep a database cache valid.
+const SERVICE_CHANGED_UUID16: Uuid = Uuid::new_short(0x2a05);
+const DATABASE_HASH_UUID16: Uuid = Uuid::new_short(0x2b2a);
+
+// Version of the serialized database cache format.
+const DATABASE_CACHE_VERSION: u8 = 2;
+
+pub struct GattServer<'reference, 'values, 'resources, M: RawMutex, T: Controller, const MAX: usize> {
     pub(crate) server: AttributeServer<'reference, 'values, M, MAX>,
     pub(crate) rx: DynamicReceiver<'reference, (ConnHandle, Pdu)>,
     pub(crate) ble: &'reference BleHost<'resources, T>,
 }
 
-impl<'reference, 'values, 'resources, M: RawMutex, T: Controller, const MAX: usize, const MTU: usize>
-    GattServer<'reference, 'values, 'resources, M, T, MAX, MTU>
+impl<'reference, 'values, 'resources, M: RawMutex, T: Controller, const MAX: usize>
+    GattServer<'reference, 'values, 'resources, M, T, MAX>
 {
     /// Process GATT requests and update the attribute table accordingly.
     ///
     /// If attributes are written or read, an event will be returned describing the handle
     /// and the connection causing the event.
+    ///
+    /// Only requests received on the fixed ATT channel are processed, requests on EATT bearers are
+    /// processed by the bearer returned from [`GattServer::accept_eatt`].
+    ///
+    /// State held for a connection, like subscriptions and per-connection values, is cleared when the connection
+    /// is closed.
     pub async fn next(&self) -> Result<GattEvent<'reference>, BleHostError<T::Error>> {
         loop {
-            let (handle, pdu) = self.rx.receive().await;
+            let (handle, pdu) = match select(self.rx.receive(), self.ble.att_disconnected.take()).await {
+                Either::First(inbound) => inbound,
+                Either::Second(conn) => {
+                    self.server.disconnected(conn);
+                    continue;
+                }
+            };
             if let Some(connection) = self.ble.connections.get_connected_handle(handle) {
-                match AttReq::decode(pdu.as_ref()) {
-                    Ok(att) => {
-                        let mut tx = [0; MTU];
-                        let mut w = WriteCursor::new(&mut tx);
-                        let (mut header, mut data) = w.split(4)?;
-
-                        match self.server.process(handle, &att, data.write_buf()) {
-                            Ok(Some(written)) => {
-                                let mtu = self.ble.connections.get_att_mtu(handle);
-                                data.commit(written)?;
-                                data.truncate(mtu as usize);
-                                header.write(written as u16)?;
-                                header.write(4_u16)?;
-                                let len = header.len() + data.len();
-                                self.ble.acl(handle, 1).await?.send(&tx[..len]).await?;
-
-                                match att {
-                                    AttReq::Write { handle, data } => {
-                                        return Ok(GattEvent::Write {
-                                            connection,
-                                            handle: Characteristic {
-                                                handle,
-                                                cccd_handle: None,
-                                            },
-                                        })
-                                    }
-
-                                    AttReq::Read { handle } => {
-                                        return Ok(GattEvent::Read {
-                                            connection,
-                                            handle: Characteristic {
-                                                handle,
-                                                cccd_handle: None,
-                                            },
-                                        })
-                                    }
-
-                                    AttReq::ReadBlob { handle, offset } => {
-                                        return Ok(GattEvent::Read {
-                                            connection,
-                                            handle: Characteristic {
-                                                handle,
-                                                cccd_handle: None,
-                                            },
-                                        })
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            Ok(None) => {
-                                debug!("No response sent");
-                            }
-                            Err(e) => {
-                                warn!("Error processing attribute: {:?}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Error decoding attribute request: {:?}", e);
-                    }
+                // Responses are limited by the negotiated MTU
+                let mtu = self.ble.connections.get_att_mtu(handle) as usize;
+                let mut tx = self.ble.alloc_att()?;
+                let (header, rsp) = tx.as_mut()[..4 + mtu].split_at_mut(4);
+                let (written, event) = self.process(&connection, pdu.as_ref(), rsp);
+                drop(pdu);
+
+                if written > 0 {
+                    let mut w = WriteCursor::new(header);
+                    w.write(written as u16)?;
+                    w.write(4_u16)?;
+                    let len = 4 + written;
+                    let pdu = &tx.as_ref()[..len];
+                    self.ble.acl(handle, self.ble.acl_packets(len)).await?.send(pdu).await?;
                 }
+
+                if let Some(event) = event {
+                    return Ok(event);
+                }
+            }
+        }
+    }
+
+    /// Accept an Enhanced ATT bearer opened by the client on a connection.
+    ///
+    /// Requests on the bearer are processed by calling [`EattBearer::next`], independently of the
+    /// fixed ATT channel and of other bearers.
+    ///
+    /// EATT requires an MTU of at least 64, so the packet pool must hold packets of at least 68 bytes.
+    pub async fn accept_eatt(
+        &self,
+        connection: &Connection<'reference>,
+    ) -> Result<EattBearer<'_, 'reference, 'values, 'resources, M, T, MAX>, BleHostError<T::Error>> {
+        let mtu = self.ble.connections.default_att_mtu();
+        if mtu < EATT_MIN_MTU {
+            return Err(Error::NotSupported.into());
+        }
+        let config = L2capChannelConfig {
+            mtu,
+            ..Default::default()
+        };
+        let channel = L2capChannel::accept(self.ble, connection, &[EATT_PSM], &config).await?;
+        Ok(EattBearer {
+            server: self,
+            connection: connection.clone(),
+            channel,
+        })
+    }
+
+    // Clear the state of the connections closed so far, before a new connection reusing the handle can see it.
+    fn release_disconnected(&self) {
+        while let Some(conn) = self.ble.att_disconnected.try_take() {
+            self.server.disconnected(conn);
+        }
+    }
+
+    // Process a request, writing the response to `rsp`.
+    //
+    // Returns the length of the response (0 if none should be sent), and the event caused by the request.
+    fn process(
+        &self,
+        connection: &Connection<'reference>,
+        req: &[u8],
+        rsp: &mut [u8],
+    ) -> (usize, Option<GattEvent<'reference>>) {
+        self.release_disconnected();
+        let att = match AttReq::decode(req) {
+            Ok(att) => att,
+            Err(e) => {
+                warn!("Error decoding attribute request: {:?}", e);
+                return match self.server.reject(req, rsp) {
+                    Ok(written) => (written.unwrap_or(0), None),
+                    Err(e) => (0, None),
+                };
+            }
+        };
+
+        match self.server.process(connection.handle(), &att, rsp) {
+            Ok(Some(written)) => {
+                let event = match att {
+                    AttReq::Write { handle, data } => Some(GattEvent::Write {
+                        connection: connection.clone(),
+                        handle: Characteristic {
+                            handle,
+                            cccd_handle: None,
+                        },
+                    }),
+                    AttReq::Read { handle } | AttReq::ReadBlob { handle, .. } => Some(GattEvent::Read {
+                        connection: connection.clone(),
+                        handle: Characteristic {
+                            handle,
+                            cccd_handle: None,
+                        },
+                    }),
+                    _ => None,
+                };
+                (written, event)
+            }
+            Ok(None) => {
+                debug!("No response sent");
+                (0, None)
+            }
+            Err(e) => {
+                warn!("Error processing attribute: {:?}", e);
+                (0, None)
             }
         }
     }
 
+    /// Initiate an ATT MTU exchange with the client on a connection.
+    ///
+    /// Servers that need a larger MTU can call this right after a connection is established, rather than
+    /// waiting for the client to initiate the exchange. The negotiated MTU is applied when the client responds.
+    pub async fn exchange_mtu(&self, connection: &Connection<'_>) -> Result<(), BleHostError<T::Error>> {
+        self.ble.exchange_att_mtu(connection.handle()).await
+    }
+
     /// Write a value to a characteristic, and notify a connection with the new value of the characteristic.
     ///
     /// If the provided connection has not subscribed for this characteristic, it will not be notified.
@@ -117,31 +216,171 @@ impl<'reference, 'values, 'resources, M: RawMutex, T: Controller, const MAX: usi
         connection: &Connection<'_>,
         value: &[u8],
     ) -> Result<(), BleHostError<T::Error>> {
-        let conn = connection.handle();
         self.server.table.set(handle, value)?;
+        self.send_notification(handle, connection).await
+    }
+
+    /// Write a typed value to a characteristic, and notify a connection with the new value of the characteristic.
+    ///
+    /// If the provided connection has not subscribed for this characteristic, it will not be notified.
+    ///
+    /// If the characteristic for the handle cannot be found, an error is returned.
+    pub async fn notify_value<V: GattValue>(
+        &self,
+        handle: Characteristic,
+        connection: &Connection<'_>,
+        value: &V,
+    ) -> Result<(), BleHostError<T::Error>> {
+        self.server.table.set_value(handle, value)?;
+        self.send_notification(handle, connection).await
+    }
 
+    /// Write values to several characteristics, and notify a connection with the new values.
+    ///
+    /// If the client has enabled Multiple Handle Value Notifications by writing the Client Supported Features
+    /// characteristic, the values are batched into as few PDUs as possible. Otherwise, or for values too large to
+    /// be batched, individual notifications are sent.
+    ///
+    /// The Client Supported Features characteristic (0x2B29) is not added to the attribute table automatically:
+    /// to let clients enable batching, add it as a readable and writable characteristic of the Generic Attribute
+    /// service, with a value of at least one byte. The features written by each client are kept by the server.
+    ///
+    /// Characteristics the connection has not subscribed to are updated, but not notified.
+    ///
+    /// If the characteristic for a handle cannot be found, an error is returned.
+    pub async fn notify_multiple(
+        &self,
+        connection: &Connection<'_>,
+        values: &[(Characteristic, &[u8])],
+    ) -> Result<(), BleHostError<T::Error>> {
+        let conn = connection.handle();
+        for (handle, value) in values.iter() {
+            self.server.table.set(*handle, value)?;
+        }
+
+        self.release_disconnected();
+        if !self.server.supports_multiple_notifications(conn) {
+            for (handle, _) in values.iter() {
+                self.send_notification(*handle, connection).await?;
+            }
+            return Ok(());
+        }
+
+        let mtu = self.ble.connections.get_att_mtu(conn) as usize;
+        let mut it = values
+            .iter()
+            .filter(|(handle, _)| {
+                handle
+                    .cccd_handle
+                    .map(|cccd| self.server.should_notify(conn, cccd))
+                    .unwrap_or(false)
+            })
+            .peekable();
+
+        while let Some(&&(first, _)) = it.peek() {
+            let mut tx = self.ble.alloc_att()?;
+            let mut w = WriteCursor::new(&mut tx.as_mut()[..4 + mtu]);
+            let (mut header, mut data) = w.split(4)?;
+            let count = batch_notification_values(&mut data, &mut it)?;
+
+            if count >= 2 {
+                header.write(data.len() as u16)?;
+                header.write(4_u16)?;
+                let total = header.len() + data.len();
+                let pdu = &tx.as_ref()[..total];
+                self.ble.acl(conn, self.ble.acl_packets(total)).await?.send(pdu).await?;
+                continue;
+            }
+
+            // A multiple notification must hold at least two values, and a value that does not fit
+            // alongside another is sent on its own.
+            drop(tx);
+            if count == 0 {
+                it.next();
+            }
+            self.send_notification(first, connection).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_notification(
+        &self,
+        handle: Characteristic,
+        connection: &Connection<'_>,
+    ) -> Result<(), BleHostError<T::Error>> {
+        let conn = connection.handle();
         let cccd_handle = handle.cccd_handle.ok_or(Error::Other)?;
 
+        self.release_disconnected();
         if !self.server.should_notify(conn, cccd_handle) {
             // No reason to fail?
             return Ok(());
         }
 
-        let mut tx = [0; MTU];
-        let mut w = WriteCursor::new(&mut tx[..]);
+        let mtu = self.ble.connections.get_att_mtu(conn) as usize;
+        let mut tx = self.ble.alloc_att()?;
+        let mut w = WriteCursor::new(&mut tx.as_mut()[..4 + mtu]);
         let (mut header, mut data) = w.split(4)?;
         data.write(ATT_HANDLE_VALUE_NTF)?;
         data.write(handle.handle)?;
-        data.append(value)?;
+        // Values longer than the MTU allows are truncated
+        self.server.table.get(handle, |value| {
+            let len = value.len().min(data.available());
+            data.append(&value[..len])
+        })??;
 
         header.write(data.len() as u16)?;
         header.write(4_u16)?;
         let total = header.len() + data.len();
-        self.ble.acl(conn, 1).await?.send(&tx[..total]).await?;
+        let pdu = &tx.as_ref()[..total];
+        self.ble.acl(conn, self.ble.acl_packets(total)).await?.send(pdu).await?;
         Ok(())
     }
 }
 
+/// An Enhanced ATT bearer between a GATT server and a client, carried by an L2CAP channel.
+pub struct EattBearer<'server, 'reference, 'values, 'resources, M: RawMutex, T: Controller, const MAX: usize> {
+    server: &'server GattServer<'reference, 'values, 'resources, M, T, MAX>,
+    connection: Connection<'reference>,
+    channel: L2capChannel<'reference>,
+}
+
+impl<'server, 'reference, 'values, 'resources, M: RawMutex, T: Controller, const MAX: usize>
+    EattBearer<'server, 'reference, 'values, 'resources, M, T, MAX>
+{
+    /// Process GATT requests received on this bearer and update the attribute table accordingly.
+    ///
+    /// If attributes are written or read, an event will be returned describing the handle
+    /// and the connection causing the event.
+    pub async fn next(&mut self) -> Result<GattEvent<'reference>, BleHostError<T::Error>> {
+        let ble = self.server.ble;
+        loop {
+            let mut rx = ble.alloc_att()?;
+            let len = self.channel.receive(ble, rx.as_mut()).await?;
+
+            let mtu = self.channel.mtu() as usize;
+            let mut tx = ble.alloc_att()?;
+            let (written, event) = self
+                .server
+                .process(&self.connection, &rx.as_ref()[..len], &mut tx.as_mut()[..mtu]);
+            drop(rx);
+
+            if written > 0 {
+                self.channel.send_pooled(ble, &tx.as_ref()[..written]).await?;
+            }
+
+            if let Some(event) = event {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// The connection this bearer belongs to.
+    pub fn connection(&self) -> &Connection<'reference> {
+        &self.connection
+    }
+}
+
 #[derive(Clone)]
 pub enum GattEvent<'reference> {
     Read {
@@ -154,11 +393,190 @@ pub enum GattEvent<'reference> {
     },
 }
 
-pub struct GattClient<'reference, 'resources, T: Controller, const MAX: usize, const ATT_MTU: usize = 27> {
+pub struct GattClient<'reference, 'resources, T: Controller, const MAX: usize> {
     pub(crate) services: Vec<ServiceHandle, MAX>,
-    pub(crate) rx: DynamicReceiver<'reference, (ConnHandle, Pdu)>,
     pub(crate) ble: &'reference BleHost<'resources, T>,
     pub(crate) connection: Connection<'reference>,
+    // Notification or indication being received, and the position of its next value. The value is kept until the
+    // subscription of its characteristic receives it.
+    pub(crate) notifications: RefCell<Option<(Pdu, usize)>>,
+    pub(crate) bearer: Bearer<'reference>,
+    // Set when a transaction on an Enhanced ATT bearer timed out, after which no more PDUs may be exchanged on it.
+    // The fixed ATT channel keeps this state with the connection, as it is shared by all its clients.
+    pub(crate) timed_out: Cell<bool>,
+    // Notifications and indications received on an Enhanced ATT bearer while waiting for a response
+    pub(crate) pending: RefCell<heapless::Deque<Pdu, { config::GATT_CLIENT_NOTIFICATION_QUEUE_SIZE }>>,
+    // Response received on an Enhanced ATT bearer while waiting for a notification
+    pub(crate) response: RefCell<Option<Pdu>>,
+    // Set while a task of the client receives from the bearer, or has a request outstanding
+    pub(crate) receiving: Cell<bool>,
+    pub(crate) requesting: Cell<bool>,
+    // Tasks waiting for one of the above to change
+    pub(crate) waiters: RefCell<MultiWakerRegistration<MAX_WAITERS>>,
+    // Value handles of the characteristics with a subscription
+    pub(crate) subscribed: RefCell<Vec<u16, MAX_SUBSCRIPTIONS>>,
+    // CCCDs of dropped subscriptions, to be disabled before the next request
+    pub(crate) unsubscribed: RefCell<Vec<u16, MAX_UNSUBSCRIBED>>,
+    // Set when a reliable write was dropped, to cancel its prepared writes before the next request
+    pub(crate) cancel_prepared: bool,
+    // Value handle of the Service Changed characteristic, once the database is discovered or restored
+    pub(crate) service_changed: Option<u16>,
+    // Set when the server indicated a change of its database
+    pub(crate) database_changed: Cell<bool>,
+}
+
+/// Reliable write session, created by [`GattClient::reliable_write`].
+///
+/// Values are queued on the server with Prepare Write requests, and the echo of every part is checked against what
+/// was sent. Nothing is written until the session is executed, and the queued values are discarded if the session is
+/// cancelled, fails or is dropped.
+pub struct ReliableWrite<'c, 'reference, 'resources, T: Controller, const MAX: usize> {
+    client: &'c mut GattClient<'reference, 'resources, T, MAX>,
+    // Set while values are queued on the server
+    prepared: bool,
+}
+
+impl<'c, 'reference, 'resources, T: Controller, const MAX: usize> ReliableWrite<'c, 'reference, 'resources, T, MAX> {
+    /// Queue a value to write to a characteristic.
+    ///
+    /// If the server doesn't echo the value as sent, the session is cancelled and [`Error::InvalidValue`] returned.
+    pub async fn write(&mut self, characteristic: &Characteristic, data: &[u8]) -> Result<(), BleHostError<T::Error>> {
+        self.prepared = true;
+        if let Err(e) = self.client.prepare_write(characteristic.handle, data, true).await {
+            self.prepared = false;
+            if let Err(e) = self.client.execute_write(false).await {
+                warn!("[gatt] error cancelling reliable write: {:?}", e);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Write all queued values.
+    pub async fn execute(mut self) -> Result<(), BleHostError<T::Error>> {
+        self.prepared = false;
+        self.client.execute_write(true).await
+    }
+
+    /// Discard all queued values.
+    pub async fn cancel(mut self) -> Result<(), BleHostError<T::Error>> {
+        self.prepared = false;
+        self.client.execute_write(false).await
+    }
+}
+
+impl<'c, 'reference, 'resources, T: Controller, const MAX: usize> Drop
+    for ReliableWrite<'c, 'reference, 'resources, T, MAX>
+{
+    fn drop(&mut self) {
+        if self.prepared {
+            self.client.cancel_prepared = true;
+        }
+    }
+}
+
+/// Subscription to the notifications or indications of a characteristic, created by [`GattClient::subscribe`].
+///
+/// Several subscriptions of a client can be used at the same time. A value received for another subscription is
+/// kept until that subscription receives it, so every subscription must keep receiving for the others to make
+/// progress. Values of characteristics without a subscription are discarded.
+pub struct Subscription<'c, 'reference, 'resources, T: Controller, const MAX: usize> {
+    client: &'c GattClient<'reference, 'resources, T, MAX>,
+    handle: u16,
+    // Cleared once the subscription is disabled
+    cccd: Option<u16>,
+}
+
+impl<'c, 'reference, 'resources, T: Controller, const MAX: usize> Subscription<'c, 'reference, 'resources, T, MAX> {
+    /// Wait for the next value of the characteristic, copying it into the provided buffer.
+    ///
+    /// The number of bytes copied is returned.
+    pub async fn next(&mut self, dest: &mut [u8]) -> Result<usize, BleHostError<T::Error>> {
+        let (_, len) = self
+            .client
+            .next_value_with(Some(self.handle), |_, value| copy_value(value, dest))
+            .await?;
+        Ok(len)
+    }
+
+    /// Wait for the next value of the characteristic, decoded as a typed value.
+    pub async fn next_value<V: GattValue>(&mut self) -> Result<V, BleHostError<T::Error>> {
+        let (_, value) = self
+            .client
+            .next_value_with(Some(self.handle), |_, value| V::from_gatt(value))
+            .await?;
+        Ok(value?)
+    }
+
+    /// Disable the subscription, waiting for the server to acknowledge it.
+    pub async fn unsubscribe(mut self) -> Result<(), BleHostError<T::Error>> {
+        self.client.remove_subscribed(self.handle);
+        match self.cccd.take() {
+            Some(cccd) => self.client.write_cccd(cccd, 0).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'c, 'reference, 'resources, T: Controller, const MAX: usize> Drop
+    for Subscription<'c, 'reference, 'resources, T, MAX>
+{
+    fn drop(&mut self) {
+        if let Some(cccd) = self.cccd.take() {
+            self.client.remove_subscribed(self.handle);
+            if self.client.unsubscribed.borrow_mut().push(cccd).is_err() {
+                warn!("[gatt] unable to disable subscription of {}", self.handle);
+            }
+        }
+    }
+}
+
+// Waiting position of a task receiving notifications.
+enum NotificationHead {
+    // The next value is for the task
+    Take,
+    // The next value is for no subscription
+    Discard,
+    // Nothing is kept, and no other task receives from the bearer
+    Receive,
+}
+
+// Marks a task receiving from the bearer of a client, or having a request outstanding on it, until dropped.
+struct Busy<'a> {
+    flag: &'a Cell<bool>,
+    waiters: &'a RefCell<MultiWakerRegistration<MAX_WAITERS>>,
+}
+
+impl<'a> Busy<'a> {
+    // Wait for the flag to be cleared, and set it.
+    async fn take(flag: &'a Cell<bool>, waiters: &'a RefCell<MultiWakerRegistration<MAX_WAITERS>>) -> Busy<'a> {
+        poll_fn(|cx| {
+            if flag.get() {
+                waiters.borrow_mut().register(cx.waker());
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+        .await;
+        flag.set(true);
+        Busy { flag, waiters }
+    }
+}
+
+impl<'a> Drop for Busy<'a> {
+    fn drop(&mut self) {
+        self.flag.set(false);
+        self.waiters.borrow_mut().wake();
+    }
+}
+
+// Bearer used by a client to reach the server.
+pub(crate) enum Bearer<'reference> {
+    // The fixed ATT channel, shared by all clients of a connection.
+    Fixed,
+    // An Enhanced ATT bearer, dedicated to a client.
+    Eatt(L2capChannel<'reference>),
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -169,30 +587,909 @@ pub struct ServiceHandle {
     uuid: Uuid,
 }
 
-impl<'reference, 'resources, T: Controller, const MAX: usize, const ATT_MTU: usize>
-    GattClient<'reference, 'resources, T, MAX, ATT_MTU>
-{
-    async fn request(&mut self, req: AttReq<'_>) -> Result<Pdu, BleHostError<T::Error>> {
-        let header = L2capHeader {
-            channel: crate::types::l2cap::L2CAP_CID_ATT,
-            length: req.size() as u16,
+/// A characteristic discovered on a server, with its properties and descriptors.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiscoveredCharacteristic {
+    declaration: u16,
+    handle: u16,
+    end: u16,
+    props: CharacteristicProps,
+    uuid: Uuid,
+    cccd_handle: Option<u16>,
+    descriptors: Vec<DiscoveredDescriptor, MAX_DESCRIPTORS>,
+}
+
+impl DiscoveredCharacteristic {
+    /// Handle of the characteristic declaration.
+    pub fn declaration(&self) -> u16 {
+        self.declaration
+    }
+
+    /// Handle of the characteristic value.
+    pub fn handle(&self) -> u16 {
+        self.handle
+    }
+
+    /// Last handle of the characteristic, including its descriptors.
+    pub fn end(&self) -> u16 {
+        self.end
+    }
+
+    /// UUID of the characteristic.
+    pub fn uuid(&self) -> &Uuid {
+        &self.uuid
+    }
+
+    /// Properties of the characteristic.
+    pub fn props(&self) -> CharacteristicProps {
+        self.props
+    }
+
+    /// Check if the characteristic has a property.
+    pub fn has(&self, prop: CharacteristicProp) -> bool {
+        self.props.any(&[prop])
+    }
+
+    /// Handle of the client characteristic configuration descriptor, if any.
+    pub fn cccd_handle(&self) -> Option<u16> {
+        self.cccd_handle
+    }
+
+    /// Descriptors of the characteristic.
+    ///
+    /// Only the first few descriptors are kept, the CCCD handle is always available through
+    /// [`DiscoveredCharacteristic::cccd_handle`].
+    pub fn descriptors(&self) -> &[DiscoveredDescriptor] {
+        &self.descriptors
+    }
+
+    /// Handles of the characteristic, for reading, writing and subscribing.
+    pub fn characteristic(&self) -> Characteristic {
+        Characteristic {
+            handle: self.handle,
+            cccd_handle: self.cccd_handle,
+        }
+    }
+}
+
+impl From<&DiscoveredCharacteristic> for Characteristic {
+    fn from(c: &DiscoveredCharacteristic) -> Self {
+        c.characteristic()
+    }
+}
+
+/// A characteristic descriptor discovered on a server.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiscoveredDescriptor {
+    handle: u16,
+    uuid: Uuid,
+}
+
+impl DiscoveredDescriptor {
+    /// Handle of the descriptor.
+    pub fn handle(&self) -> u16 {
+        self.handle
+    }
+
+    /// UUID of the descriptor.
+    pub fn uuid(&self) -> &Uuid {
+        &self.uuid
+    }
+}
+
+/// Services, characteristics and descriptors discovered on a server, which can be stored to skip discovery when
+/// reconnecting to the same peer.
+#[derive(Debug, Clone)]
+pub struct DatabaseCache<const SERVICES: usize, const CHARACTERISTICS: usize> {
+    peer: Option<Address>,
+    hash: Option<[u8; 16]>,
+    services: Vec<ServiceHandle, SERVICES>,
+    characteristics: Vec<DiscoveredCharacteristic, CHARACTERISTICS>,
+}
+
+impl<const SERVICES: usize, const CHARACTERISTICS: usize> DatabaseCache<SERVICES, CHARACTERISTICS> {
+    /// Identity address of the peer the database belongs to, if known when it was discovered.
+    ///
+    /// A database without one can't be matched to the peer when it reconnects, so it is never restored.
+    pub fn peer(&self) -> Option<Address> {
+        self.peer
+    }
+
+    /// Database Hash of the server when the database was discovered, if it has one.
+    pub fn hash(&self) -> Option<&[u8; 16]> {
+        self.hash.as_ref()
+    }
+
+    /// Primary services of the server.
+    pub fn services(&self) -> &[ServiceHandle] {
+        &self.services
+    }
+
+    /// Characteristics of a service.
+    pub fn characteristics<'a>(
+        &'a self,
+        service: &'a ServiceHandle,
+    ) -> impl Iterator<Item = &'a DiscoveredCharacteristic> + 'a {
+        self.characteristics
+            .iter()
+            .filter(|c| c.declaration >= service.start && c.declaration <= service.end)
+    }
+
+    /// Find a characteristic of a service by UUID.
+    pub fn characteristic_by_uuid(&self, service: &ServiceHandle, uuid: &Uuid) -> Option<&DiscoveredCharacteristic> {
+        self.characteristics(service).find(|c| &c.uuid == uuid)
+    }
+
+    /// Serialize the database into `dest`, returning the number of bytes written.
+    pub fn encode(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        let mut w = WriteCursor::new(dest);
+        w.write(DATABASE_CACHE_VERSION)?;
+        match &self.peer {
+            Some(peer) => {
+                w.write(1u8)?;
+                w.write_hci(&peer.kind)?;
+                w.write_hci(&peer.addr)?;
+            }
+            None => w.write(0u8)?,
+        }
+        match &self.hash {
+            Some(hash) => {
+                w.write(1u8)?;
+                w.append(hash)?;
+            }
+            None => w.write(0u8)?,
+        }
+
+        w.write(self.services.len() as u16)?;
+        for service in self.services.iter() {
+            w.write(service.start)?;
+            w.write(service.end)?;
+            write_uuid(&mut w, &service.uuid)?;
+        }
+
+        w.write(self.characteristics.len() as u16)?;
+        for c in self.characteristics.iter() {
+            w.write(c.declaration)?;
+            w.write(c.handle)?;
+            w.write(c.end)?;
+            w.write(u8::from(c.props))?;
+            write_uuid(&mut w, &c.uuid)?;
+            // Handle 0 is never valid, so marks a missing CCCD
+            w.write(c.cccd_handle.unwrap_or(0))?;
+            w.write(c.descriptors.len() as u8)?;
+            for d in c.descriptors.iter() {
+                w.write(d.handle)?;
+                write_uuid(&mut w, &d.uuid)?;
+            }
+        }
+        Ok(w.len())
+    }
+
+    /// Deserialize a database written by [`DatabaseCache::encode`].
+    pub fn decode(src: &[u8]) -> Result<Self, Error> {
+        let mut r = ReadCursor::new(src);
+        let version: u8 = r.read()?;
+        if version != DATABASE_CACHE_VERSION {
+            return Err(Error::InvalidValue);
+        }
+        let peer = match r.read::<u8>()? {
+            0 => None,
+            _ => {
+                let kind = AddrKind::from_hci_bytes_complete(r.slice(1)?)?;
+                let addr: [u8; 6] = r.slice(6)?.try_into().map_err(|_| Error::InvalidValue)?;
+                Some(Address {
+                    kind,
+                    addr: BdAddr::new(addr),
+                })
+            }
+        };
+        let hash = match r.read::<u8>()? {
+            0 => None,
+            _ => Some(r.slice(16)?.try_into().map_err(|_| Error::InvalidValue)?),
+        };
+
+        let mut services = Vec::new();
+        for _ in 0..r.read::<u16>()? {
+            let service = ServiceHandle {
+                start: r.read()?,
+                end: r.read()?,
+                uuid: read_uuid(&mut r)?,
+            };
+            services.push(service).map_err(|_| Error::InsufficientSpace)?;
+        }
+
+        let mut characteristics = Vec::new();
+        for _ in 0..r.read::<u16>()? {
+            let mut c = DiscoveredCharacteristic {
+                declaration: r.read()?,
+                handle: r.read()?,
+                end: r.read()?,
+                props: r.read::<u8>()?.into(),
+                uuid: read_uuid(&mut r)?,
+                cccd_handle: Some(r.read()?).filter(|&h| h != 0),
+                descriptors: Vec::new(),
+            };
+            for _ in 0..r.read::<u8>()? {
+                let d = DiscoveredDescriptor {
+                    handle: r.read()?,
+                    uuid: read_uuid(&mut r)?,
+                };
+                c.descriptors.push(d).map_err(|_| Error::InvalidValue)?;
+            }
+            characteristics.push(c).map_err(|_| Error::InsufficientSpace)?;
+        }
+
+        Ok(Self {
+            peer,
+            hash,
+            services,
+            characteristics,
+        })
+    }
+}
+
+fn write_uuid(w: &mut WriteCursor<'_>, uuid: &Uuid) -> Result<(), codec::Error> {
+    let raw = uuid.as_raw();
+    w.write(raw.len() as u8)?;
+    w.append(raw)
+}
+
+fn read_uuid(r: &mut ReadCursor<'_>) -> Result<Uuid, codec::Error> {
+    let len: u8 = r.read()?;
+    match len {
+        2 | 16 => Ok(Uuid::from_slice(r.slice(len as usize)?)),
+        _ => Err(codec::Error::InvalidValue),
+    }
+}
+
+/// Persistent storage for the databases of peers discovered by GATT clients, such as flash.
+///
+/// Databases are keyed by the identity address of the peer. Failing to load or store a database only means it
+/// will be discovered again, so errors are left to the implementation to report.
+pub trait DatabaseStorage {
+    /// Load the serialized database of a peer into `buf`, returning its length, or `None` if none is stored.
+    async fn load(&mut self, peer: &Address, buf: &mut [u8]) -> Option<usize>;
+
+    /// Store the serialized database of a peer, replacing any previous one.
+    async fn store(&mut self, peer: &Address, data: &[u8]);
+
+    /// Remove the database of a peer, which is no longer valid.
+    async fn remove(&mut self, peer: &Address);
+}
+
+impl ServiceHandle {
+    /// Handle of the service declaration.
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// Last handle of the service.
+    pub fn end(&self) -> u16 {
+        self.end
+    }
+
+    /// UUID of the service.
+    pub fn uuid(&self) -> &Uuid {
+        &self.uuid
+    }
+}
+
+impl<'reference, 'resources, T: Controller, const MAX: usize> GattClient<'reference, 'resources, T, MAX> {
+    /// Open an additional Enhanced ATT bearer to the server on the same connection.
+    ///
+    /// The returned client sends its requests over the new bearer, so that they are processed independently of
+    /// requests from other clients. Services discovered by this client are available to the new client.
+    ///
+    /// EATT requires an MTU of at least 64, so the packet pool must hold packets of at least 68 bytes.
+    pub async fn open_eatt(&self) -> Result<Self, BleHostError<T::Error>> {
+        let mtu = self.ble.connections.default_att_mtu();
+        if mtu < EATT_MIN_MTU {
+            return Err(Error::NotSupported.into());
+        }
+        let config = L2capChannelConfig {
+            mtu,
+            ..Default::default()
         };
+        // Enhanced ATT bearers use the enhanced credit based flow control mode
+        let mut channels = L2capChannel::create_many::<_, 1>(self.ble, &self.connection, EATT_PSM, &config).await?;
+        let channel = channels.pop().ok_or(Error::NotFound)?;
+        Ok(Self {
+            services: self.services.clone(),
+            ble: self.ble,
+            connection: self.connection.clone(),
+            notifications: RefCell::new(None),
+            bearer: Bearer::Eatt(channel),
+            timed_out: Cell::new(false),
+            pending: RefCell::new(heapless::Deque::new()),
+            response: RefCell::new(None),
+            receiving: Cell::new(false),
+            requesting: Cell::new(false),
+            waiters: RefCell::new(MultiWakerRegistration::new()),
+            subscribed: RefCell::new(Vec::new()),
+            unsubscribed: RefCell::new(Vec::new()),
+            cancel_prepared: false,
+            service_changed: self.service_changed,
+            database_changed: Cell::new(self.database_changed.get()),
+        })
+    }
+
+    /// Exchange the ATT MTU with the server, offering to receive PDUs of up to `desired` bytes.
+    ///
+    /// The offer is limited to the largest MTU that fits in the packet pool. The negotiated MTU, which is the
+    /// smallest of both offers, is applied to the connection and returned. It may only be exchanged once per
+    /// connection, and not at all on an Enhanced ATT bearer, whose MTU is set when the channel is opened.
+    pub async fn exchange_mtu(&mut self, desired: u16) -> Result<u16, BleHostError<T::Error>> {
+        if let Bearer::Eatt(_) = self.bearer {
+            return Err(Error::NotSupported.into());
+        }
+        let mtu = desired.min(self.ble.connections.default_att_mtu()).max(23);
+        let pdu = self.request(AttReq::ExchangeMtu { mtu }).await?;
+        match AttRsp::decode(pdu.as_ref())? {
+            AttRsp::ExchangeMtu { mtu: server } => Ok(self
+                .ble
+                .connections
+                .exchange_att_mtu(self.connection.handle(), server.min(mtu))),
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::InvalidValue.into()),
+        }
+    }
+
+    // ATT MTU of the bearer used by this client.
+    fn mtu(&self) -> usize {
+        match &self.bearer {
+            Bearer::Fixed => self.connection.att_mtu() as usize,
+            Bearer::Eatt(channel) => channel.mtu() as usize,
+        }
+    }
 
-        let mut buf = [0; ATT_MTU];
-        let mut w = WriteCursor::new(&mut buf);
-        w.write_hci(&header)?;
+    async fn request(&mut self, req: AttReq<'_>) -> Result<Pdu, BleHostError<T::Error>> {
+        // Requests larger than the MTU are rejected by the cursor
+        let mtu = self.mtu();
+        let mut tx = self.ble.alloc_att()?;
+        let mut w = WriteCursor::new(&mut tx.as_mut()[4..4 + mtu]);
         w.write(req)?;
+        let len = w.len();
+
+        self.transaction(tx, len).await
+    }
+
+    // Send a request and wait for the response, after cleaning up the subscriptions and reliable writes that
+    // were dropped.
+    async fn transaction(&mut self, tx: Packet, len: usize) -> Result<Pdu, BleHostError<T::Error>> {
+        loop {
+            let Some(cccd) = self.unsubscribed.borrow_mut().pop() else {
+                break;
+            };
+            if let Err(e) = self.write_cccd(cccd, 0).await {
+                warn!("[gatt] error disabling subscription: {:?}", e);
+            }
+        }
+        if self.cancel_prepared {
+            self.cancel_prepared = false;
+            if let Err(e) = self.execute_write(false).await {
+                warn!("[gatt] error cancelling reliable write: {:?}", e);
+            }
+        }
+        self.exchange(tx, len).await
+    }
+
+    // Send a request and wait for the response, within the ATT transaction timeout.
+    //
+    // When the server doesn't respond in time, the bearer is closed: the link for the fixed ATT channel, or the
+    // L2CAP channel for an Enhanced ATT bearer.
+    async fn exchange(&self, tx: Packet, len: usize) -> Result<Pdu, BleHostError<T::Error>> {
+        // Only one request may be outstanding on a bearer, so the clients sharing the fixed ATT channel take turns,
+        // as do the tasks sharing a client
+        let ble = self.ble;
+        let handle = self.connection.handle();
+        let _turn = match &self.bearer {
+            Bearer::Fixed => Some(ble.connections.att_turn(handle).await?),
+            Bearer::Eatt(_) => None,
+        };
+        let _requesting = Busy::take(&self.requesting, &self.waiters).await;
+
+        self.send(tx, len).await?;
+        let expired = Timer::after(ATT_TRANSACTION_TIMEOUT);
+        within_timeout(self.response(), expired, || {
+            warn!("[gatt] transaction timed out, closing bearer");
+            match &self.bearer {
+                Bearer::Fixed => {
+                    ble.connections.set_att_timed_out(handle);
+                    self.connection.disconnect();
+                }
+                Bearer::Eatt(channel) => {
+                    self.timed_out.set(true);
+                    channel.clone().disconnect();
+                }
+            }
+        })
+        .await
+    }
+
+    // Whether a transaction on the bearer timed out, after which no more PDUs may be exchanged on it.
+    fn timed_out(&self) -> bool {
+        match &self.bearer {
+            Bearer::Fixed => self.ble.connections.att_timed_out(self.connection.handle()),
+            Bearer::Eatt(_) => self.timed_out.get(),
+        }
+    }
+
+    // Send an ATT PDU of `len` bytes, stored after room for the l2cap header.
+    async fn send(&self, mut tx: Packet, len: usize) -> Result<(), BleHostError<T::Error>> {
+        if self.timed_out() {
+            return Err(Error::Timeout.into());
+        }
+        match &self.bearer {
+            Bearer::Fixed => {
+                let mut w = WriteCursor::new(&mut tx.as_mut()[..4]);
+                w.write_hci(&L2capHeader {
+                    channel: L2CAP_CID_ATT,
+                    length: len as u16,
+                })?;
+                let pdu = &tx.as_ref()[..4 + len];
+                let mut grant = self
+                    .ble
+                    .acl(self.connection.handle(), self.ble.acl_packets(pdu.len()))
+                    .await?;
+                grant.send(pdu).await
+            }
+            Bearer::Eatt(channel) => channel.clone().send_pooled(self.ble, &tx.as_ref()[4..4 + len]).await,
+        }
+    }
+
+    // Send an ATT PDU of `len` bytes, stored after room for the l2cap header, without waiting for link credits.
+    fn try_send(&self, mut tx: Packet, len: usize) -> Result<(), BleHostError<T::Error>>
+    where
+        T: blocking::Controller,
+    {
+        if self.timed_out() {
+            return Err(Error::Timeout.into());
+        }
+        match &self.bearer {
+            Bearer::Fixed => {
+                let mut w = WriteCursor::new(&mut tx.as_mut()[..4]);
+                w.write_hci(&L2capHeader {
+                    channel: L2CAP_CID_ATT,
+                    length: len as u16,
+                })?;
+                let pdu = &tx.as_ref()[..4 + len];
+                let mut grant = self
+                    .ble
+                    .try_acl(self.connection.handle(), self.ble.acl_packets(pdu.len()))?;
+                grant.try_send(pdu)
+            }
+            Bearer::Eatt(channel) => channel.clone().try_send_pooled(self.ble, &tx.as_ref()[4..4 + len]),
+        }
+    }
+
+    // Wait for the response to a request.
+    //
+    // Notifications and indications received on an Enhanced ATT bearer in the meantime are kept for
+    // `next_notification`. On the fixed ATT channel, the connection keeps them apart from responses.
+    async fn response(&self) -> Result<Pdu, BleHostError<T::Error>> {
+        if self.timed_out() {
+            return Err(Error::Timeout.into());
+        }
+        let ble = self.ble;
+        let handle = self.connection.handle();
+        match &self.bearer {
+            Bearer::Fixed => Ok(poll_fn(|cx| ble.connections.poll_att_response(handle, cx)).await?),
+            Bearer::Eatt(channel) => loop {
+                // Another task may be receiving from the bearer, waiting for a notification
+                let received = poll_fn(|cx| match self.response.borrow_mut().take() {
+                    Some(pdu) => Poll::Ready(Some(pdu)),
+                    None if self.receiving.get() => {
+                        self.waiters.borrow_mut().register(cx.waker());
+                        Poll::Pending
+                    }
+                    None => Poll::Ready(None),
+                })
+                .await;
+                if let Some(pdu) = received {
+                    return Ok(pdu);
+                }
+
+                let _receiving = Busy::take(&self.receiving, &self.waiters).await;
+                let pdu = receive_eatt(ble, &mut channel.clone()).await?;
+                match pdu.as_ref().first() {
+                    Some(&opcode) if att::is_notification(opcode) => {
+                        queue_notification(handle, &mut self.pending.borrow_mut(), pdu);
+                    }
+                    _ => return Ok(pdu),
+                }
+            },
+        }
+    }
+
+    // Receive the next notification or indication from the bearer.
+    //
+    // With several clients on the fixed ATT channel of a connection, each is received by only one of them. A
+    // response received on an Enhanced ATT bearer is kept for the task waiting for it.
+    async fn notification(&self) -> Result<Pdu, BleHostError<T::Error>> {
+        if self.timed_out() {
+            return Err(Error::Timeout.into());
+        }
+        if let Some(pdu) = self.pending.borrow_mut().pop_front() {
+            return Ok(pdu);
+        }
+        let ble = self.ble;
+        let handle = self.connection.handle();
+        match &self.bearer {
+            Bearer::Fixed => Ok(poll_fn(|cx| ble.connections.poll_att_notification(handle, cx)).await?),
+            Bearer::Eatt(channel) => loop {
+                let pdu = receive_eatt(ble, &mut channel.clone()).await?;
+                match pdu.as_ref().first() {
+                    Some(&opcode) if att::is_notification(opcode) => return Ok(pdu),
+                    _ => {
+                        if self.response.borrow_mut().replace(pdu).is_some() {
+                            warn!("[gatt] dropping unexpected response");
+                        }
+                        self.waiters.borrow_mut().wake();
+                    }
+                }
+            },
+        }
+    }
+
+    /// Wait for the next notification or indication from the server, copying its value into the provided buffer.
+    ///
+    /// Multiple handle value notifications are split, and their values returned one at a time. Indications are
+    /// confirmed once their value is copied. Values kept for a [`Subscription`] of the client are not returned.
+    ///
+    /// The handle of the notified characteristic and the number of bytes copied are returned.
+    pub async fn next_notification(&self, dest: &mut [u8]) -> Result<(u16, usize), BleHostError<T::Error>> {
+        self.next_value_with(None, |_, value| copy_value(value, dest)).await
+    }
+
+    // Wait for the next notified or indicated value of the characteristic with handle `subscribed`, or of any
+    // characteristic without a subscription if `None`, and hand it to `f` along with its handle.
+    //
+    // Values are received one at a time, shared by the tasks waiting on the client. A value for another
+    // subscription is kept until it is received there, and a value for no subscription is discarded.
+    async fn next_value_with<R>(
+        &self,
+        subscribed: Option<u16>,
+        mut f: impl FnMut(u16, &[u8]) -> R,
+    ) -> Result<(u16, R), BleHostError<T::Error>> {
+        loop {
+            match poll_fn(|cx| self.poll_notification_head(subscribed, cx)).await {
+                NotificationHead::Take => {
+                    if let Some((handle, indication, value)) = self.take_notification_value(&mut f) {
+                        self.notification_value_taken(handle, indication).await?;
+                        return Ok((handle, value));
+                    }
+                }
+                NotificationHead::Discard => {
+                    if let Some((handle, indication, _)) = self.take_notification_value(|_, _| ()) {
+                        debug!("[gatt] discarding value of unsubscribed characteristic {}", handle);
+                        self.notification_value_taken(handle, indication).await?;
+                    }
+                }
+                NotificationHead::Receive => {
+                    let _receiving = Busy::take(&self.receiving, &self.waiters).await;
+                    let pdu = self.notification().await?;
+                    match pdu.as_ref().first() {
+                        Some(&opcode)
+                            if att::is_notification(opcode) && notification_entry(pdu.as_ref(), 1).is_some() =>
+                        {
+                            self.notifications.borrow_mut().replace((pdu, 1));
+                        }
+                        Some(&opcode) => warn!("[gatt] ignoring malformed or unexpected pdu with opcode {:x}", opcode),
+                        None => {}
+                    }
+                }
+            }
+        }
+    }
+
+    // Whether the next value kept is for the subscription of `subscribed`, or nothing is kept and the bearer is free.
+    fn poll_notification_head(&self, subscribed: Option<u16>, cx: &mut Context<'_>) -> Poll<NotificationHead> {
+        let head = self
+            .notifications
+            .borrow()
+            .as_ref()
+            .and_then(|(pdu, pos)| notification_entry(pdu.as_ref(), *pos))
+            .map(|(handle, _)| handle);
+        let is_subscribed = |handle| self.subscribed.borrow().contains(&handle);
+        match head {
+            Some(handle) if subscribed == Some(handle) => Poll::Ready(NotificationHead::Take),
+            Some(handle) if subscribed.is_none() && !is_subscribed(handle) => Poll::Ready(NotificationHead::Take),
+            Some(handle) if !is_subscribed(handle) => Poll::Ready(NotificationHead::Discard),
+            None if !self.pending.borrow().is_empty() || !self.receiving.get() => {
+                Poll::Ready(NotificationHead::Receive)
+            }
+            _ => {
+                self.waiters.borrow_mut().register(cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+
+    // Take the next value kept, handing it to `f`. Whether it was indicated is returned along with its handle.
+    fn take_notification_value<R>(&self, mut f: impl FnMut(u16, &[u8]) -> R) -> Option<(u16, bool, R)> {
+        let mut notifications = self.notifications.borrow_mut();
+        let (pdu, pos) = notifications.as_mut()?;
+        let (handle, value) = notification_entry(pdu.as_ref(), *pos)?;
+        let indication = pdu.as_ref()[0] == ATT_HANDLE_VALUE_IND;
+        let result = f(handle, &pdu.as_ref()[value.clone()]);
+        *pos = value.end;
+        if notification_entry(pdu.as_ref(), *pos).is_none() {
+            notifications.take();
+        }
+        drop(notifications);
+        self.waiters.borrow_mut().wake();
+        Some((handle, indication, result))
+    }
+
+    // Confirm a value once taken, if it was indicated.
+    //
+    // Whoever takes it, an indication of the Service Changed characteristic marks the database as changed.
+    async fn notification_value_taken(&self, handle: u16, indication: bool) -> Result<(), BleHostError<T::Error>> {
+        if !indication {
+            return Ok(());
+        }
+        if self.service_changed == Some(handle) {
+            info!("[gatt] server database changed");
+            self.database_changed.set(true);
+        }
+        let mut tx = self.ble.alloc_att()?;
+        tx.as_mut()[4] = ATT_HANDLE_VALUE_CFM;
+        self.send(tx, 1).await
+    }
+
+    /// Subscribe to notifications, or indications, of a characteristic.
+    ///
+    /// The client characteristic configuration descriptor of the characteristic is written, and values sent by
+    /// the server are received from the returned subscription. The subscription is disabled when dropped, before
+    /// the next request of the client.
+    pub async fn subscribe(
+        &self,
+        characteristic: &Characteristic,
+        indications: bool,
+    ) -> Result<Subscription<'_, 'reference, 'resources, T, MAX>, BleHostError<T::Error>> {
+        let cccd = characteristic.cccd_handle.ok_or(Error::NotSupported)?;
+        let value = if indications { 0x02 } else { 0x01 };
+        self.subscribed
+            .borrow_mut()
+            .push(characteristic.handle)
+            .map_err(|_| Error::InsufficientSpace)?;
+        if let Err(e) = self.write_cccd(cccd, value).await {
+            self.remove_subscribed(characteristic.handle);
+            return Err(e);
+        }
+        Ok(Subscription {
+            client: self,
+            handle: characteristic.handle,
+            cccd: Some(cccd),
+        })
+    }
+
+    // Write the client characteristic configuration descriptor of a characteristic.
+    async fn write_cccd(&self, cccd: u16, value: u16) -> Result<(), BleHostError<T::Error>> {
+        let mut tx = self.ble.alloc_att()?;
+        let mut w = WriteCursor::new(&mut tx.as_mut()[4..]);
+        w.write(AttReq::Write {
+            handle: cccd,
+            data: &value.to_le_bytes(),
+        })?;
+        let len = w.len();
+
+        let pdu = self.exchange(tx, len).await?;
+        match AttRsp::decode(pdu.as_ref())? {
+            AttRsp::Write => Ok(()),
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::InvalidValue.into()),
+        }
+    }
+
+    // Forget a subscription, discarding the values kept for it once no other subscription has its characteristic.
+    fn remove_subscribed(&self, handle: u16) {
+        let mut subscribed = self.subscribed.borrow_mut();
+        if let Some(i) = subscribed.iter().position(|&h| h == handle) {
+            subscribed.swap_remove(i);
+        }
+        drop(subscribed);
+        self.waiters.borrow_mut().wake();
+    }
+
+    /// Read the Database Hash of the server, which changes whenever its database does.
+    ///
+    /// Returns `None` if the server doesn't expose a Database Hash.
+    pub async fn database_hash(&mut self) -> Result<Option<[u8; 16]>, BleHostError<T::Error>> {
+        let pdu = self
+            .request(AttReq::ReadByType {
+                start: 0x0001,
+                end: 0xffff,
+                attribute_type: DATABASE_HASH_UUID16,
+            })
+            .await?;
+        match AttRsp::decode(pdu.as_ref())? {
+            AttRsp::ReadByType { mut it } => match it.next() {
+                Some(item) => {
+                    let (_handle, value) = item?;
+                    Ok(Some(value.try_into().map_err(|_| Error::InvalidValue)?))
+                }
+                None => Ok(None),
+            },
+            AttRsp::Error { code, .. } if code == att::AttErrorCode::AttributeNotFound => Ok(None),
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::InvalidValue.into()),
+        }
+    }
+
+    /// Whether the server indicated, through the Service Changed characteristic, that its database changed since it
+    /// was discovered or restored from a cache.
+    ///
+    /// The database must then be discovered again, which [`GattClient::load_database`] does.
+    pub fn database_changed(&self) -> bool {
+        self.database_changed.get()
+    }
+
+    /// Discover the services, characteristics and descriptors of the server, for caching.
+    ///
+    /// Indications of the Service Changed characteristic are enabled, so that [`GattClient::database_changed`]
+    /// reports changes of the database.
+    pub async fn discover_database<const SERVICES: usize, const CHARACTERISTICS: usize>(
+        &mut self,
+    ) -> Result<DatabaseCache<SERVICES, CHARACTERISTICS>, BleHostError<T::Error>> {
+        // The hash is read first, so that changes during discovery invalidate the cache
+        let hash = self.database_hash().await?;
+        let services: Vec<ServiceHandle, MAX> = Vec::from_slice(self.services().await?).unwrap();
+
+        let mut cache = DatabaseCache {
+            peer: self.connection.peer_identity(),
+            hash,
+            services: Vec::new(),
+            characteristics: Vec::new(),
+        };
+        for service in services.iter() {
+            cache
+                .services
+                .push(service.clone())
+                .map_err(|_| Error::InsufficientSpace)?;
+            for c in self.characteristics::<CHARACTERISTICS>(service).await? {
+                cache.characteristics.push(c).map_err(|_| Error::InsufficientSpace)?;
+            }
+        }
+
+        self.database_changed.set(false);
+        self.track_service_changed(&cache).await;
+        Ok(cache)
+    }
 
-        let mut grant = self.ble.acl(self.connection.handle(), 1).await?;
-        grant.send(w.finish()).await?;
+    /// Reuse a cached database, if it belongs to the peer and is still valid.
+    ///
+    /// A cache belongs to the peer if it was discovered on a connection with the same identity address. It is
+    /// valid if its Database Hash matches the one of the server, and no change of the database was indicated since
+    /// it was discovered. Services of a valid cache become the services of this client.
+    pub async fn restore_database<const SERVICES: usize, const CHARACTERISTICS: usize>(
+        &mut self,
+        cache: &DatabaseCache<SERVICES, CHARACTERISTICS>,
+    ) -> Result<bool, BleHostError<T::Error>> {
+        let peer = self.connection.peer_identity();
+        if self.database_changed.get() || peer.is_none() || cache.peer != peer {
+            return Ok(false);
+        }
+        // Without a Database Hash, the cache is only invalidated by Service Changed indications
+        if cache.hash.is_some() && self.database_hash().await? != cache.hash {
+            return Ok(false);
+        }
+        self.services = Vec::from_slice(&cache.services).map_err(|_| Error::InsufficientSpace)?;
+        self.track_service_changed(cache).await;
+        Ok(true)
+    }
+
+    /// Load the database of the peer from storage, discovering and storing it again if missing or no longer valid.
+    ///
+    /// The database is keyed by the identity address of the peer. If it is unknown, because the peer uses a private
+    /// address the controller did not resolve, the database is discovered without being stored. `buf` holds the
+    /// serialized database, and a database too large for it is not stored.
+    pub async fn load_database<S: DatabaseStorage, const SERVICES: usize, const CHARACTERISTICS: usize>(
+        &mut self,
+        storage: &mut S,
+        buf: &mut [u8],
+    ) -> Result<DatabaseCache<SERVICES, CHARACTERISTICS>, BleHostError<T::Error>> {
+        let Some(peer) = self.connection.peer_identity() else {
+            debug!("[gatt] identity of peer unknown, not caching its database");
+            return self.discover_database().await;
+        };
+        if let Some(len) = storage.load(&peer, buf).await {
+            match DatabaseCache::decode(&buf[..len.min(buf.len())]) {
+                Ok(cache) => {
+                    if self.restore_database(&cache).await? {
+                        return Ok(cache);
+                    }
+                    debug!("[gatt] cached database no longer valid");
+                }
+                Err(e) => warn!("[gatt] error decoding cached database: {:?}", e),
+            }
+            storage.remove(&peer).await;
+        }
 
-        let (h, pdu) = self.rx.receive().await;
-        assert_eq!(h, self.connection.handle());
-        Ok(pdu)
+        let cache = self.discover_database().await?;
+        match cache.encode(buf) {
+            Ok(len) => storage.store(&peer, &buf[..len]).await,
+            Err(e) => warn!("[gatt] unable to store database: {:?}", e),
+        }
+        Ok(cache)
+    }
+
+    // Watch the Service Changed characteristic of a database, enabling its indications.
+    async fn track_service_changed<const SERVICES: usize, const CHARACTERISTICS: usize>(
+        &mut self,
+        cache: &DatabaseCache<SERVICES, CHARACTERISTICS>,
+    ) {
+        let c = cache.characteristics.iter().find(|c| c.uuid == SERVICE_CHANGED_UUID16);
+        self.service_changed = c.map(|c| c.handle);
+        if let Some(cccd) = c.and_then(|c| c.cccd_handle) {
+            if let Err(e) = self.write_cccd(cccd, 0x02).await {
+                warn!("[gatt] error enabling service changed indications: {:?}", e);
+            }
+        }
+    }
+
+    /// Discover all primary services of the server.
+    ///
+    /// The services found replace any previously discovered, and an error is returned if there are more
+    /// services than the client can hold.
+    pub async fn services(&mut self) -> Result<&[ServiceHandle], BleHostError<T::Error>> {
+        self.services.clear();
+        let mut start: u16 = 0x0001;
+
+        loop {
+            let data = att::AttReq::ReadByGroupType {
+                start,
+                end: 0xffff,
+                group_type: PRIMARY_SERVICE_UUID16,
+            };
+
+            let pdu = self.request(data).await?;
+            let mut last = 0;
+            match AttRsp::decode(pdu.as_ref())? {
+                AttRsp::Error { code, .. } => {
+                    if code == att::AttErrorCode::AttributeNotFound {
+                        break;
+                    }
+                    return Err(Error::Att(code).into());
+                }
+                AttRsp::ReadByGroupType { mut it } => {
+                    while let Some(res) = it.next() {
+                        let (handle, end, uuid) = res?;
+                        if uuid.len() != 2 && uuid.len() != 16 {
+                            return Err(Error::InvalidValue.into());
+                        }
+                        self.services
+                            .push(ServiceHandle {
+                                start: handle,
+                                end,
+                                uuid: Uuid::from_slice(uuid),
+                            })
+                            .map_err(|_| Error::InsufficientSpace)?;
+                        last = end;
+                    }
+                }
+                _ => {
+                    return Err(Error::InvalidValue.into());
+                }
+            }
+
+            // Stop at the end of the handle range, or if the server didn't make progress
+            if last == 0xffff || last < start {
+                break;
+            }
+            start = last + 1;
+        }
+
+        Ok(&self.services[..])
     }
 
     /// Discover primary services associated with a UUID.
+    ///
+    /// The services found replace any previously discovered, and an error is returned if there are more
+    /// services than the client can hold.
     pub async fn services_by_uuid(&mut self, uuid: &Uuid) -> Result<&[ServiceHandle], BleHostError<T::Error>> {
+        self.services.clear();
         let mut start: u16 = 0x0001;
 
         loop {
@@ -222,7 +1519,7 @@ impl<'reference, 'resources, T: Controller, const MAX: usize, const ATT_MTU: usi
                                 end,
                                 uuid: uuid.clone(),
                             })
-                            .unwrap();
+                            .map_err(|_| Error::InsufficientSpace)?;
                     }
                     if end == 0xFFFF {
                         break;
@@ -235,54 +1532,176 @@ impl<'reference, 'resources, T: Controller, const MAX: usize, const ATT_MTU: usi
             }
         }
 
-        Ok(&self.services[..])
+        Ok(&self.services[..])
+    }
+
+    /// Discover the services included by a given service.
+    ///
+    /// An error is returned if the service includes more than `N` services.
+    pub async fn included_services<const N: usize>(
+        &mut self,
+        service: &ServiceHandle,
+    ) -> Result<Vec<ServiceHandle, N>, BleHostError<T::Error>> {
+        let mut found = Vec::new();
+        let mut start = service.start;
+        while start <= service.end {
+            let data = att::AttReq::ReadByType {
+                start,
+                end: service.end,
+                attribute_type: INCLUDE_SERVICE_UUID16,
+            };
+            let pdu = self.request(data).await?;
+            let first = found.len();
+            let Some((last, uuid_missing)) = decode_included_services(pdu.as_ref(), start, service.end, &mut found)?
+            else {
+                break;
+            };
+            // The response is released before reading 128-bit UUIDs, which aren't part of the declarations
+            drop(pdu);
+            if uuid_missing {
+                for i in first..found.len() {
+                    found[i].uuid = self.service_uuid(found[i].start).await?;
+                }
+            }
+
+            // Stop if the server didn't make progress
+            if last < start {
+                break;
+            }
+            start = match last.checked_add(1) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        Ok(found)
+    }
+
+    // Read the 128-bit UUID of an included service from its declaration, as it isn't part of the include.
+    async fn service_uuid(&mut self, declaration: u16) -> Result<Uuid, BleHostError<T::Error>> {
+        let pdu = self.request(AttReq::Read { handle: declaration }).await?;
+        match AttRsp::decode(pdu.as_ref())? {
+            AttRsp::Read { data } if data.len() == 16 => Ok(Uuid::from_slice(data)),
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::InvalidValue.into()),
+        }
     }
 
     /// Discover characteristics in a given service using a UUID.
+    ///
+    /// The handle of the client characteristic configuration descriptor is included, if the characteristic has one.
     pub async fn characteristic_by_uuid(
         &mut self,
         service: &ServiceHandle,
         uuid: &Uuid,
     ) -> Result<Characteristic, BleHostError<T::Error>> {
-        let mut start: u16 = service.start;
-        loop {
+        let mut found: Option<DiscoveredCharacteristic> = None;
+        self.discover_characteristics(service, |c| {
+            match found.as_mut() {
+                // The characteristic ends before the next one is declared
+                Some(found) => {
+                    found.end = c.declaration - 1;
+                    return Ok(false);
+                }
+                None if &c.uuid == uuid => found = Some(DiscoveredCharacteristic { end: service.end, ..c }),
+                None => {}
+            }
+            Ok(true)
+        })
+        .await?;
+
+        let mut c = found.ok_or(Error::NotFound)?;
+        self.discover_descriptors(&mut c).await?;
+        Ok(c.characteristic())
+    }
+
+    /// Discover all characteristics in a given service, along with their descriptors.
+    ///
+    /// An error is returned if the service has more than `N` characteristics.
+    pub async fn characteristics<const N: usize>(
+        &mut self,
+        service: &ServiceHandle,
+    ) -> Result<Vec<DiscoveredCharacteristic, N>, BleHostError<T::Error>> {
+        let mut found: Vec<DiscoveredCharacteristic, N> = Vec::new();
+        self.discover_characteristics(service, |c| {
+            // Each characteristic ends before the next one is declared
+            if let Some(last) = found.last_mut() {
+                last.end = c.declaration - 1;
+            }
+            found
+                .push(DiscoveredCharacteristic { end: service.end, ..c })
+                .map_err(|_| Error::InsufficientSpace)?;
+            Ok(true)
+        })
+        .await?;
+
+        for c in found.iter_mut() {
+            self.discover_descriptors(c).await?;
+        }
+        Ok(found)
+    }
+
+    // Discover the characteristics declared in a service, in order, handing each to `f` while it returns true.
+    //
+    // The end handles and descriptors of the characteristics are not known yet.
+    async fn discover_characteristics(
+        &mut self,
+        service: &ServiceHandle,
+        mut f: impl FnMut(DiscoveredCharacteristic) -> Result<bool, Error>,
+    ) -> Result<(), BleHostError<T::Error>> {
+        let mut start = service.start;
+        while start <= service.end {
             let data = att::AttReq::ReadByType {
                 start,
                 end: service.end,
                 attribute_type: CHARACTERISTIC_UUID16,
             };
             let pdu = self.request(data).await?;
+            // Every declaration of the response is used before requesting those after the last one
+            let Some(last) = decode_characteristics(pdu.as_ref(), start, service.end, &mut f)? else {
+                break;
+            };
+            start = match last.checked_add(1) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        Ok(())
+    }
+
+    // Discover the descriptors between the value and the end of a characteristic.
+    async fn discover_descriptors(&mut self, c: &mut DiscoveredCharacteristic) -> Result<(), BleHostError<T::Error>> {
+        let mut start = c.handle.saturating_add(1);
+        while start > c.handle && start <= c.end {
+            let data = att::AttReq::FindInformation {
+                start_handle: start,
+                end_handle: c.end,
+            };
+            let pdu = self.request(data).await?;
 
+            let mut last = start;
             match AttRsp::decode(pdu.as_ref())? {
-                AttRsp::ReadByType { mut it } => {
-                    while let Some(Ok((handle, item))) = it.next() {
-                        if item.len() < 5 {
+                AttRsp::FindInformation { mut it } => {
+                    while let Some(res) = it.next() {
+                        let (handle, uuid) = res?;
+                        if handle < start || handle > c.end {
                             return Err(Error::InvalidValue.into());
                         }
-                        let mut r = ReadCursor::new(item);
-                        let _props: u8 = r.read()?;
-                        let value_handle: u16 = r.read()?;
-                        let value_uuid: Uuid = r.read()?;
-
-                        if uuid == &value_uuid {
-                            return Ok(Characteristic {
-                                handle: value_handle,
-                                cccd_handle: None,
-                            });
+                        if uuid == CHARACTERISTIC_CCCD_UUID16 {
+                            c.cccd_handle.replace(handle);
                         }
-
-                        if handle == 0xFFFF {
-                            return Err(Error::NotFound.into());
+                        if c.descriptors.push(DiscoveredDescriptor { handle, uuid }).is_err() {
+                            warn!("[gatt] too many descriptors for characteristic {}", c.handle);
                         }
-                        start = handle + 1;
+                        last = handle;
                     }
                 }
-                AttRsp::Error { request, handle, code } => return Err(Error::Att(code).into()),
-                _ => {
-                    return Err(Error::InvalidValue.into());
-                }
+                AttRsp::Error { code, .. } if code == att::AttErrorCode::AttributeNotFound => break,
+                AttRsp::Error { code, .. } => return Err(Error::Att(code).into()),
+                _ => return Err(Error::InvalidValue.into()),
             }
+            start = last.saturating_add(1);
         }
+        Ok(())
     }
 
     /// Read a characteristic described by a handle.
@@ -310,6 +1729,84 @@ impl<'reference, 'resources, T: Controller, const MAX: usize, const ATT_MTU: usi
         }
     }
 
+    /// Read the complete value of a characteristic, however long.
+    ///
+    /// Values longer than fit in a single response are read in parts, using Read Blob requests. The number of bytes
+    /// copied into the provided buffer is returned, or [`Error::InsufficientSpace`] if the value doesn't fit.
+    pub async fn read_characteristic_long(
+        &mut self,
+        characteristic: &Characteristic,
+        dest: &mut [u8],
+    ) -> Result<usize, BleHostError<T::Error>> {
+        self.read_long(characteristic.handle, dest).await
+    }
+
+    /// Read the complete value of a descriptor, however long.
+    ///
+    /// See [`GattClient::read_characteristic_long`].
+    pub async fn read_descriptor_long(
+        &mut self,
+        descriptor: &DiscoveredDescriptor,
+        dest: &mut [u8],
+    ) -> Result<usize, BleHostError<T::Error>> {
+        self.read_long(descriptor.handle, dest).await
+    }
+
+    // Read an attribute value, continuing with Read Blob requests at increasing offsets until a response isn't full.
+    async fn read_long(&mut self, handle: u16, dest: &mut [u8]) -> Result<usize, BleHostError<T::Error>> {
+        let mut len = 0;
+        loop {
+            // A full response holds MTU - 1 bytes of the value, after the opcode
+            let full = self.mtu() - 1;
+            let req = if len == 0 {
+                AttReq::Read { handle }
+            } else {
+                // Offsets are 16 bits, and no attribute value is longer than 512 bytes
+                let offset = u16::try_from(len).map_err(|_| Error::InvalidValue)?;
+                AttReq::ReadBlob { handle, offset }
+            };
+
+            let pdu = self.request(req).await?;
+            let data = match AttRsp::decode(pdu.as_ref())? {
+                AttRsp::Read { data } if len == 0 => data,
+                AttRsp::ReadBlob { data } if len > 0 => data,
+                // The value was exactly as long as a full response
+                AttRsp::Error { code, .. } if len > 0 && code == att::AttErrorCode::AttributeNotLong => return Ok(len),
+                AttRsp::Error { code, .. } => return Err(Error::Att(code).into()),
+                _ => return Err(Error::InvalidValue.into()),
+            };
+
+            let end = len + data.len();
+            if end > dest.len() {
+                return Err(Error::InsufficientSpace.into());
+            }
+            dest[len..end].copy_from_slice(data);
+            len = end;
+
+            if data.len() < full {
+                return Ok(len);
+            }
+        }
+    }
+
+    /// Read a characteristic described by a handle as a typed value.
+    pub async fn read_characteristic_value<V: GattValue>(
+        &mut self,
+        characteristic: &Characteristic,
+    ) -> Result<V, BleHostError<T::Error>> {
+        let data = att::AttReq::Read {
+            handle: characteristic.handle,
+        };
+
+        let pdu = self.request(data).await?;
+
+        match AttRsp::decode(pdu.as_ref())? {
+            AttRsp::Read { data } => Ok(V::from_gatt(data)?),
+            AttRsp::Error { request, handle, code } => Err(Error::Att(code).into()),
+            _ => Err(Error::InvalidValue.into()),
+        }
+    }
+
     /// Read a characteristic described by a UUID.
     ///
     /// The number of bytes copied into the provided buffer is returned.
@@ -342,6 +1839,65 @@ impl<'reference, 'resources, T: Controller, const MAX: usize, const ATT_MTU: usi
         }
     }
 
+    /// Read the values of several characteristics at once, using a Read Multiple request.
+    ///
+    /// The values are copied into the provided buffer back to back, and the number of bytes copied is returned.
+    /// As the values aren't delimited, all but the last should have a known, fixed length. At least two
+    /// characteristics must be given, and an error is returned if the values don't fit the buffer.
+    pub async fn read_multiple(
+        &mut self,
+        characteristics: &[Characteristic],
+        dest: &mut [u8],
+    ) -> Result<usize, BleHostError<T::Error>> {
+        let (tx, len) = self.read_multiple_request(ATT_READ_MULTIPLE_REQ, characteristics)?;
+        let pdu = self.transaction(tx, len).await?;
+        Ok(read_multiple_values(pdu.as_ref(), dest)?)
+    }
+
+    /// Read the values of several characteristics at once, using a Read Multiple Variable Length request.
+    ///
+    /// The values are copied into the provided buffer back to back, with the length of each value stored in
+    /// `lens`. The number of values read is returned, which is less than requested if the response didn't fit the
+    /// MTU, in which case the last value may be truncated. At least two characteristics must be given.
+    pub async fn read_multiple_variable(
+        &mut self,
+        characteristics: &[Characteristic],
+        dest: &mut [u8],
+        lens: &mut [usize],
+    ) -> Result<usize, BleHostError<T::Error>> {
+        if lens.len() < characteristics.len() {
+            return Err(Error::InsufficientSpace.into());
+        }
+        let (tx, len) = self.read_multiple_request(ATT_READ_MULTIPLE_VARIABLE_REQ, characteristics)?;
+        let pdu = self.transaction(tx, len).await?;
+        Ok(read_multiple_variable_values(
+            pdu.as_ref(),
+            dest,
+            &mut lens[..characteristics.len()],
+        )?)
+    }
+
+    // Encode a Read Multiple or Read Multiple Variable Length request for the characteristic handles.
+    fn read_multiple_request(
+        &mut self,
+        opcode: u8,
+        characteristics: &[Characteristic],
+    ) -> Result<(Packet, usize), BleHostError<T::Error>> {
+        if characteristics.len() < 2 {
+            return Err(Error::InvalidValue.into());
+        }
+        // Requests larger than the MTU are rejected by the cursor
+        let mtu = self.mtu();
+        let mut tx = self.ble.alloc_att()?;
+        let mut w = WriteCursor::new(&mut tx.as_mut()[4..4 + mtu]);
+        w.write(opcode)?;
+        for c in characteristics {
+            w.write(c.handle)?;
+        }
+        let len = w.len();
+        Ok((tx, len))
+    }
+
     /// Write to a characteristic described by a handle.
     pub async fn write_characteristic(
         &mut self,
@@ -360,4 +1916,747 @@ impl<'reference, 'resources, T: Controller, const MAX: usize, const ATT_MTU: usi
             _ => Err(Error::InvalidValue.into()),
         }
     }
+
+    /// Write to a characteristic using a Write Command, which the server doesn't acknowledge.
+    ///
+    /// Waits until the link has credits for sending, rather than for a response, allowing values to be streamed.
+    pub async fn write_without_response(
+        &mut self,
+        characteristic: &Characteristic,
+        data: &[u8],
+    ) -> Result<(), BleHostError<T::Error>> {
+        let (tx, len) = self.write_command(characteristic, data)?;
+        self.send(tx, len).await
+    }
+
+    /// Write to a characteristic using a Write Command, which the server doesn't acknowledge.
+    ///
+    /// If the link has no credits or the controller buffers are full, returns Error::Busy.
+    pub fn try_write_without_response(
+        &mut self,
+        characteristic: &Characteristic,
+        data: &[u8],
+    ) -> Result<(), BleHostError<T::Error>>
+    where
+        T: blocking::Controller,
+    {
+        let (tx, len) = self.write_command(characteristic, data)?;
+        self.try_send(tx, len)
+    }
+
+    // Encode a Write Command, which must fit the MTU.
+    fn write_command(
+        &mut self,
+        characteristic: &Characteristic,
+        data: &[u8],
+    ) -> Result<(Packet, usize), BleHostError<T::Error>> {
+        let mtu = self.mtu();
+        let mut tx = self.ble.alloc_att()?;
+        let mut w = WriteCursor::new(&mut tx.as_mut()[4..4 + mtu]);
+        w.write(AttReq::WriteCmd {
+            handle: characteristic.handle,
+            data,
+        })?;
+        let len = w.len();
+        Ok((tx, len))
+    }
+
+    /// Write a value of any length to a characteristic, using Prepare Write and Execute Write requests.
+    ///
+    /// The value is split in parts fitting the MTU, which the server applies at once when all are queued.
+    pub async fn write_characteristic_long(
+        &mut self,
+        characteristic: &Characteristic,
+        data: &[u8],
+    ) -> Result<(), BleHostError<T::Error>> {
+        if let Err(e) = self.prepare_write(characteristic.handle, data, false).await {
+            if let Err(e) = self.execute_write(false).await {
+                warn!("[gatt] error cancelling long write: {:?}", e);
+            }
+            return Err(e);
+        }
+        self.execute_write(true).await
+    }
+
+    /// Start a reliable write session, to write one or more characteristics at once with verification.
+    pub fn reliable_write(&mut self) -> ReliableWrite<'_, 'reference, 'resources, T, MAX> {
+        ReliableWrite {
+            client: self,
+            prepared: false,
+        }
+    }
+
+    // Queue a value on the server in parts, optionally checking that each part is echoed as sent.
+    async fn prepare_write(&mut self, handle: u16, data: &[u8], verify: bool) -> Result<(), BleHostError<T::Error>> {
+        // Each part follows the opcode, handle and offset
+        let part = self.mtu() - 5;
+        let mut offset = 0;
+        for value in data.chunks(part) {
+            let req_offset = u16::try_from(offset).map_err(|_| Error::InsufficientSpace)?;
+            let pdu = self
+                .request(AttReq::PrepareWrite {
+                    handle,
+                    offset: req_offset,
+                    value,
+                })
+                .await?;
+            match AttRsp::decode(pdu.as_ref())? {
+                AttRsp::PrepareWrite {
+                    handle: h,
+                    offset: o,
+                    value: v,
+                } => {
+                    if verify && (h != handle || o != req_offset || v != value) {
+                        warn!("[gatt] prepared write of {} not echoed as sent", handle);
+                        return Err(Error::InvalidValue.into());
+                    }
+                }
+                AttRsp::Error { code, .. } => return Err(Error::Att(code).into()),
+                _ => return Err(Error::InvalidValue.into()),
+            }
+            offset += value.len();
+        }
+        Ok(())
+    }
+
+    // Write, or discard, all values queued on the server.
+    async fn execute_write(&mut self, commit: bool) -> Result<(), BleHostError<T::Error>> {
+        // Sent as is, as this also cancels dropped reliable writes before a transaction
+        let mut tx = self.ble.alloc_att()?;
+        let mut w = WriteCursor::new(&mut tx.as_mut()[4..]);
+        w.write(AttReq::ExecuteWrite { flags: commit as u8 })?;
+        let len = w.len();
+
+        let pdu = self.exchange(tx, len).await?;
+        match AttRsp::decode(pdu.as_ref())? {
+            AttRsp::ExecuteWrite => Ok(()),
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::InvalidValue.into()),
+        }
+    }
+
+    /// Write a typed value to a characteristic described by a handle.
+    pub async fn write_characteristic_value<V: GattValue>(
+        &mut self,
+        handle: &Characteristic,
+        value: &V,
+    ) -> Result<(), BleHostError<T::Error>> {
+        // Encode the value in place, after the l2cap header, opcode and handle
+        let mtu = self.mtu();
+        let mut tx = self.ble.alloc_att()?;
+        let (head, body) = tx.as_mut()[4..4 + mtu].split_at_mut(3);
+        let len = value.to_gatt(body)?;
+
+        let mut w = WriteCursor::new(head);
+        w.write(ATT_WRITE_REQ)?;
+        w.write(handle.handle)?;
+
+        let pdu = self.transaction(tx, 3 + len).await?;
+        match AttRsp::decode(pdu.as_ref())? {
+            AttRsp::Write => Ok(()),
+            AttRsp::Error { request, handle, code } => Err(Error::Att(code).into()),
+            _ => Err(Error::InvalidValue.into()),
+        }
+    }
+}
+
+// Wait for the response to a transaction, unless `expired` completes first. The bearer is then closed by `close`,
+// and the transaction fails with a timeout.
+async fn within_timeout<R, E: From<Error>>(
+    response: impl Future<Output = Result<R, E>>,
+    expired: impl Future,
+    close: impl FnOnce(),
+) -> Result<R, E> {
+    match select(response, expired).await {
+        Either::First(res) => res,
+        Either::Second(_) => {
+            close();
+            Err(Error::Timeout.into())
+        }
+    }
+}
+
+// Receive the next PDU from the server on an Enhanced ATT bearer.
+async fn receive_eatt<T: Controller>(
+    ble: &BleHost<'_, T>,
+    channel: &mut L2capChannel<'_>,
+) -> Result<Pdu, BleHostError<T::Error>> {
+    let mut rx = ble.alloc_att()?;
+    let len = channel.receive(ble, rx.as_mut()).await?;
+    Ok(Pdu::new(rx, len))
+}
+
+// Decode a Read By Type response for include declarations between `start` and `end`, appending the included
+// services to `found`. The handle of the last declaration is returned, along with whether the UUIDs are missing
+// from the declarations, which is the case for 128-bit UUIDs. `None` is returned once no declaration is left.
+fn decode_included_services<const N: usize>(
+    pdu: &[u8],
+    start: u16,
+    end: u16,
+    found: &mut Vec<ServiceHandle, N>,
+) -> Result<Option<(u16, bool)>, Error> {
+    let mut it = match AttRsp::decode(pdu)? {
+        AttRsp::ReadByType { it } => it,
+        AttRsp::Error { code, .. } if code == att::AttErrorCode::AttributeNotFound => return Ok(None),
+        AttRsp::Error { code, .. } => return Err(Error::Att(code)),
+        _ => return Err(Error::InvalidValue),
+    };
+
+    let mut last = 0;
+    let mut uuid_missing = false;
+    while let Some(item) = it.next() {
+        let (handle, item) = item?;
+        if handle < start || handle > end {
+            return Err(Error::InvalidValue);
+        }
+        let mut r = ReadCursor::new(item);
+        let included_start: u16 = r.read()?;
+        let included_end: u16 = r.read()?;
+        let uuid = match r.remaining() {
+            [] => {
+                uuid_missing = true;
+                Uuid::new_short(0)
+            }
+            uuid if uuid.len() == 2 => Uuid::from_slice(uuid),
+            _ => return Err(Error::InvalidValue),
+        };
+        found
+            .push(ServiceHandle {
+                start: included_start,
+                end: included_end,
+                uuid,
+            })
+            .map_err(|_| Error::InsufficientSpace)?;
+        last = handle;
+    }
+    Ok(Some((last, uuid_missing)))
+}
+
+// Decode the characteristic declarations of a Read By Type response, handing each to `f` while it returns true.
+//
+// The handle of the last declaration is returned, or `None` if no declaration is left or `f` wants no more.
+fn decode_characteristics(
+    pdu: &[u8],
+    start: u16,
+    end: u16,
+    mut f: impl FnMut(DiscoveredCharacteristic) -> Result<bool, Error>,
+) -> Result<Option<u16>, Error> {
+    let mut it = match AttRsp::decode(pdu)? {
+        AttRsp::ReadByType { it } => it,
+        AttRsp::Error { code, .. } if code == att::AttErrorCode::AttributeNotFound => return Ok(None),
+        AttRsp::Error { code, .. } => return Err(Error::Att(code)),
+        _ => return Err(Error::InvalidValue),
+    };
+
+    let mut last: Option<u16> = None;
+    while let Some(item) = it.next() {
+        let (declaration, item) = item?;
+        let mut r = ReadCursor::new(item);
+        let props: u8 = r.read()?;
+        let handle: u16 = r.read()?;
+        let uuid = r.remaining();
+        // Declarations are in increasing order within the range, each followed by the value it declares
+        let ordered = last.map_or(declaration >= start, |last| declaration > last);
+        if (uuid.len() != 2 && uuid.len() != 16) || !ordered || declaration > end || handle <= declaration {
+            return Err(Error::InvalidValue);
+        }
+        last = Some(declaration);
+
+        let more = f(DiscoveredCharacteristic {
+            declaration,
+            handle,
+            end: handle,
+            props: CharacteristicProps::from(props),
+            uuid: Uuid::from_slice(uuid),
+            cccd_handle: None,
+            descriptors: Vec::new(),
+        })?;
+        if !more {
+            return Ok(None);
+        }
+    }
+    Ok(last)
+}
+
+// Copy the values of a Read Multiple response into `dest`, returning the number of bytes copied.
+fn read_multiple_values(pdu: &[u8], dest: &mut [u8]) -> Result<usize, Error> {
+    match AttRsp::decode(pdu)? {
+        // As the values aren't delimited, a truncated copy can't be told apart from shorter values
+        AttRsp::ReadMultiple { data } if data.len() > dest.len() => Err(Error::InsufficientSpace),
+        AttRsp::ReadMultiple { data } => Ok(copy_value(data, dest)),
+        AttRsp::Error { code, .. } => Err(Error::Att(code)),
+        _ => Err(Error::InvalidValue),
+    }
+}
+
+// Copy the values of a Read Multiple Variable Length response back to back into `dest`, storing the length of each
+// in `lens`, which holds one entry per requested value. The number of values is returned.
+fn read_multiple_variable_values(pdu: &[u8], dest: &mut [u8], lens: &mut [usize]) -> Result<usize, Error> {
+    match AttRsp::decode(pdu)? {
+        AttRsp::ReadMultipleVariable { mut it } => {
+            let mut count = 0;
+            let mut offset = 0;
+            while let Some(value) = it.next() {
+                let value = value?;
+                if count == lens.len() {
+                    return Err(Error::InvalidValue);
+                }
+                let end = offset + value.len();
+                if end > dest.len() {
+                    return Err(Error::InsufficientSpace);
+                }
+                dest[offset..end].copy_from_slice(value);
+                lens[count] = value.len();
+                offset = end;
+                count += 1;
+            }
+            Ok(count)
+        }
+        AttRsp::Error { code, .. } => Err(Error::Att(code)),
+        _ => Err(Error::InvalidValue),
+    }
+}
+
+// Handle and value range of the entry at `pos` of a notification or indication PDU, if any is left.
+fn notification_entry(pdu: &[u8], pos: usize) -> Option<(u16, Range<usize>)> {
+    let mut r = ReadCursor::new(pdu.get(pos..)?);
+    match *pdu.first()? {
+        ATT_HANDLE_VALUE_NTF | ATT_HANDLE_VALUE_IND if pos == 1 => {
+            let handle: u16 = r.read().ok()?;
+            Some((handle, pos + 2..pdu.len()))
+        }
+        ATT_MULTIPLE_HANDLE_VALUE_NTF if r.available() > 0 => {
+            let handle: u16 = r.read().ok()?;
+            let len: u16 = r.read().ok()?;
+            let start = pos + 4;
+            let end = start + len as usize;
+            (end <= pdu.len()).then_some((handle, start..end))
+        }
+        _ => None,
+    }
+}
+
+// Write a Multiple Handle Value Notification of as many of the next `values` as fit, returning how many were written.
+fn batch_notification_values<'a, 'v: 'a>(
+    data: &mut WriteCursor<'_>,
+    values: &mut Peekable<impl Iterator<Item = &'a (Characteristic, &'v [u8])>>,
+) -> Result<usize, Error> {
+    data.write(ATT_MULTIPLE_HANDLE_VALUE_NTF)?;
+    let mut count = 0;
+    while let Some((handle, value)) = values.peek() {
+        if data.available() < 4 + value.len() {
+            break;
+        }
+        data.write(handle.handle)?;
+        data.write(value.len() as u16)?;
+        data.append(value)?;
+        count += 1;
+        values.next();
+    }
+    Ok(count)
+}
+
+fn copy_value(value: &[u8], dest: &mut [u8]) -> usize {
+    let to_copy = value.len().min(dest.len());
+    dest[..to_copy].copy_from_slice(&value[..to_copy]);
+    to_copy
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::{pending, ready};
+
+    use embassy_futures::block_on;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use super::*;
+    use crate::attribute::{AttributeTable, Service};
+
+    #[test]
+    fn transaction_timeout() {
+        type E = BleHostError<()>;
+
+        // Without a response before the timeout expires, the bearer is closed and a timeout error returned
+        let closed = Cell::new(false);
+        let res = block_on(within_timeout(pending::<Result<u8, E>>(), ready(()), || {
+            closed.set(true)
+        }));
+        assert!(matches!(res, Err(BleHostError::BleHost(Error::Timeout))));
+        assert!(closed.get());
+
+        // A response in time is returned as it is, leaving the bearer open
+        let closed = Cell::new(false);
+        let res = block_on(within_timeout(ready(Ok::<u8, E>(7)), pending::<()>(), || {
+            closed.set(true)
+        }));
+        assert!(matches!(res, Ok(7)));
+        let res = block_on(within_timeout(
+            ready(Err::<u8, E>(Error::Disconnected.into())),
+            pending::<()>(),
+            || closed.set(true),
+        ));
+        assert!(matches!(res, Err(BleHostError::BleHost(Error::Disconnected))));
+        assert!(!closed.get());
+    }
+
+    #[test]
+    fn database_cache_roundtrip() {
+        let mut characteristics = Vec::new();
+        let mut descriptors = Vec::new();
+        unwrap!(descriptors.push(DiscoveredDescriptor {
+            handle: 5,
+            uuid: Uuid::new_short(0x2901),
+        }));
+        unwrap!(characteristics.push(DiscoveredCharacteristic {
+            declaration: 2,
+            handle: 3,
+            end: 5,
+            props: CharacteristicProps::from(&[CharacteristicProp::Read, CharacteristicProp::Notify][..]),
+            uuid: Uuid::new_long([7; 16]),
+            cccd_handle: Some(4),
+            descriptors,
+        }));
+        unwrap!(characteristics.push(DiscoveredCharacteristic {
+            declaration: 7,
+            handle: 8,
+            end: 8,
+            props: CharacteristicProps::from(&[CharacteristicProp::Indicate][..]),
+            uuid: SERVICE_CHANGED_UUID16,
+            cccd_handle: None,
+            descriptors: Vec::new(),
+        }));
+        let cache: DatabaseCache<2, 4> = DatabaseCache {
+            peer: Some(Address::random([1, 2, 3, 4, 5, 0xc6])),
+            hash: Some([9; 16]),
+            services: unwrap!(Vec::from_slice(&[
+                ServiceHandle {
+                    start: 1,
+                    end: 5,
+                    uuid: Uuid::new_short(0x180f),
+                },
+                ServiceHandle {
+                    start: 6,
+                    end: 8,
+                    uuid: Uuid::new_short(0x1801),
+                },
+            ])),
+            characteristics,
+        };
+
+        let mut buf = [0; 128];
+        let len = unwrap!(cache.encode(&mut buf));
+        let decoded: DatabaseCache<2, 4> = unwrap!(DatabaseCache::decode(&buf[..len]));
+        assert_eq!(decoded.peer(), cache.peer());
+        assert_eq!(decoded.hash(), Some(&[9; 16]));
+        assert_eq!(decoded.services(), cache.services());
+        assert_eq!(decoded.characteristics, cache.characteristics);
+
+        let service = &decoded.services()[1];
+        assert_eq!(decoded.characteristics(service).count(), 1);
+        assert!(decoded
+            .characteristic_by_uuid(service, &SERVICE_CHANGED_UUID16)
+            .is_some());
+
+        // Truncated input and too small capacities are rejected
+        assert!(DatabaseCache::<2, 4>::decode(&buf[..len - 1]).is_err());
+        assert!(DatabaseCache::<1, 4>::decode(&buf[..len]).is_err());
+        assert!(cache.encode(&mut buf[..len - 1]).is_err());
+
+        // A database discovered without knowing the identity of the peer is kept without it
+        let cache = DatabaseCache { peer: None, ..cache };
+        let len = unwrap!(cache.encode(&mut buf));
+        let decoded: DatabaseCache<2, 4> = unwrap!(DatabaseCache::decode(&buf[..len]));
+        assert_eq!(decoded.peer(), None);
+        assert_eq!(decoded.services(), cache.services());
+    }
+
+    #[test]
+    fn multiple_notifications() {
+        let level = Characteristic {
+            handle: 3,
+            cccd_handle: Some(4),
+        };
+        let alert = Characteristic {
+            handle: 6,
+            cccd_handle: Some(7),
+        };
+        let name = Characteristic {
+            handle: 9,
+            cccd_handle: Some(10),
+        };
+        let values: [(Characteristic, &[u8]); 3] = [(level, &[1, 2]), (alert, &[3]), (name, &[4, 5, 6, 7])];
+        let mut it = values.iter().peekable();
+
+        // As many values as fit are batched, the others are left for the next PDU
+        let mut pdu = [0; 13];
+        let mut w = WriteCursor::new(&mut pdu);
+        assert_eq!(unwrap!(batch_notification_values(&mut w, &mut it)), 2);
+        let len = w.len();
+        assert_eq!(
+            &pdu[..len],
+            &[0x23, 0x03, 0x00, 0x02, 0x00, 1, 2, 0x06, 0x00, 0x01, 0x00, 3]
+        );
+
+        // A value that can't be batched is left to be sent on its own
+        let mut small = [0; 8];
+        let mut w = WriteCursor::new(&mut small);
+        assert_eq!(unwrap!(batch_notification_values(&mut w, &mut it)), 0);
+        assert!(matches!(it.next(), Some((c, _)) if c.handle == 9));
+
+        // The client splits the batched values apart
+        let pdu = &pdu[..len];
+        assert_eq!(notification_entry(pdu, 1), Some((3, 5..7)));
+        assert_eq!(notification_entry(pdu, 7), Some((6, 11..12)));
+        assert_eq!(notification_entry(pdu, 12), None);
+
+        // A single notification or indication holds one value, up to the end of the PDU
+        assert_eq!(notification_entry(&[0x1b, 0x03, 0x00, 1, 2], 1), Some((3, 3..5)));
+        assert_eq!(notification_entry(&[0x1b, 0x03, 0x00, 1, 2], 5), None);
+        assert_eq!(notification_entry(&[0x1d, 0x03, 0x00], 1), Some((3, 3..3)));
+
+        // Truncated entries are rejected
+        assert_eq!(notification_entry(&[0x23, 0x03, 0x00, 0x03, 0x00, 1, 2], 1), None);
+        assert_eq!(notification_entry(&[0x23, 0x03, 0x00, 0x01], 1), None);
+        assert_eq!(notification_entry(&[0x1b, 0x03], 1), None);
+    }
+
+    #[test]
+    fn read_multiple_from_server() {
+        // Handles: service 1, characteristic declarations 2 and 4, values 3 and 5
+        let mut level = [0x55];
+        let mut name = [0; 8];
+        let mut table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(0x180f));
+        service
+            .add_characteristic(0x2a19, &[CharacteristicProp::Read], &mut level)
+            .build();
+        service
+            .add_characteristic_variable(0x2a00, &[CharacteristicProp::Read], &mut name, 3)
+            .build();
+        drop(service);
+        let server = AttributeServer::new(&table);
+        let conn = ConnHandle::new(1);
+
+        // The client decodes what the server produces, with the variable length values split apart
+        let mut rsp = [0; 23];
+        let req = AttReq::ReadMultiple { handles: &[3, 0, 5, 0] };
+        let len = unwrap!(unwrap!(server.process(conn, &req, &mut rsp)));
+        let mut dest = [0; 8];
+        assert_eq!(unwrap!(read_multiple_values(&rsp[..len], &mut dest)), 4);
+        assert_eq!(&dest[..4], &[0x55, 0, 0, 0]);
+        assert!(matches!(
+            read_multiple_values(&rsp[..len], &mut dest[..3]),
+            Err(Error::InsufficientSpace)
+        ));
+
+        let req = AttReq::ReadMultipleVariable { handles: &[3, 0, 5, 0] };
+        let len = unwrap!(unwrap!(server.process(conn, &req, &mut rsp)));
+        let mut lens = [0; 2];
+        assert_eq!(
+            unwrap!(read_multiple_variable_values(&rsp[..len], &mut dest, &mut lens)),
+            2
+        );
+        assert_eq!(lens, [1, 3]);
+        assert_eq!(&dest[..4], &[0x55, 0, 0, 0]);
+
+        // More values than requested, values not fitting the buffer and errors are reported
+        let mut one = [0; 1];
+        assert!(matches!(
+            read_multiple_variable_values(&rsp[..len], &mut dest, &mut one),
+            Err(Error::InvalidValue)
+        ));
+        assert!(matches!(
+            read_multiple_variable_values(&rsp[..len], &mut dest[..2], &mut lens),
+            Err(Error::InsufficientSpace)
+        ));
+        let error = [0x01, 0x20, 0x05, 0x00, 0x02];
+        assert!(matches!(
+            read_multiple_variable_values(&error, &mut dest, &mut lens),
+            Err(Error::Att(att::AttErrorCode::ReadNotPermitted))
+        ));
+    }
+
+    #[test]
+    fn characteristics_decoded() {
+        // Declarations 2, 5 and 8, each followed by its value
+        let rsp = [
+            0x09, 0x07, 0x02, 0x00, 0x02, 0x03, 0x00, 0x19, 0x2a, 0x05, 0x00, 0x10, 0x06, 0x00, 0x29, 0x2a, 0x08, 0x00,
+            0x0a, 0x09, 0x00, 0x00, 0x2a,
+        ];
+
+        // Every declaration of a response is decoded
+        let mut found: Vec<DiscoveredCharacteristic, 4> = Vec::new();
+        let last = unwrap!(decode_characteristics(&rsp, 1, 9, |c| {
+            unwrap!(found.push(c));
+            Ok(true)
+        }));
+        assert_eq!(last, Some(8));
+        let handles: Vec<(u16, u16), 4> = found.iter().map(|c| (c.declaration, c.handle)).collect();
+        assert_eq!(handles.as_slice(), &[(2, 3), (5, 6), (8, 9)]);
+        assert_eq!(found[1].uuid, Uuid::new_short(0x2a29));
+        assert!(found[1].props.any(&[CharacteristicProp::Notify]));
+
+        // Decoding stops when no more declarations are wanted
+        let mut count = 0;
+        let last = unwrap!(decode_characteristics(&rsp, 1, 9, |_| {
+            count += 1;
+            Ok(count < 2)
+        }));
+        assert_eq!(last, None);
+        assert_eq!(count, 2);
+
+        // No declaration is left
+        let not_found = [0x01, 0x08, 0x0a, 0x00, 0x0a];
+        assert_eq!(unwrap!(decode_characteristics(&not_found, 10, 12, |_| Ok(true))), None);
+
+        // Declarations out of the requested range or order are rejected
+        assert!(decode_characteristics(&rsp, 3, 9, |_| Ok(true)).is_err());
+        assert!(decode_characteristics(&rsp, 1, 7, |_| Ok(true)).is_err());
+        let unordered = [
+            0x09, 0x07, 0x05, 0x00, 0x10, 0x06, 0x00, 0x29, 0x2a, 0x02, 0x00, 0x02, 0x03, 0x00, 0x19, 0x2a,
+        ];
+        assert!(decode_characteristics(&unordered, 1, 9, |_| Ok(true)).is_err());
+    }
+
+    #[test]
+    fn included_services_decoded() {
+        let mut found: Vec<ServiceHandle, 2> = Vec::new();
+
+        // Declarations 2 and 3 include services with a 16-bit UUID
+        let rsp = [
+            0x09, 0x08, 0x02, 0x00, 0x10, 0x00, 0x12, 0x00, 0x0f, 0x18, 0x03, 0x00, 0x20, 0x00, 0x21, 0x00, 0x0a, 0x18,
+        ];
+        assert_eq!(
+            unwrap!(decode_included_services(&rsp, 1, 9, &mut found)),
+            Some((3, false))
+        );
+        assert_eq!(
+            found.as_slice(),
+            &[
+                ServiceHandle {
+                    start: 0x10,
+                    end: 0x12,
+                    uuid: Uuid::new_short(0x180f)
+                },
+                ServiceHandle {
+                    start: 0x20,
+                    end: 0x21,
+                    uuid: Uuid::new_short(0x180a)
+                },
+            ]
+        );
+
+        // A 128-bit UUID is left to be read from the service declaration
+        found.clear();
+        let rsp = [0x09, 0x06, 0x04, 0x00, 0x30, 0x00, 0x38, 0x00];
+        assert_eq!(
+            unwrap!(decode_included_services(&rsp, 4, 9, &mut found)),
+            Some((4, true))
+        );
+        assert_eq!(found[0].start, 0x30);
+
+        // No declaration is left, or one is out of the requested range, or there are too many
+        let not_found = [0x01, 0x08, 0x05, 0x00, 0x0a];
+        assert_eq!(unwrap!(decode_included_services(&not_found, 5, 9, &mut found)), None);
+        assert!(decode_included_services(&rsp, 5, 9, &mut found).is_err());
+        unwrap!(decode_included_services(&rsp, 4, 9, &mut found));
+        assert!(matches!(
+            decode_included_services(&rsp, 4, 9, &mut found),
+            Err(Error::InsufficientSpace)
+        ));
+    }
+
+    #[cfg(feature = "derive")]
+    #[crate::gatt_service(uuid = "180f")]
+    struct TestService {
+        #[characteristic(uuid = "2a19", read, notify)]
+        level: u8,
+        #[characteristic(uuid = "2a06", write_without_response)]
+        alert: u8,
+        #[characteristic(uuid = "2a00", read, write)]
+        name: heapless::String<8>,
+        #[characteristic(uuid = "2a05", indicate)]
+        changed: [u8; 4],
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn gatt_service_macro() {
+        use bt_hci::param::{AddrKind, LeConnRole};
+
+        use crate::connection_manager::{ConnectionManager, ConnectionStorage};
+
+        let mut table: AttributeTable<'_, NoopRawMutex, 16> = AttributeTable::new();
+        let mut storage = TestServiceStorage::new();
+        let service = TestService::new(&mut table, &mut storage);
+
+        // Handles: service 1, then declaration and value of each characteristic, with a CCCD after those subscribable
+        assert_eq!(service.handle().handle, 1);
+        assert_eq!(service.level().handle(), 3);
+        assert_eq!(service.level().cccd_handle(), Some(4));
+        assert_eq!(service.alert().handle(), 6);
+        assert_eq!(service.name().handle(), 8);
+        assert_eq!(service.changed().handle(), 10);
+        assert_eq!(service.changed().cccd_handle(), Some(11));
+
+        let mut connections = [ConnectionStorage::<1>::DISCONNECTED; 1];
+        let mgr = ConnectionManager::new(&mut connections[..], 23);
+        unwrap!(mgr.connect(
+            ConnHandle::new(0),
+            AddrKind::RANDOM,
+            BdAddr::new([1; 6]),
+            LeConnRole::Peripheral
+        ));
+        let Poll::Ready(connection) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+
+        // Events are mapped according to the properties of the characteristic
+        let read = |handle| GattEvent::Read {
+            connection: connection.clone(),
+            handle,
+        };
+        let write = |handle| GattEvent::Write {
+            connection: connection.clone(),
+            handle,
+        };
+        assert!(matches!(
+            service.event(&read(service.level())),
+            Some(TestServiceEvent::LevelRead { .. })
+        ));
+        assert!(service.event(&write(service.level())).is_none());
+        assert!(matches!(
+            service.event(&write(service.alert())),
+            Some(TestServiceEvent::AlertWrite { .. })
+        ));
+        assert!(service.event(&read(service.alert())).is_none());
+        assert!(matches!(
+            service.event(&read(service.name())),
+            Some(TestServiceEvent::NameRead { .. })
+        ));
+        assert!(matches!(
+            service.event(&write(service.name())),
+            Some(TestServiceEvent::NameWrite { .. })
+        ));
+        assert!(service.event(&read(service.changed())).is_none());
+        assert!(service.event(&write(service.changed())).is_none());
+    }
+
+    // Checks that the client proxy provides the methods matching the properties of each characteristic.
+    #[cfg(feature = "derive")]
+    #[allow(unused)]
+    async fn gatt_service_client<T: Controller>(
+        mut client: GattClient<'_, '_, T, 4>,
+    ) -> Result<(), BleHostError<T::Error>> {
+        let service = TestServiceClient::discover(&mut client).await?;
+        let level: u8 = service.read_level(&mut client).await?;
+        service.write_alert(&mut client, &1).await?;
+        let name: heapless::String<8> = service.read_name(&mut client).await?;
+        service.write_name(&mut client, &name).await?;
+        let mut changed = service.subscribe_changed(&client).await?;
+        let changed: [u8; 4] = changed.next_value().await?;
+        let mut levels = service.subscribe_level(&client).await?;
+        let level: u8 = levels.next_value().await?;
+        Ok(())
+    }
 }
@@ -1,18 +1,18 @@
 //! BleHost
 //!
 //! The host module contains the main entry point for the TrouBLE host.
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::future::poll_fn;
 use core::mem::MaybeUninit;
 use core::task::Poll;
 
 use bt_hci::cmd::controller_baseband::{HostBufferSize, Reset, SetEventMask};
 use bt_hci::cmd::le::{
-    LeAddDeviceToFilterAcceptList, LeClearAdvSets, LeClearFilterAcceptList, LeCreateConn, LeCreateConnCancel,
-    LeExtCreateConn, LeReadBufferSize, LeReadNumberOfSupportedAdvSets, LeSetAdvData, LeSetAdvEnable, LeSetAdvParams,
-    LeSetAdvSetRandomAddr, LeSetEventMask, LeSetExtAdvData, LeSetExtAdvEnable, LeSetExtAdvParams, LeSetExtScanEnable,
-    LeSetExtScanParams, LeSetExtScanResponseData, LeSetRandomAddr, LeSetScanEnable, LeSetScanParams,
-    LeSetScanResponseData,
+    LeAddDeviceToFilterAcceptList, LeClearAdvSets, LeClearFilterAcceptList, LeConnUpdate, LeCreateConn,
+    LeCreateConnCancel, LeExtCreateConn, LeReadBufferSize, LeReadNumberOfSupportedAdvSets, LeSetAdvData,
+    LeSetAdvEnable, LeSetAdvParams, LeSetAdvSetRandomAddr, LeSetEventMask, LeSetExtAdvData, LeSetExtAdvEnable,
+    LeSetExtAdvParams, LeSetExtScanEnable, LeSetExtScanParams, LeSetExtScanResponseData, LeSetRandomAddr,
+    LeSetScanEnable, LeSetScanParams, LeSetScanResponseData,
 };
 use bt_hci::cmd::link_control::Disconnect;
 use bt_hci::cmd::{AsyncCmd, SyncCmd};
@@ -25,21 +25,22 @@ use bt_hci::param::{
     FilterDuplicates, InitiatingPhy, LeConnRole, LeEventMask, Operation, PhyParams, ScanningPhy, Status,
 };
 use bt_hci::{ControllerToHostPacket, FromHciBytes, WriteHci};
-use embassy_futures::select::{select, select3, select4, Either, Either3, Either4};
+use embassy_futures::select::{select, select4, Either, Either4};
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_sync::channel::Channel;
 use embassy_sync::once_lock::OnceLock;
-use embassy_sync::waitqueue::WakerRegistration;
+use embassy_sync::waitqueue::{MultiWakerRegistration, WakerRegistration};
+use embassy_time::with_timeout;
 use futures::pin_mut;
 
 use crate::advertise::{Advertisement, AdvertisementParameters, AdvertisementSet, RawAdvertisement};
-use crate::channel_manager::{ChannelManager, ChannelStorage, PacketChannel};
+use crate::channel_manager::{ChannelManager, ChannelStorage, ConnParamUpdateRequest, PacketChannel};
 use crate::command::CommandState;
-use crate::connection::{ConnectConfig, Connection};
+use crate::connection::{ConnectConfig, ConnectParams, Connection};
 use crate::connection_manager::{ConnectionManager, ConnectionStorage, DynamicConnectionManager, PacketGrant};
 use crate::cursor::WriteCursor;
 use crate::l2cap::sar::{PacketReassembly, SarType, EMPTY_SAR};
-use crate::packet_pool::{AllocId, GlobalPacketPool, PacketPool, Qos};
+use crate::packet_pool::{AllocId, GlobalPacketPool, PacketPool, Qos, ATT_ID};
 use crate::pdu::Pdu;
 use crate::scan::{PhySet, ScanConfig, ScanReport};
 use crate::types::l2cap::{
@@ -49,13 +50,16 @@ use crate::{att, config, Address, BleHostError, Error};
 #[cfg(feature = "gatt")]
 use crate::{
     attribute::AttributeTable,
-    gatt::{GattClient, GattServer},
+    gatt::{Bearer, GattClient, GattServer},
 };
 
 /// BleHostResources holds the resources used by the host.
 ///
 /// The l2cap packet pool is used by the host to handle inbound data, by allocating space for
 /// incoming packets and dispatching to the appropriate connection and channel.
+///
+/// The packet size (`L2CAP_MTU`) also limits the ATT MTU, which is negotiated up to
+/// `L2CAP_MTU - 4` bytes (at most 517).
 pub struct BleHostResources<
     const CONNS: usize,
     const CHANNELS: usize,
@@ -63,22 +67,28 @@ pub struct BleHostResources<
     const ADV_SETS: usize = 1,
 > {
     rx_pool: PacketPool<NoopRawMutex, L2CAP_MTU, { config::L2CAP_RX_PACKET_POOL_SIZE }, CHANNELS>,
-    connections: [ConnectionStorage; CONNS],
+    connections: [ConnectionStorage<{ config::GATT_CLIENT_NOTIFICATION_QUEUE_SIZE }>; CONNS],
     channels: [ChannelStorage; CHANNELS],
     channels_rx: [PacketChannel<{ config::L2CAP_RX_QUEUE_SIZE }>; CHANNELS],
     sar: [SarType; CONNS],
+    att_disconnected: [Option<ConnHandle>; CONNS],
     advertise_handles: [AdvHandleState; ADV_SETS],
 }
 
 impl<const CONNS: usize, const CHANNELS: usize, const L2CAP_MTU: usize, const ADV_SETS: usize>
     BleHostResources<CONNS, CHANNELS, L2CAP_MTU, ADV_SETS>
 {
+    // Packets must hold an ATT PDU of the default MTU of 23 along with its l2cap header.
+    const L2CAP_MTU_CHECK: () = assert!(L2CAP_MTU >= 27, "L2CAP_MTU must be at least 27");
+
     /// Create a new instance of host resources with the provided QoS requirements for packets.
     pub fn new(qos: Qos) -> Self {
+        let () = Self::L2CAP_MTU_CHECK;
         Self {
             rx_pool: PacketPool::new(qos),
             connections: [ConnectionStorage::DISCONNECTED; CONNS],
             sar: [EMPTY_SAR; CONNS],
+            att_disconnected: [None; CONNS],
             channels: [ChannelStorage::DISCONNECTED; CHANNELS],
             channels_rx: [PacketChannel::NEW; CHANNELS],
             advertise_handles: [AdvHandleState::None; ADV_SETS],
@@ -98,17 +108,25 @@ pub struct BleHost<'d, T> {
     initialized: OnceLock<()>,
     metrics: RefCell<Metrics>,
     pub(crate) controller: T,
-    pub(crate) connections: ConnectionManager<'d>,
+    pub(crate) connections: ConnectionManager<'d, { config::GATT_CLIENT_NOTIFICATION_QUEUE_SIZE }>,
     pub(crate) reassembly: PacketReassembly<'d>,
     pub(crate) channels: ChannelManager<'d, { config::L2CAP_RX_QUEUE_SIZE }>,
-    pub(crate) att_inbound: Channel<NoopRawMutex, (ConnHandle, Pdu), 1>,
+    // Requests, commands and confirmations for the GATT server. Responses, notifications and indications for GATT
+    // clients are kept with their connection.
+    pub(crate) att_server_inbound: Channel<NoopRawMutex, (ConnHandle, Pdu), 1>,
+    // Connections closed since the GATT server last released their state, oldest first.
+    pub(crate) att_disconnected: AttDisconnected<'d>,
     pub(crate) rx_pool: &'static dyn GlobalPacketPool,
     outbound: Channel<NoopRawMutex, (ConnHandle, Pdu), 1>,
+    // Largest ACL data packet accepted by the controller.
+    acl_max_len: Cell<usize>,
 
     pub(crate) scanner: Channel<NoopRawMutex, Option<ScanReport>, 1>,
     advertise_state: AdvState<'d>,
     advertise_command_state: CommandState<bool>,
     connect_command_state: CommandState<bool>,
+    // Decides whether connection parameters requested by a peripheral are accepted
+    conn_params_policy: fn(&ConnectParams) -> bool,
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -204,6 +222,66 @@ impl<'d> AdvState<'d> {
     }
 }
 
+pub(crate) struct AttDisconnectedState<'d> {
+    handles: &'d mut [Option<ConnHandle>],
+    waker: WakerRegistration,
+}
+
+// Connections closed since the GATT server last released their state, oldest first. There is room for as many as
+// connections can be open at once.
+pub(crate) struct AttDisconnected<'d> {
+    state: RefCell<AttDisconnectedState<'d>>,
+}
+
+impl<'d> AttDisconnected<'d> {
+    pub fn new(handles: &'d mut [Option<ConnHandle>]) -> Self {
+        Self {
+            state: RefCell::new(AttDisconnectedState {
+                handles,
+                waker: WakerRegistration::new(),
+            }),
+        }
+    }
+
+    // Keep a closed connection, dropping the oldest one if there is no room left. Returns false if one was dropped.
+    pub fn push(&self, handle: ConnHandle) -> bool {
+        let mut state = self.state.borrow_mut();
+        state.waker.wake();
+        if state.handles.contains(&Some(handle)) {
+            return true;
+        }
+        if let Some(entry) = state.handles.iter_mut().find(|entry| entry.is_none()) {
+            entry.replace(handle);
+            return true;
+        }
+        let len = state.handles.len();
+        if len > 0 {
+            state.handles.rotate_left(1);
+            state.handles[len - 1] = Some(handle);
+        }
+        false
+    }
+
+    // Take the oldest closed connection.
+    pub fn try_take(&self) -> Option<ConnHandle> {
+        let mut state = self.state.borrow_mut();
+        let handle = state.handles.first_mut()?.take()?;
+        state.handles.rotate_left(1);
+        Some(handle)
+    }
+
+    pub async fn take(&self) -> ConnHandle {
+        poll_fn(|cx| match self.try_take() {
+            Some(handle) => Poll::Ready(handle),
+            None => {
+                self.state.borrow_mut().waker.register(cx.waker());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
 #[derive(Default)]
 struct Metrics {
     connect_events: u32,
@@ -228,7 +306,11 @@ where
             initialized: OnceLock::new(),
             metrics: RefCell::new(Metrics::default()),
             controller,
-            connections: ConnectionManager::new(&mut host_resources.connections[..]),
+            connections: ConnectionManager::new(
+                &mut host_resources.connections[..],
+                // ATT PDUs are allocated from the packet pool, with room for the l2cap header
+                L2CAP_MTU.saturating_sub(4).clamp(23, att::ATT_MTU_MAX) as u16,
+            ),
             reassembly: PacketReassembly::new(&mut host_resources.sar[..]),
             channels: ChannelManager::new(
                 &host_resources.rx_pool,
@@ -236,12 +318,15 @@ where
                 &mut host_resources.channels_rx[..],
             ),
             rx_pool: &host_resources.rx_pool,
-            att_inbound: Channel::new(),
+            att_server_inbound: Channel::new(),
+            att_disconnected: AttDisconnected::new(&mut host_resources.att_disconnected[..]),
             scanner: Channel::new(),
             advertise_state: AdvState::new(&mut host_resources.advertise_handles[..]),
             advertise_command_state: CommandState::new(),
             connect_command_state: CommandState::new(),
             outbound: Channel::new(),
+            acl_max_len: Cell::new(27),
+            conn_params_policy: |_| true,
         }
     }
 
@@ -250,6 +335,14 @@ where
         self.address.replace(address);
     }
 
+    /// Set the policy deciding whether connection parameters requested by a peripheral are accepted.
+    ///
+    /// Requests with parameters out of the ranges allowed by the specification are rejected before the policy is
+    /// consulted. By default, all other requests are accepted.
+    pub fn set_connection_params_policy(&mut self, policy: fn(&ConnectParams) -> bool) {
+        self.conn_params_policy = policy;
+    }
+
     pub(crate) async fn set_accept_filter(
         &self,
         filter_accept_list: &[(AddrKind, &BdAddr)],
@@ -733,33 +826,96 @@ where
         use crate::attribute_server::AttributeServer;
         GattServer {
             server: AttributeServer::new(table),
-            rx: self.att_inbound.receiver().into(),
+            rx: self.att_server_inbound.receiver().into(),
             ble: self,
         }
     }
 
     /// Creates a GATT client capable of processing the GATT protocol using the provided table of attributes.
+    ///
+    /// An ATT MTU exchange is performed, requesting the largest MTU that fits in the packet pool, unless the MTU
+    /// of the connection was already negotiated. A server rejecting the exchange leaves the MTU at 23.
     #[cfg(feature = "gatt")]
-    pub async fn gatt_client<'reference, const MAX: usize, const ATT_MTU: usize>(
+    pub async fn gatt_client<'reference, const MAX: usize>(
         &'reference self,
         connection: &Connection<'reference>,
-    ) -> Result<GattClient<'reference, 'd, T, MAX, ATT_MTU>, BleHostError<T::Error>> {
-        let l2cap = L2capHeader { channel: 4, length: 3 };
+    ) -> Result<GattClient<'reference, 'd, T, MAX>, BleHostError<T::Error>> {
+        let mut client = self.gatt_client_without_mtu_exchange(connection);
+        if connection.att_mtu() == 23 {
+            match client.exchange_mtu(self.connections.default_att_mtu()).await {
+                Ok(_) | Err(BleHostError::BleHost(Error::Att(_))) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(client)
+    }
+
+    /// Creates a GATT client without exchanging the ATT MTU, which stays at the default of 23 until
+    /// [`GattClient::exchange_mtu`] is called or the peer initiates an exchange.
+    #[cfg(feature = "gatt")]
+    pub fn gatt_client_without_mtu_exchange<'reference, const MAX: usize>(
+        &'reference self,
+        connection: &Connection<'reference>,
+    ) -> GattClient<'reference, 'd, T, MAX> {
+        GattClient {
+            services: heapless::Vec::new(),
+            ble: self,
+            connection: connection.clone(),
+            notifications: RefCell::new(None),
+            bearer: Bearer::Fixed,
+            timed_out: Cell::new(false),
+            pending: RefCell::new(heapless::Deque::new()),
+            response: RefCell::new(None),
+            receiving: Cell::new(false),
+            requesting: Cell::new(false),
+            waiters: RefCell::new(MultiWakerRegistration::new()),
+            subscribed: RefCell::new(heapless::Vec::new()),
+            unsubscribed: RefCell::new(heapless::Vec::new()),
+            cancel_prepared: false,
+            service_changed: None,
+            database_changed: Cell::new(false),
+        }
+    }
+
+    /// Send an ATT MTU exchange request for a connection, offering the largest MTU supported by the host.
+    ///
+    /// The negotiated MTU is applied when the response from the peer is received. The request takes the turn of
+    /// the fixed ATT channel until then, so that it never overlaps with a request of a GATT client.
+    #[cfg(feature = "gatt")]
+    pub(crate) async fn exchange_att_mtu(&self, handle: ConnHandle) -> Result<(), BleHostError<T::Error>> {
+        let l2cap = L2capHeader {
+            channel: L2CAP_CID_ATT,
+            length: 3,
+        };
         let mut buf = [0; 7];
         let mut w = WriteCursor::new(&mut buf);
         w.write_hci(&l2cap)?;
-        w.write(att::AttReq::ExchangeMtu { mtu: ATT_MTU as u16 })?;
-
-        let mut grant = self.acl(connection.handle(), 1).await?;
+        w.write(att::AttReq::ExchangeMtu {
+            mtu: self.connections.default_att_mtu(),
+        })?;
 
+        let _turn = self.connections.att_turn(handle).await?;
+        let mut grant = self.acl(handle, 1).await?;
         grant.send(w.finish()).await?;
+        drop(grant);
 
-        Ok(GattClient {
-            services: heapless::Vec::new(),
-            rx: self.att_inbound.receiver().into(),
-            ble: self,
-            connection: connection.clone(),
-        })
+        let response = poll_fn(|cx| self.connections.poll_att_response(handle, cx));
+        match with_timeout(crate::gatt::ATT_TRANSACTION_TIMEOUT, response).await {
+            Ok(res) => {
+                res?;
+                Ok(())
+            }
+            Err(_) => {
+                warn!("[host] mtu exchange timed out");
+                self.connections.set_att_timed_out(handle);
+                Err(Error::Timeout.into())
+            }
+        }
+    }
+
+    /// Allocate a buffer for an outbound ATT PDU from the packet pool.
+    pub(crate) fn alloc_att(&self) -> Result<crate::packet_pool::Packet, Error> {
+        self.rx_pool.alloc(ATT_ID).ok_or(Error::OutOfMemory)
     }
 
     fn handle_connection(
@@ -801,7 +957,7 @@ where
         true
     }
 
-    fn handle_acl(&self, acl: AclPacket<'_>) -> Result<(), Error> {
+    async fn handle_acl(&self, acl: AclPacket<'_>) -> Result<(), Error> {
         if !self.connections.is_handle_connected(acl.handle()) {
             return Err(Error::Disconnected);
         }
@@ -859,9 +1015,12 @@ where
                 if let Ok(att::AttReq::ExchangeMtu { mtu }) =
                     att::AttReq::decode(&packet.as_ref()[..header.length as usize])
                 {
-                    let mtu = self.connections.exchange_att_mtu(acl.handle(), mtu);
+                    self.connections.exchange_att_mtu(acl.handle(), mtu);
 
-                    let rsp = att::AttRsp::ExchangeMtu { mtu };
+                    // The response carries our own receive MTU, not the negotiated one
+                    let rsp = att::AttRsp::ExchangeMtu {
+                        mtu: self.connections.default_att_mtu(),
+                    };
                     let l2cap = L2capHeader {
                         channel: L2CAP_CID_ATT,
                         length: 3,
@@ -875,17 +1034,26 @@ where
                     if let Err(e) = self.outbound.try_send((acl.handle(), Pdu::new(packet, len))) {
                         return Err(Error::OutOfMemory);
                     }
-                } else if let Ok(att::AttRsp::ExchangeMtu { mtu }) =
-                    att::AttRsp::decode(&packet.as_ref()[..header.length as usize])
-                {
-                    self.connections.exchange_att_mtu(acl.handle(), mtu);
                 } else {
+                    // Everything else, including responses to exchanges initiated by a client, is for GATT.
+                    // PDUs sent by the peer as a server go to the clients of the connection, and the others to the
+                    // server.
                     #[cfg(feature = "gatt")]
-                    if let Err(e) = self
-                        .att_inbound
-                        .try_send((acl.handle(), Pdu::new(packet, header.length as usize)))
                     {
-                        return Err(Error::OutOfMemory);
+                        // Apply the response to an exchange initiated by us, whether or not a client is waiting for it
+                        if let Ok(att::AttRsp::ExchangeMtu { mtu }) =
+                            att::AttRsp::decode(&packet.as_ref()[..header.length as usize])
+                        {
+                            let ours = self.connections.default_att_mtu();
+                            self.connections.exchange_att_mtu(acl.handle(), ours.min(mtu));
+                        }
+
+                        let pdu = Pdu::new(packet, header.length as usize);
+                        if pdu.as_ref().first().is_some_and(|&opcode| att::from_server(opcode)) {
+                            self.connections.post_att(acl.handle(), pdu)?;
+                        } else if let Err(e) = self.att_server_inbound.try_send((acl.handle(), pdu)) {
+                            return Err(Error::OutOfMemory);
+                        }
                     }
 
                     #[cfg(not(feature = "gatt"))]
@@ -919,7 +1087,8 @@ where
             + ControllerCmdSync<LeCreateConnCancel>
             + for<'t> ControllerCmdSync<LeSetAdvEnable>
             + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
-            + ControllerCmdSync<LeReadBufferSize>,
+            + ControllerCmdSync<LeReadBufferSize>
+            + ControllerCmdAsync<LeConnUpdate>,
     {
         self.run_with_handler(|_| {}).await
     }
@@ -935,7 +1104,8 @@ where
             + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
             + ControllerCmdSync<Reset>
             + ControllerCmdSync<LeCreateConnCancel>
-            + ControllerCmdSync<LeReadBufferSize>,
+            + ControllerCmdSync<LeReadBufferSize>
+            + ControllerCmdAsync<LeConnUpdate>,
     {
         const MAX_HCI_PACKET_LEN: usize = 259;
 
@@ -989,13 +1159,18 @@ where
             info!("[host] setting txq to {}", ret.total_num_le_acl_data_packets as usize);
             self.connections
                 .set_link_credits(ret.total_num_le_acl_data_packets as usize);
-            // TODO: Configure ACL max buffer size as well?
+            if ret.le_acl_data_packet_length > 0 {
+                self.acl_max_len.set(ret.le_acl_data_packet_length as usize);
+            }
             let _ = self.initialized.init(());
 
             loop {
                 match select4(
                     poll_fn(|cx| self.connections.poll_disconnecting(Some(cx))),
-                    poll_fn(|cx| self.channels.poll_disconnecting(Some(cx))),
+                    select(
+                        poll_fn(|cx| self.channels.poll_disconnecting(Some(cx))),
+                        poll_fn(|cx| self.channels.poll_signal_response(cx)),
+                    ),
                     poll_fn(|cx| self.connect_command_state.poll_cancelled(cx)),
                     poll_fn(|cx| self.advertise_command_state.poll_cancelled(cx)),
                 )
@@ -1006,11 +1181,15 @@ where
                             .await?;
                         request.confirm();
                     }
-                    Either4::Second(request) => {
+                    Either4::Second(Either::First(request)) => {
                         let mut grant = self.acl(request.handle(), 1).await?;
                         request.send(&mut grant).await?;
                         request.confirm();
                     }
+                    Either4::Second(Either::Second(response)) => {
+                        let mut grant = self.acl(response.handle(), 1).await?;
+                        response.send(&mut grant).await?;
+                    }
                     Either4::Third(_) => {
                         // trace!("[host] cancelling create connection");
                         if let Err(e) = self.command(LeCreateConnCancel::new()).await {
@@ -1032,10 +1211,24 @@ where
         };
         pin_mut!(control_fut);
 
+        // Connection parameter updates requested by peripherals, handled apart from the control future as
+        // applying them waits for the controller.
+        let conn_param_fut = async {
+            let _ = self.initialized.get().await;
+            loop {
+                let request = poll_fn(|cx| self.channels.poll_conn_param_update_request(cx)).await;
+                // Errors only concern the connection of the request, such as one closed before it is answered
+                if let Err(e) = self.handle_conn_param_update(request).await {
+                    warn!("[host] error handling connection parameter update request: {:?}", e);
+                }
+            }
+        };
+        pin_mut!(conn_param_fut);
+
         let tx_fut = async {
             loop {
                 let (conn, pdu) = self.outbound.receive().await;
-                match self.acl(conn, 1).await {
+                match self.acl(conn, self.acl_packets(pdu.len)).await {
                     Ok(mut sender) => {
                         if let Err(e) = sender.send(pdu.as_ref()).await {
                             warn!("[host] error sending outbound pdu");
@@ -1057,7 +1250,7 @@ where
                 let mut rx = [0u8; MAX_HCI_PACKET_LEN];
                 let result = self.controller.read(&mut rx).await;
                 match result {
-                    Ok(ControllerToHostPacket::Acl(acl)) => match self.handle_acl(acl) {
+                    Ok(ControllerToHostPacket::Acl(acl)) => match self.handle_acl(acl).await {
                         Ok(_) => {}
                         Err(e) => {
                             trace!("Error processing ACL packet: {:?}", e);
@@ -1115,6 +1308,10 @@ where
                             let _ = self.connections.disconnected(handle);
                             let _ = self.channels.disconnected(handle);
                             self.reassembly.disconnected(handle);
+                            if !self.att_disconnected.push(handle) {
+                                // The GATT server is not running, so the oldest connection has no state left to release
+                                warn!("[host] gatt server not releasing state of closed connections");
+                            }
                             let mut m = self.metrics.borrow_mut();
                             m.disconnect_events = m.disconnect_events.wrapping_add(1);
                         }
@@ -1143,11 +1340,43 @@ where
         pin_mut!(rx_fut);
 
         // info!("Entering select loop");
-        match select3(&mut control_fut, &mut rx_fut, &mut tx_fut).await {
-            Either3::First(result) => result,
-            Either3::Second(result) => result,
-            Either3::Third(result) => result,
+        match select4(&mut control_fut, &mut rx_fut, &mut tx_fut, &mut conn_param_fut).await {
+            Either4::First(result) => result,
+            Either4::Second(result) => result,
+            Either4::Third(result) => result,
+            // Connection parameter update requests are handled until the host stops
+            Either4::Fourth(()) => Ok(()),
+        }
+    }
+
+    // Answer a connection parameter update request of a peripheral, and apply the parameters if accepted.
+    async fn handle_conn_param_update(&self, request: ConnParamUpdateRequest) -> Result<(), BleHostError<T::Error>>
+    where
+        T: ControllerCmdAsync<LeConnUpdate>,
+    {
+        let handle = request.handle();
+        let Ok(role) = self.connections.role_of(handle) else {
+            return Ok(());
+        };
+        let mut grant = self.acl(handle, 1).await?;
+        let Some(accepted) = request.decide(role, self.conn_params_policy) else {
+            return request.reject(&mut grant).await;
+        };
+        request.respond(&mut grant, accepted).await?;
+        drop(grant);
+
+        if accepted {
+            trace!("[host] applying connection params requested for {:?}", handle);
+            if let Err(e) = self.async_command(request.params().conn_update(handle)).await {
+                warn!("[host] error applying connection params: {:?}", e);
+            }
         }
+        Ok(())
+    }
+
+    // Number of ACL packets needed to send a l2cap PDU of the given length
+    pub(crate) fn acl_packets(&self, len: usize) -> u16 {
+        len.div_ceil(self.acl_max_len.get()).max(1) as u16
     }
 
     // Request to send n ACL packets to the HCI controller for a connection
@@ -1157,6 +1386,7 @@ where
             controller: &self.controller,
             handle,
             grant,
+            fragment_size: self.acl_max_len.get(),
         })
     }
 
@@ -1172,6 +1402,7 @@ where
             controller: &self.controller,
             handle,
             grant,
+            fragment_size: self.acl_max_len.get(),
         })
     }
 
@@ -1190,7 +1421,7 @@ where
 pub struct Advertiser<'a, 'd> {
     advertise_state: &'a AdvState<'d>,
     advertise_command_state: &'a CommandState<bool>,
-    connections: &'a ConnectionManager<'d>,
+    connections: &'a ConnectionManager<'d, { config::GATT_CLIENT_NOTIFICATION_QUEUE_SIZE }>,
     extended: bool,
 }
 
@@ -1220,46 +1451,61 @@ impl<'a, 'd> Drop for Advertiser<'a, 'd> {
 pub struct AclSender<'a, 'd, T: Controller> {
     pub(crate) controller: &'a T,
     pub(crate) handle: ConnHandle,
-    pub(crate) grant: PacketGrant<'a, 'd>,
+    pub(crate) grant: PacketGrant<'a, 'd, { config::GATT_CLIENT_NOTIFICATION_QUEUE_SIZE }>,
+    pub(crate) fragment_size: usize,
 }
 
 impl<'a, 'd, T: Controller> AclSender<'a, 'd, T> {
+    /// Send a l2cap PDU, fragmented into ACL packets no larger than the controller buffers.
     pub(crate) fn try_send(&mut self, pdu: &[u8]) -> Result<(), BleHostError<T::Error>>
     where
         T: blocking::Controller,
     {
-        let acl = AclPacket::new(
-            self.handle,
-            AclPacketBoundary::FirstNonFlushable,
-            AclBroadcastFlag::PointToPoint,
-            pdu,
-        );
-        // info!("Sent ACL {:?}", acl);
-        match self.controller.try_write_acl_data(&acl) {
-            Ok(result) => {
-                self.grant.confirm(1);
-                Ok(result)
-            }
-            Err(blocking::TryError::Busy) => {
-                warn!("hci: acl data send busy");
-                Err(Error::Busy.into())
+        for (i, fragment) in pdu.chunks(self.fragment_size).enumerate() {
+            let acl = AclPacket::new(
+                self.handle,
+                if i == 0 {
+                    AclPacketBoundary::FirstNonFlushable
+                } else {
+                    AclPacketBoundary::Continuing
+                },
+                AclBroadcastFlag::PointToPoint,
+                fragment,
+            );
+            // info!("Sent ACL {:?}", acl);
+            match self.controller.try_write_acl_data(&acl) {
+                Ok(_) => {
+                    self.grant.confirm(1);
+                }
+                Err(blocking::TryError::Busy) => {
+                    warn!("hci: acl data send busy");
+                    return Err(Error::Busy.into());
+                }
+                Err(blocking::TryError::Error(e)) => return Err(BleHostError::Controller(e)),
             }
-            Err(blocking::TryError::Error(e)) => Err(BleHostError::Controller(e)),
         }
+        Ok(())
     }
 
+    /// Send a l2cap PDU, fragmented into ACL packets no larger than the controller buffers.
     pub(crate) async fn send(&mut self, pdu: &[u8]) -> Result<(), BleHostError<T::Error>> {
-        let acl = AclPacket::new(
-            self.handle,
-            AclPacketBoundary::FirstNonFlushable,
-            AclBroadcastFlag::PointToPoint,
-            pdu,
-        );
-        self.controller
-            .write_acl_data(&acl)
-            .await
-            .map_err(BleHostError::Controller)?;
-        self.grant.confirm(1);
+        for (i, fragment) in pdu.chunks(self.fragment_size).enumerate() {
+            let acl = AclPacket::new(
+                self.handle,
+                if i == 0 {
+                    AclPacketBoundary::FirstNonFlushable
+                } else {
+                    AclPacketBoundary::Continuing
+                },
+                AclBroadcastFlag::PointToPoint,
+                fragment,
+            );
+            self.controller
+                .write_acl_data(&acl)
+                .await
+                .map_err(BleHostError::Controller)?;
+            self.grant.confirm(1);
+        }
         Ok(())
     }
 
@@ -1268,6 +1514,18 @@ impl<'a, 'd, T: Controller> AclSender<'a, 'd, T> {
         identifier: u8,
         signal: &D,
         p_buf: &mut [u8],
+    ) -> Result<(), BleHostError<T::Error>> {
+        self.signal_with_cids(identifier, signal, &[], p_buf).await
+    }
+
+    /// Send a signal whose fields are followed by a list of channel identifiers, as in enhanced credit based
+    /// flow control mode.
+    pub(crate) async fn signal_with_cids<D: L2capSignal>(
+        &mut self,
+        identifier: u8,
+        signal: &D,
+        cids: &[u16],
+        p_buf: &mut [u8],
     ) -> Result<(), BleHostError<T::Error>> {
         //trace!(
         //    "[l2cap] sending control signal (req = {}) signal: {:?}",
@@ -1277,7 +1535,7 @@ impl<'a, 'd, T: Controller> AclSender<'a, 'd, T> {
         let header = L2capSignalHeader {
             identifier,
             code: D::code(),
-            length: signal.size() as u16,
+            length: (signal.size() + 2 * cids.len()) as u16,
         };
         let l2cap = L2capHeader {
             channel: D::channel(),
@@ -1288,6 +1546,9 @@ impl<'a, 'd, T: Controller> AclSender<'a, 'd, T> {
         w.write_hci(&l2cap)?;
         w.write_hci(&header)?;
         w.write_hci(signal)?;
+        for cid in cids {
+            w.write(*cid)?;
+        }
 
         self.send(w.finish()).await?;
 
@@ -1,11 +1,13 @@
 //! L2CAP channels.
 use bt_hci::controller::{blocking, Controller};
+use heapless::Vec;
 
 pub use crate::channel_manager::CreditFlowPolicy;
 use crate::channel_manager::{ChannelIndex, DynamicChannelManager};
 use crate::connection::Connection;
 use crate::host::BleHost;
-use crate::BleHostError;
+use crate::types::l2cap::L2CAP_ENHANCED_MAX_CHANNELS;
+use crate::{BleHostError, Error};
 
 pub(crate) mod sar;
 
@@ -61,6 +63,11 @@ impl<'d> L2capChannel<'d> {
         Self { index, manager }
     }
 
+    /// The MTU agreed for this channel, the largest SDU both sides can receive.
+    pub fn mtu(&self) -> u16 {
+        self.manager.mtu(self.index)
+    }
+
     /// Disconnect this channel.
     pub fn disconnect(&mut self) {
         self.manager.disconnect(self.index);
@@ -96,6 +103,28 @@ impl<'d> L2capChannel<'d> {
         ble.channels.try_send(self.index, buf, &mut p_buf[..], ble)
     }
 
+    /// Send the provided buffer over this l2cap channel, segmenting it using a buffer from the packet pool.
+    pub(crate) async fn send_pooled<T: Controller>(
+        &mut self,
+        ble: &BleHost<'_, T>,
+        buf: &[u8],
+    ) -> Result<(), BleHostError<T::Error>> {
+        let mut p_buf = ble.alloc_att()?;
+        ble.channels.send(self.index, buf, p_buf.as_mut(), ble).await
+    }
+
+    /// Send the provided buffer over this l2cap channel, segmenting it using a buffer from the packet pool.
+    ///
+    /// If there are no available credits to send, returns Error::Busy.
+    pub(crate) fn try_send_pooled<T: Controller + blocking::Controller>(
+        &mut self,
+        ble: &BleHost<'_, T>,
+        buf: &[u8],
+    ) -> Result<(), BleHostError<T::Error>> {
+        let mut p_buf = ble.alloc_att()?;
+        ble.channels.try_send(self.index, buf, p_buf.as_mut(), ble)
+    }
+
     /// Receive data on this channel and copy it into the buffer.
     ///
     /// The length provided buffer slice must be equal or greater to the agreed MTU.
@@ -108,6 +137,10 @@ impl<'d> L2capChannel<'d> {
     }
 
     /// Await an incoming connection request matching the list of PSM.
+    ///
+    /// If the request opens several channels in enhanced credit based flow control mode, only the first is accepted.
+    ///
+    /// Requests arriving while no task is accepting their PSM on the connection are refused by the host.
     pub async fn accept<T: Controller>(
         ble: &'d BleHost<'_, T>,
         connection: &Connection<'_>,
@@ -120,6 +153,21 @@ impl<'d> L2capChannel<'d> {
             .await
     }
 
+    /// Await an incoming connection request matching the list of PSM, accepting up to `N` of the channels it opens.
+    ///
+    /// Channels requested in enhanced credit based flow control mode beyond the first `N` are refused.
+    pub async fn accept_many<T: Controller, const N: usize>(
+        ble: &'d BleHost<'_, T>,
+        connection: &Connection<'_>,
+        psm: &[u16],
+        config: &L2capChannelConfig,
+    ) -> Result<Vec<Self, N>, BleHostError<T::Error>> {
+        let handle = connection.handle();
+        ble.channels
+            .accept_many(handle, psm, config.mtu, config.flow_policy, config.initial_credits, ble)
+            .await
+    }
+
     /// Create a new connection request with the provided PSM.
     pub async fn create<T: Controller>(
         ble: &'d BleHost<'_, T>,
@@ -140,4 +188,43 @@ where {
             )
             .await
     }
+
+    /// Open `N` channels with the provided PSM, up to 5, using a single request in enhanced credit based flow
+    /// control mode.
+    ///
+    /// The channels accepted by the peer are returned, which may be fewer than requested. If the peer refuses all
+    /// of them, the reason it gave is returned as [`Error::ChannelRefused`]. Enhanced mode requires an MTU of at
+    /// least 64, so the packet pool must hold packets of at least 68 bytes.
+    pub async fn create_many<T: Controller, const N: usize>(
+        ble: &'d BleHost<'_, T>,
+        connection: &Connection<'_>,
+        psm: u16,
+        config: &L2capChannelConfig,
+    ) -> Result<Vec<Self, N>, BleHostError<T::Error>> {
+        ble.channels
+            .create_many(
+                connection.handle(),
+                psm,
+                config.mtu,
+                config.flow_policy,
+                config.initial_credits,
+                ble,
+            )
+            .await
+    }
+
+    /// Increase the MTU of channels opened in enhanced credit based flow control mode, up to 5 at once.
+    ///
+    /// All channels must belong to the same connection. The new MTU applies once the peer accepts it.
+    pub async fn reconfigure<T: Controller>(
+        ble: &BleHost<'_, T>,
+        channels: &[L2capChannel<'_>],
+        mtu: u16,
+    ) -> Result<(), BleHostError<T::Error>> {
+        let mut indices: Vec<ChannelIndex, L2CAP_ENHANCED_MAX_CHANNELS> = Vec::new();
+        for channel in channels {
+            indices.push(channel.index).map_err(|_| Error::InvalidValue)?;
+        }
+        ble.channels.reconfigure(&indices, mtu, ble).await
+    }
 }
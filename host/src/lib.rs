@@ -14,6 +14,7 @@ pub use bt_hci::param::{AddrKind, BdAddr, LeConnRole as Role};
 use bt_hci::FromHciBytesError;
 
 use crate::att::AttErrorCode;
+use crate::types::l2cap::LeCreditConnResultCode;
 
 mod fmt;
 
@@ -45,8 +46,23 @@ mod attribute_server;
 #[cfg(feature = "gatt")]
 pub mod gatt;
 
+#[cfg(feature = "derive")]
+pub use trouble_host_macros::gatt_service;
+
+// Lets the tests use the macros, whose generated code refers to this crate by name.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as trouble_host;
+
+/// Items used by code generated by the macros. Not part of the public API.
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    pub use bt_hci::controller::Controller;
+    pub use embassy_sync::blocking_mutex::raw::RawMutex;
+}
+
 /// A BLE address.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Address {
     pub kind: AddrKind,
@@ -82,6 +98,7 @@ pub enum Error {
     Advertisement(AdvertisementDataError),
     InvalidChannelId,
     NoChannelAvailable,
+    ChannelRefused(LeCreditConnResultCode),
     NotFound,
     InvalidState,
     OutOfMemory,
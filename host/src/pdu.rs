@@ -11,6 +11,12 @@ impl Pdu {
     }
 }
 
+impl core::fmt::Debug for Pdu {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Pdu").field("len", &self.len).finish()
+    }
+}
+
 impl AsRef<[u8]> for Pdu {
     fn as_ref(&self) -> &[u8] {
         &self.packet.as_ref()[..self.len]
@@ -0,0 +1,311 @@
+//! Conversion between Rust types and GATT characteristic values.
+use heapless::{String, Vec};
+
+use crate::Error;
+
+/// A type that can be stored in a GATT characteristic value.
+///
+/// Values are encoded in little endian, as GATT characteristics are.
+pub trait GattValue: Sized {
+    /// Minimum size of the encoded value.
+    const MIN_SIZE: usize;
+    /// Maximum size of the encoded value.
+    const MAX_SIZE: usize;
+
+    /// Encode the value into `dest`, returning the number of bytes written.
+    fn to_gatt(&self, dest: &mut [u8]) -> Result<usize, Error>;
+
+    /// Decode a value from `src`.
+    fn from_gatt(src: &[u8]) -> Result<Self, Error>;
+}
+
+fn copy_to(src: &[u8], dest: &mut [u8]) -> Result<usize, Error> {
+    if dest.len() < src.len() {
+        return Err(Error::InsufficientSpace);
+    }
+    dest[..src.len()].copy_from_slice(src);
+    Ok(src.len())
+}
+
+macro_rules! primitive {
+    ($($t:ty),+) => {
+        $(
+            impl GattValue for $t {
+                const MIN_SIZE: usize = core::mem::size_of::<$t>();
+                const MAX_SIZE: usize = core::mem::size_of::<$t>();
+
+                fn to_gatt(&self, dest: &mut [u8]) -> Result<usize, Error> {
+                    copy_to(&self.to_le_bytes(), dest)
+                }
+
+                fn from_gatt(src: &[u8]) -> Result<Self, Error> {
+                    let bytes = src.try_into().map_err(|_| Error::InvalidValue)?;
+                    Ok(<$t>::from_le_bytes(bytes))
+                }
+            }
+        )+
+    };
+}
+
+primitive!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl GattValue for bool {
+    const MIN_SIZE: usize = 1;
+    const MAX_SIZE: usize = 1;
+
+    fn to_gatt(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        copy_to(&[*self as u8], dest)
+    }
+
+    fn from_gatt(src: &[u8]) -> Result<Self, Error> {
+        match src {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+}
+
+impl<const N: usize> GattValue for [u8; N] {
+    const MIN_SIZE: usize = N;
+    const MAX_SIZE: usize = N;
+
+    fn to_gatt(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        copy_to(self, dest)
+    }
+
+    fn from_gatt(src: &[u8]) -> Result<Self, Error> {
+        src.try_into().map_err(|_| Error::InvalidValue)
+    }
+}
+
+impl<const N: usize> GattValue for Vec<u8, N> {
+    const MIN_SIZE: usize = 0;
+    const MAX_SIZE: usize = N;
+
+    fn to_gatt(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        copy_to(self, dest)
+    }
+
+    fn from_gatt(src: &[u8]) -> Result<Self, Error> {
+        Vec::from_slice(src).map_err(|_| Error::InvalidValue)
+    }
+}
+
+impl<const N: usize> GattValue for String<N> {
+    const MIN_SIZE: usize = 0;
+    const MAX_SIZE: usize = N;
+
+    fn to_gatt(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        copy_to(self.as_bytes(), dest)
+    }
+
+    fn from_gatt(src: &[u8]) -> Result<Self, Error> {
+        let s = core::str::from_utf8(src).map_err(|_| Error::InvalidValue)?;
+        String::try_from(s).map_err(|_| Error::InvalidValue)
+    }
+}
+
+/// Multiply a mantissa by a power of ten without relying on `std` float functions.
+fn scale(mantissa: f64, exponent: i8) -> f64 {
+    let mut value = mantissa;
+    for _ in 0..exponent.unsigned_abs() {
+        if exponent > 0 {
+            value *= 10.0;
+        } else {
+            value /= 10.0;
+        }
+    }
+    value
+}
+
+/// IEEE 11073-20601 16-bit floating point value (SFLOAT), with a 12-bit mantissa and 4-bit exponent.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SFloat {
+    mantissa: i16,
+    exponent: i8,
+}
+
+impl SFloat {
+    pub const NAN: SFloat = SFloat {
+        mantissa: 0x07FF,
+        exponent: 0,
+    };
+    /// Not at this resolution.
+    pub const NRES: SFloat = SFloat {
+        mantissa: -0x0800,
+        exponent: 0,
+    };
+    pub const INFINITY: SFloat = SFloat {
+        mantissa: 0x07FE,
+        exponent: 0,
+    };
+    pub const NEG_INFINITY: SFloat = SFloat {
+        mantissa: -0x07FE,
+        exponent: 0,
+    };
+
+    /// Create a value of `mantissa * 10^exponent`.
+    ///
+    /// The mantissa must fit in 12 bits and the exponent in 4 bits, both signed.
+    pub fn new(mantissa: i16, exponent: i8) -> Result<Self, Error> {
+        if !(-2048..=2047).contains(&mantissa) || !(-8..=7).contains(&exponent) {
+            return Err(Error::InvalidValue);
+        }
+        Ok(Self { mantissa, exponent })
+    }
+
+    pub fn mantissa(&self) -> i16 {
+        self.mantissa
+    }
+
+    pub fn exponent(&self) -> i8 {
+        self.exponent
+    }
+
+    /// Convert to a float, mapping the special values to NaN and infinities.
+    pub fn to_f32(&self) -> f32 {
+        match (self.exponent, self.mantissa) {
+            (0, 0x07FE) => f32::INFINITY,
+            (0, -0x07FE) => f32::NEG_INFINITY,
+            (0, 0x07FF | -0x0800 | -0x07FF) => f32::NAN,
+            _ => scale(self.mantissa as f64, self.exponent) as f32,
+        }
+    }
+}
+
+impl GattValue for SFloat {
+    const MIN_SIZE: usize = 2;
+    const MAX_SIZE: usize = 2;
+
+    fn to_gatt(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        let raw = ((self.exponent as u16) << 12) | (self.mantissa as u16 & 0x0FFF);
+        copy_to(&raw.to_le_bytes(), dest)
+    }
+
+    fn from_gatt(src: &[u8]) -> Result<Self, Error> {
+        let raw = u16::from_gatt(src)?;
+        Ok(Self {
+            mantissa: ((raw << 4) as i16) >> 4,
+            exponent: ((raw >> 8) as i8) >> 4,
+        })
+    }
+}
+
+/// IEEE 11073-20601 32-bit floating point value (FLOAT), with a 24-bit mantissa and 8-bit exponent.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Float {
+    mantissa: i32,
+    exponent: i8,
+}
+
+impl Float {
+    pub const NAN: Float = Float {
+        mantissa: 0x007F_FFFF,
+        exponent: 0,
+    };
+    /// Not at this resolution.
+    pub const NRES: Float = Float {
+        mantissa: -0x0080_0000,
+        exponent: 0,
+    };
+    pub const INFINITY: Float = Float {
+        mantissa: 0x007F_FFFE,
+        exponent: 0,
+    };
+    pub const NEG_INFINITY: Float = Float {
+        mantissa: -0x007F_FFFE,
+        exponent: 0,
+    };
+
+    /// Create a value of `mantissa * 10^exponent`.
+    ///
+    /// The mantissa must fit in 24 bits, signed.
+    pub fn new(mantissa: i32, exponent: i8) -> Result<Self, Error> {
+        if !(-0x0080_0000..=0x007F_FFFF).contains(&mantissa) {
+            return Err(Error::InvalidValue);
+        }
+        Ok(Self { mantissa, exponent })
+    }
+
+    pub fn mantissa(&self) -> i32 {
+        self.mantissa
+    }
+
+    pub fn exponent(&self) -> i8 {
+        self.exponent
+    }
+
+    /// Convert to a float, mapping the special values to NaN and infinities.
+    pub fn to_f64(&self) -> f64 {
+        match (self.exponent, self.mantissa) {
+            (0, 0x007F_FFFE) => f64::INFINITY,
+            (0, -0x007F_FFFE) => f64::NEG_INFINITY,
+            (0, 0x007F_FFFF | -0x0080_0000 | -0x007F_FFFF) => f64::NAN,
+            _ => scale(self.mantissa as f64, self.exponent),
+        }
+    }
+}
+
+impl GattValue for Float {
+    const MIN_SIZE: usize = 4;
+    const MAX_SIZE: usize = 4;
+
+    fn to_gatt(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        let raw = ((self.exponent as u8 as u32) << 24) | (self.mantissa as u32 & 0x00FF_FFFF);
+        copy_to(&raw.to_le_bytes(), dest)
+    }
+
+    fn from_gatt(src: &[u8]) -> Result<Self, Error> {
+        let raw = u32::from_gatt(src)?;
+        Ok(Self {
+            mantissa: ((raw << 8) as i32) >> 8,
+            exponent: (raw >> 24) as u8 as i8,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sfloat_roundtrip() {
+        let value = SFloat::new(-123, -2).unwrap();
+        let mut buf = [0; 2];
+        assert_eq!(value.to_gatt(&mut buf).unwrap(), 2);
+        assert_eq!(buf, [0x85, 0xEF]);
+        assert_eq!(SFloat::from_gatt(&buf).unwrap(), value);
+        let diff = value.to_f32() + 1.23;
+        assert!(diff > -1e-6 && diff < 1e-6);
+        assert!(SFloat::from_gatt(&[0xFF, 0x07]).unwrap().to_f32().is_nan());
+        assert!(SFloat::new(2048, 0).is_err());
+    }
+
+    #[test]
+    fn float_roundtrip() {
+        let value = Float::new(-36_600, -3).unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(value.to_gatt(&mut buf).unwrap(), 4);
+        assert_eq!(Float::from_gatt(&buf).unwrap(), value);
+        let diff = value.to_f64() + 36.6;
+        assert!(diff > -1e-9 && diff < 1e-9);
+        assert_eq!(
+            Float::from_gatt(&[0xFE, 0xFF, 0x7F, 0x00]).unwrap().to_f64(),
+            f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn variable_length() {
+        let s: String<8> = String::try_from("abc").unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(s.to_gatt(&mut buf).unwrap(), 3);
+        assert_eq!(String::<8>::from_gatt(&buf[..3]).unwrap(), s);
+        assert!(String::<2>::from_gatt(&buf[..3]).is_err());
+        assert!(u16::from_gatt(&buf[..3]).is_err());
+        assert!(bool::from_gatt(&[2]).is_err());
+    }
+}
@@ -6,6 +6,15 @@ pub(crate) const L2CAP_CID_ATT: u16 = 0x0004;
 pub(crate) const L2CAP_CID_LE_U_SIGNAL: u16 = 0x0005;
 pub(crate) const L2CAP_CID_DYN_START: u16 = 0x0040;
 
+/// Protocol/Service Multiplexer used by Enhanced ATT bearers.
+pub(crate) const EATT_PSM: u16 = 0x0027;
+
+/// Maximum number of channels opened by a single credit based connection request.
+pub(crate) const L2CAP_ENHANCED_MAX_CHANNELS: usize = 5;
+
+/// Smallest MTU and MPS of channels in enhanced credit based flow control mode.
+pub(crate) const L2CAP_ENHANCED_MIN_MTU: u16 = 64;
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
@@ -129,7 +138,7 @@ impl L2capSignal for LeCreditConnReq {
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u16)]
 pub enum LeCreditConnResultCode {
     Success = 0x0000,
@@ -142,6 +151,8 @@ pub enum LeCreditConnResultCode {
     InvalidSourceId = 0x0009,
     ScidAlreadyAllocated = 0x000A,
     UnacceptableParameters = 0x000B,
+    /// Only used in enhanced credit based flow control mode.
+    InvalidParameters = 0x000C,
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -167,6 +178,106 @@ unsafe impl FixedSizeValue for LeCreditConnRes {
     }
 }
 
+/// Credit based connection request, followed by the source CIDs of up to 5 channels to open.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CreditConnReq {
+    pub psm: u16,
+    pub mtu: u16,
+    pub mps: u16,
+    pub credits: u16,
+}
+
+unsafe impl FixedSizeValue for CreditConnReq {
+    fn is_valid(data: &[u8]) -> bool {
+        true
+    }
+}
+
+impl L2capSignal for CreditConnReq {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CreditConnReq
+    }
+}
+
+/// Credit based connection response, followed by the destination CIDs of the channels, 0 for those refused.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CreditConnRes {
+    pub mtu: u16,
+    pub mps: u16,
+    pub credits: u16,
+    pub result: LeCreditConnResultCode,
+}
+
+unsafe impl FixedSizeValue for CreditConnRes {
+    fn is_valid(data: &[u8]) -> bool {
+        matches!(
+            u16::from_le_bytes([data[6], data[7]]),
+            0x0000 | 0x0002 | 0x0004..=0x000C
+        )
+    }
+}
+
+impl L2capSignal for CreditConnRes {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CreditConnRes
+    }
+}
+
+/// Credit based reconfigure request, followed by the CIDs of the channels on the requesting side.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CreditConnReconfigReq {
+    pub mtu: u16,
+    pub mps: u16,
+}
+
+unsafe impl FixedSizeValue for CreditConnReconfigReq {
+    fn is_valid(data: &[u8]) -> bool {
+        true
+    }
+}
+
+impl L2capSignal for CreditConnReconfigReq {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CreditConnReconfigReq
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+pub enum CreditConnReconfigResultCode {
+    Success = 0x0000,
+    MtuReductionNotAllowed = 0x0001,
+    MpsReductionNotAllowed = 0x0002,
+    InvalidDestinationCid = 0x0003,
+    UnacceptableParameters = 0x0004,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CreditConnReconfigRes {
+    pub result: CreditConnReconfigResultCode,
+}
+
+unsafe impl FixedSizeValue for CreditConnReconfigRes {
+    fn is_valid(data: &[u8]) -> bool {
+        u16::from_le_bytes([data[0], data[1]]) <= 0x0004
+    }
+}
+
+impl L2capSignal for CreditConnReconfigRes {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CreditConnReconfigRes
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -201,6 +312,76 @@ unsafe impl FixedSizeValue for CommandRejectRes {
     }
 }
 
+impl L2capSignal for CommandRejectRes {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CommandRejectRes
+    }
+}
+
+/// Connection parameter update request, sent by a peripheral.
+///
+/// Intervals are in units of 1.25 ms, and the supervision timeout in units of 10 ms.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ConnParamUpdateReq {
+    pub interval_min: u16,
+    pub interval_max: u16,
+    pub latency: u16,
+    pub timeout: u16,
+}
+
+impl ConnParamUpdateReq {
+    /// Whether the parameters are within the ranges allowed by the specification.
+    pub fn in_range(&self) -> bool {
+        (6..=3200).contains(&self.interval_min)
+            && (self.interval_min..=3200).contains(&self.interval_max)
+            && self.latency <= 499
+            && (10..=3200).contains(&self.timeout)
+            // The supervision timeout must be longer than twice the effective connection interval
+            && 4 * self.timeout as u32 > (1 + self.latency as u32) * self.interval_max as u32
+    }
+}
+
+unsafe impl FixedSizeValue for ConnParamUpdateReq {
+    fn is_valid(data: &[u8]) -> bool {
+        true
+    }
+}
+
+impl L2capSignal for ConnParamUpdateReq {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::ConnParamUpdateReq
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+pub enum ConnParamUpdateResultCode {
+    Accepted = 0x0000,
+    Rejected = 0x0001,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ConnParamUpdateRes {
+    pub result: ConnParamUpdateResultCode,
+}
+
+unsafe impl FixedSizeValue for ConnParamUpdateRes {
+    fn is_valid(data: &[u8]) -> bool {
+        u16::from_le_bytes([data[0], data[1]]) <= 0x0001
+    }
+}
+
+impl L2capSignal for ConnParamUpdateRes {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::ConnParamUpdateRes
+    }
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
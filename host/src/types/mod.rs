@@ -2,4 +2,5 @@
 pub(crate) mod l2cap;
 pub(crate) mod primitives;
 
+pub mod gatt_traits;
 pub mod uuid;
@@ -17,19 +17,25 @@ impl FixedSize for u32 {
 
 impl Decode<'_> for u8 {
     fn decode(src: &[u8]) -> Result<Self, Error> {
-        Ok(src[0])
+        src.first().copied().ok_or(Error::InsufficientSpace)
     }
 }
 
 impl Decode<'_> for u16 {
     fn decode(src: &[u8]) -> Result<Self, Error> {
-        Ok(u16::from_le_bytes([src[0], src[1]]))
+        match src {
+            [a, b, ..] => Ok(u16::from_le_bytes([*a, *b])),
+            _ => Err(Error::InsufficientSpace),
+        }
     }
 }
 
 impl Decode<'_> for u32 {
     fn decode(src: &[u8]) -> Result<Self, Error> {
-        Ok(u32::from_le_bytes([src[0], src[1], src[2], src[3]]))
+        match src {
+            [a, b, c, d, ..] => Ok(u32::from_le_bytes([*a, *b, *c, *d])),
+            _ => Err(Error::InsufficientSpace),
+        }
     }
 }
 
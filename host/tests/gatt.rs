@@ -162,7 +162,7 @@ async fn gatt_client_server() {
                 tokio::time::sleep(Duration::from_secs(5)).await;
 
                 println!("[central] creating gatt client");
-                let mut client = adapter.gatt_client::<10, 128>(&conn).await.unwrap();
+                let mut client = adapter.gatt_client::<10>(&conn).await.unwrap();
 
                 println!("[central] discovering services");
                 let services = client.services_by_uuid(&SERVICE_UUID).await.unwrap();